@@ -0,0 +1,444 @@
+// キーフレームアニメーション. 補間の仕方は glTF の animation.sampler と同じ
+use crate::math::*;
+use crate::skeleton::Pose;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Interpolation {
+  Step,
+  // 回転は slerp
+  Linear,
+  // Hermite. 値はキーごとに (in tangent, 値, out tangent) の 3 つが並ぶ
+  CubicSpline,
+}
+
+#[derive(Clone, Debug)]
+pub enum AnimationValues {
+  Translation(Vec<Vec3>),
+  Rotation(Vec<Quat>),
+  Scale(Vec<Vec3>),
+  // morph target の重み. キーごとに target の数だけ並ぶ
+  Weights(Vec<f32>),
+}
+
+#[derive(Clone, Debug)]
+pub struct AnimationChannel {
+  // 動かすもの(Skeleton なら joint)の index
+  pub target: usize,
+  pub interpolation: Interpolation,
+  // 秒. 昇順
+  pub times: Vec<f32>,
+  pub values: AnimationValues,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct AnimationClip {
+  pub name: String,
+  pub channels: Vec<AnimationChannel>,
+}
+impl AnimationClip {
+  pub fn duration(&self) -> f32 {
+    self
+      .channels
+      .iter()
+      .filter_map(|channel| channel.times.last().copied())
+      .fold(0.0, f32::max)
+  }
+  // 範囲外の target は無視する. channel のない姿勢は poses のまま. Weights は sample_weights で読む
+  pub fn sample(&self, time: f32, poses: &mut [Pose]) {
+    for channel in &self.channels {
+      let pose = match poses.get_mut(channel.target) {
+        Some(pose) => pose,
+        None => continue,
+      };
+      let (times, interpolation) = (&channel.times, channel.interpolation);
+      match &channel.values {
+        AnimationValues::Translation(values) => {
+          if let Some(v) = sample_keys(times, values, interpolation, time, Vec3::lerp, hermite) {
+            pose.translation = v;
+          }
+        }
+        AnimationValues::Scale(values) => {
+          if let Some(v) = sample_keys(times, values, interpolation, time, Vec3::lerp, hermite) {
+            pose.scale = v;
+          }
+        }
+        AnimationValues::Rotation(values) => {
+          let slerp = |a: Quat, b: Quat, u: f32| a.slerp(b, u);
+          // 4 成分のまま補間して正規化する
+          let hermite_quat = |v0: Quat, b0: Quat, v1: Quat, a1: Quat, u: f32, dt: f32| {
+            let v = hermite(
+              Vec4::from(v0),
+              Vec4::from(b0),
+              Vec4::from(v1),
+              Vec4::from(a1),
+              u,
+              dt,
+            );
+            Quat::from_vec4(v)
+          };
+          if let Some(v) = sample_keys(times, values, interpolation, time, slerp, hermite_quat) {
+            pose.rotation = v.normalize();
+          }
+        }
+        AnimationValues::Weights(_) => {}
+      }
+    }
+  }
+  // target の Weights channel を weights に書く. 重みの数はキーの数から求める
+  pub fn sample_weights(&self, time: f32, target: usize, weights: &mut [f32]) {
+    for channel in &self.channels {
+      let values = match &channel.values {
+        AnimationValues::Weights(values) if channel.target == target => values,
+        _ => continue,
+      };
+      let stride = if channel.interpolation == Interpolation::CubicSpline {
+        3
+      } else {
+        1
+      };
+      let key_count = channel.times.len() * stride;
+      if key_count == 0 {
+        continue;
+      }
+      let n = values.len() / key_count;
+      let lerp = |a: f32, b: f32, u: f32| a + (b - a) * u;
+      for (k, weight) in weights.iter_mut().enumerate().take(n) {
+        // k 番目の target の値だけを抜き出す
+        let values: Vec<f32> = values.iter().skip(k).step_by(n).copied().collect();
+        if let Some(v) = sample_keys(
+          &channel.times,
+          &values,
+          channel.interpolation,
+          time,
+          lerp,
+          hermite,
+        ) {
+          *weight = v;
+        }
+      }
+    }
+  }
+  // target を付け替えたもの. None を返したものは捨てる
+  pub fn retarget(&self, f: impl Fn(usize) -> Option<usize>) -> Self {
+    Self {
+      name: self.name.clone(),
+      channels: self
+        .channels
+        .iter()
+        .filter_map(|channel| {
+          let target = f(channel.target)?;
+          Some(AnimationChannel {
+            target,
+            ..channel.clone()
+          })
+        })
+        .collect(),
+    }
+  }
+}
+
+fn hermite<T>(v0: T, b0: T, v1: T, a1: T, u: f32, dt: f32) -> T
+where
+  T: std::ops::Mul<f32, Output = T> + std::ops::Add<Output = T>,
+{
+  let u2 = u * u;
+  let u3 = u2 * u;
+  v0 * (2.0 * u3 - 3.0 * u2 + 1.0)
+    + b0 * ((u3 - 2.0 * u2 + u) * dt)
+    + v1 * (-2.0 * u3 + 3.0 * u2)
+    + a1 * ((u3 - u2) * dt)
+}
+// キーが足りなければ None
+fn sample_keys<T: Copy>(
+  times: &[f32],
+  values: &[T],
+  interpolation: Interpolation,
+  time: f32,
+  lerp: impl Fn(T, T, f32) -> T,
+  cubic: impl Fn(T, T, T, T, f32, f32) -> T,
+) -> Option<T> {
+  let stride = if interpolation == Interpolation::CubicSpline {
+    3
+  } else {
+    1
+  };
+  let count = times.len().min(values.len() / stride);
+  if count == 0 {
+    return None;
+  }
+  let value = |i: usize| values[i * stride + stride / 2];
+  if count == 1 || time.is_nan() || time <= times[0] {
+    return Some(value(0));
+  }
+  if time >= times[count - 1] {
+    return Some(value(count - 1));
+  }
+  // times[i] <= time < times[i + 1]. 昇順でなくても範囲内に収める
+  let i = times[..count]
+    .partition_point(|t| *t <= time)
+    .saturating_sub(1)
+    .min(count - 2);
+  let dt = times[i + 1] - times[i];
+  let u = if dt > 0.0 {
+    (time - times[i]) / dt
+  } else {
+    0.0
+  };
+  Some(match interpolation {
+    Interpolation::Step => value(i),
+    Interpolation::Linear => lerp(value(i), value(i + 1), u),
+    Interpolation::CubicSpline => cubic(
+      value(i),
+      values[i * 3 + 2],
+      value(i + 1),
+      values[(i + 1) * 3],
+      u,
+      dt,
+    ),
+  })
+}
+
+// 再生位置を持って clip を進めるもの
+#[derive(Clone, Debug)]
+pub struct AnimationPlayer {
+  pub clip: AnimationClip,
+  // 秒
+  pub time: f32,
+  pub speed: f32,
+  pub looping: bool,
+}
+impl AnimationPlayer {
+  pub fn new(clip: AnimationClip, looping: bool) -> Self {
+    Self {
+      clip,
+      time: 0.0,
+      speed: 1.0,
+      looping,
+    }
+  }
+  pub fn advance(&mut self, delta_sec: f32) {
+    let duration = self.clip.duration();
+    self.time += delta_sec * self.speed;
+    if self.looping && duration > 0.0 {
+      self.time = self.time.rem_euclid(duration);
+    } else {
+      self.time = self.time.clamp(0.0, duration);
+    }
+  }
+  pub fn is_finished(&self) -> bool {
+    if self.looping {
+      return false;
+    }
+    let duration = self.clip.duration();
+    if self.speed < 0.0 {
+      self.time <= 0.0
+    } else {
+      self.time >= duration
+    }
+  }
+  pub fn sample(&self, poses: &mut [Pose]) {
+    self.clip.sample(self.time, poses);
+  }
+  pub fn sample_weights(&self, target: usize, weights: &mut [f32]) {
+    self.clip.sample_weights(self.time, target, weights);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn channel(
+    interpolation: Interpolation,
+    times: &[f32],
+    values: AnimationValues,
+  ) -> AnimationChannel {
+    AnimationChannel {
+      target: 0,
+      interpolation,
+      times: times.to_vec(),
+      values,
+    }
+  }
+  fn sample_translation(channel: AnimationChannel, time: f32) -> Vec3 {
+    let clip = AnimationClip {
+      name: String::new(),
+      channels: vec![channel],
+    };
+    let mut poses = [Pose::default()];
+    clip.sample(time, &mut poses);
+    poses[0].translation
+  }
+  fn sample_f32(
+    interpolation: Interpolation,
+    times: &[f32],
+    values: &[f32],
+    time: f32,
+  ) -> Option<f32> {
+    let lerp = |a: f32, b: f32, u: f32| a + (b - a) * u;
+    sample_keys(times, values, interpolation, time, lerp, hermite)
+  }
+
+  #[test]
+  fn step_and_linear_boundaries() {
+    let times = [1.0, 2.0, 4.0];
+    let values = [10.0, 20.0, 40.0];
+    let step = |time| sample_f32(Interpolation::Step, &times, &values, time);
+    assert_eq!(step(0.0), Some(10.0));
+    assert_eq!(step(1.0), Some(10.0));
+    assert_eq!(step(1.99), Some(10.0));
+    // キーちょうどではそのキーの値
+    assert_eq!(step(2.0), Some(20.0));
+    assert_eq!(step(3.9), Some(20.0));
+    assert_eq!(step(4.0), Some(40.0));
+    assert_eq!(step(100.0), Some(40.0));
+    let linear = |time| sample_f32(Interpolation::Linear, &times, &values, time);
+    assert_eq!(linear(-1.0), Some(10.0));
+    assert_eq!(linear(1.5), Some(15.0));
+    assert_eq!(linear(2.0), Some(20.0));
+    assert_eq!(linear(3.0), Some(30.0));
+    assert_eq!(linear(5.0), Some(40.0));
+    assert_eq!(linear(f32::NAN), Some(10.0));
+    // 同じ時刻のキーが並んでいたら後ろのもの
+    assert_eq!(
+      sample_f32(
+        Interpolation::Linear,
+        &[0.0, 1.0, 1.0, 2.0],
+        &[0.0, 1.0, 5.0, 6.0],
+        1.0
+      ),
+      Some(5.0)
+    );
+    // 昇順でなくても panic しない
+    assert!(sample_f32(Interpolation::Linear, &[0.0, 3.0, 1.0, 4.0], &[0.0; 4], 2.0).is_some());
+    // キーが足りなければ None, 値が足りない分のキーは使わない
+    assert_eq!(sample_f32(Interpolation::Linear, &[], &[], 0.0), None);
+    assert_eq!(
+      sample_f32(Interpolation::Linear, &[0.0, 1.0], &[3.0], 0.5),
+      Some(3.0)
+    );
+    assert_eq!(
+      sample_f32(Interpolation::CubicSpline, &[0.0], &[1.0, 2.0], 0.0),
+      None
+    );
+  }
+
+  #[test]
+  fn cubic_spline() {
+    // キーごとに (in tangent, 値, out tangent). 使われない tangent は大きな値にしておく
+    let times = [0.0, 2.0, 3.0];
+    let values = [100.0, 0.0, 2.0, -4.0, 1.0, 100.0, 100.0, 5.0, 100.0];
+    let cubic = |time| sample_f32(Interpolation::CubicSpline, &times, &values, time).unwrap();
+    assert_eq!(cubic(-1.0), 0.0);
+    assert_eq!(cubic(0.0), 0.0);
+    assert_eq!(cubic(2.0), 1.0);
+    assert_eq!(cubic(3.0), 5.0);
+    // u = 0.5, dt = 2. 0 * 0.5 + 2 * 0.125 * 2 + 1 * 0.5 + -4 * -0.125 * 2
+    assert!((cubic(1.0) - 2.0).abs() < 1e-6);
+    // 両端の微分は tangent と一致する
+    let h = 1e-3;
+    assert!(((cubic(h) - cubic(0.0)) / h - 2.0).abs() < 1e-2);
+    assert!(((cubic(2.0) - cubic(2.0 - h)) / h - -4.0).abs() < 1e-2);
+
+    let translation = channel(
+      Interpolation::CubicSpline,
+      &[0.0, 1.0],
+      AnimationValues::Translation(vec![
+        Vec3::splat(100.0),
+        Vec3::ZERO,
+        Vec3::X,
+        Vec3::ZERO,
+        Vec3::Y,
+        Vec3::splat(100.0),
+      ]),
+    );
+    // 0.125 * X + 0.5 * Y
+    assert!(sample_translation(translation, 0.5).abs_diff_eq(Vec3::new(0.125, 0.5, 0.0), 1e-6));
+  }
+
+  #[test]
+  fn rotation_and_weights() {
+    let half = Quat::from_rotation_z(std::f32::consts::FRAC_PI_2);
+    let clip = AnimationClip {
+      name: String::new(),
+      channels: vec![
+        AnimationChannel {
+          target: 1,
+          ..channel(
+            Interpolation::Linear,
+            &[0.0, 1.0],
+            AnimationValues::Rotation(vec![Quat::IDENTITY, half]),
+          )
+        },
+        // 範囲外の target は無視
+        AnimationChannel {
+          target: 5,
+          ..channel(
+            Interpolation::Step,
+            &[0.0],
+            AnimationValues::Scale(vec![Vec3::ZERO]),
+          )
+        },
+        // 2 つの target の重み. CubicSpline は (in, 値, out) ごとに target の数だけ並ぶ
+        channel(
+          Interpolation::CubicSpline,
+          &[0.0, 1.0],
+          AnimationValues::Weights(vec![
+            9.0, 9.0, 0.0, 1.0, 0.0, 0.0, //
+            0.0, 0.0, 1.0, 0.0, 9.0, 9.0,
+          ]),
+        ),
+      ],
+    };
+    assert_eq!(clip.duration(), 1.0);
+    let mut poses = [Pose::default(); 2];
+    clip.sample(0.5, &mut poses);
+    assert_eq!(poses[0], Pose::default());
+    let expected = Quat::from_rotation_z(std::f32::consts::FRAC_PI_4);
+    assert!(poses[1].rotation.abs_diff_eq(expected, 1e-6));
+    let mut weights = [0.0; 3];
+    clip.sample_weights(0.5, 0, &mut weights);
+    assert!((weights[0] - 0.5).abs() < 1e-6);
+    assert!((weights[1] - 0.5).abs() < 1e-6);
+    // target の数より多い分は触らない
+    assert_eq!(weights[2], 0.0);
+    clip.sample_weights(1.0, 0, &mut weights);
+    assert_eq!(weights[..2], [1.0, 0.0]);
+
+    let retargeted = clip.retarget(|target| (target == 1).then_some(0));
+    assert_eq!(retargeted.channels.len(), 1);
+    assert_eq!(retargeted.channels[0].target, 0);
+  }
+
+  #[test]
+  fn player() {
+    let clip = AnimationClip {
+      name: String::new(),
+      channels: vec![channel(
+        Interpolation::Linear,
+        &[0.0, 2.0],
+        AnimationValues::Translation(vec![Vec3::ZERO, Vec3::X * 2.0]),
+      )],
+    };
+    let mut looping = AnimationPlayer::new(clip.clone(), true);
+    looping.advance(2.5);
+    assert_eq!(looping.time, 0.5);
+    looping.speed = -1.0;
+    looping.advance(1.0);
+    assert_eq!(looping.time, 1.5);
+    assert!(!looping.is_finished());
+    let mut once = AnimationPlayer::new(clip, false);
+    once.advance(1.0);
+    let mut poses = [Pose::default()];
+    once.sample(&mut poses);
+    assert_eq!(poses[0].translation, Vec3::X);
+    once.advance(5.0);
+    assert_eq!(once.time, 2.0);
+    assert!(once.is_finished());
+    once.speed = -1.0;
+    assert!(!once.is_finished());
+    once.advance(3.0);
+    assert_eq!(once.time, 0.0);
+    assert!(once.is_finished());
+  }
+}
@@ -0,0 +1,1343 @@
+// glTF 2.0 (.gltf + .bin / .glb) の読み込み
+// https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html
+// GPU に渡す前の CPU 側のデータまで. 画像のデコードは呼び出し側(ブラウザ)に任せる
+use crate::animation::*;
+use crate::json::*;
+use crate::math::*;
+use crate::skeleton::*;
+
+const GLB_MAGIC: u32 = 0x4654_6c67; // "glTF"
+const GLB_CHUNK_JSON: u32 = 0x4e4f_534a; // "JSON"
+const GLB_CHUNK_BIN: u32 = 0x004e_4942; // "BIN\0"
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum GltfError {
+  InvalidGlb,
+  Json(JsonError),
+  UnsupportedVersion(String),
+  // 読めなかった buffer の index
+  MissingBuffer(usize),
+  InvalidAccessor(usize),
+  InvalidBase64,
+  Unsupported(&'static str),
+}
+impl From<JsonError> for GltfError {
+  fn from(e: JsonError) -> Self {
+    Self::Json(e)
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GltfPrimitiveMode {
+  Points = 0,
+  Lines = 1,
+  LineLoop = 2,
+  LineStrip = 3,
+  Triangles = 4,
+  TriangleStrip = 5,
+  TriangleFan = 6,
+}
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GltfAlphaMode {
+  Opaque,
+  Mask,
+  Blend,
+}
+
+// 使わない属性は空の Vec になる
+#[derive(Clone, Debug)]
+pub struct GltfPrimitive {
+  pub mode: GltfPrimitiveMode,
+  pub positions: Vec<Vec3>,
+  pub normals: Vec<Vec3>,
+  // w は bitangent の向き(±1)
+  pub tangents: Vec<Vec4>,
+  pub uvs: Vec<Vec2>,
+  pub colors: Vec<Vec4>,
+  // JOINTS_0 / WEIGHTS_0. joints は skin.joints の index
+  pub joints: Vec<[u16; 4]>,
+  pub weights: Vec<Vec4>,
+  pub indices: Option<Vec<u32>>,
+  pub material: Option<usize>,
+  pub targets: Vec<GltfMorphTarget>,
+}
+// morph target. 元の属性への差分. ないものは空
+#[derive(Clone, Debug)]
+pub struct GltfMorphTarget {
+  pub positions: Vec<Vec3>,
+  pub normals: Vec<Vec3>,
+}
+#[derive(Clone, Debug)]
+pub struct GltfMesh {
+  pub name: String,
+  pub primitives: Vec<GltfPrimitive>,
+  // morph target の既定の重み. 指定がなければ 0 で埋めてある
+  pub weights: Vec<f32>,
+}
+#[derive(Clone, Debug)]
+pub struct GltfNode {
+  pub name: String,
+  pub mesh: Option<usize>,
+  pub skin: Option<usize>,
+  pub children: Vec<usize>,
+  // mesh の weights を上書きする. 空なら mesh のもの
+  pub weights: Vec<f32>,
+  // matrix で指定されていても分解して持つ
+  pub translation: Vec3,
+  pub rotation: Quat,
+  pub scale: Vec3,
+}
+impl GltfNode {
+  pub fn local_matrix(&self) -> Mat4 {
+    Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+  }
+}
+#[derive(Clone, Debug)]
+pub struct GltfSkin {
+  pub name: String,
+  // node の index
+  pub joints: Vec<usize>,
+  // なければ単位行列で埋めてある
+  pub inverse_bind_matrices: Vec<Mat4>,
+  pub skeleton: Option<usize>,
+}
+#[derive(Clone, Debug)]
+pub struct GltfScene {
+  pub name: String,
+  pub nodes: Vec<usize>,
+}
+#[derive(Clone, Copy, Debug)]
+pub struct GltfTextureRef {
+  pub index: usize,
+  // TEXCOORD_n の n
+  pub tex_coord: usize,
+}
+#[derive(Clone, Debug)]
+pub struct GltfMaterial {
+  pub name: String,
+  pub base_color_factor: Vec4,
+  pub base_color_texture: Option<GltfTextureRef>,
+  pub metallic_factor: f32,
+  pub roughness_factor: f32,
+  // g: roughness, b: metallic
+  pub metallic_roughness_texture: Option<GltfTextureRef>,
+  pub normal_texture: Option<GltfTextureRef>,
+  pub normal_scale: f32,
+  pub occlusion_texture: Option<GltfTextureRef>,
+  pub occlusion_strength: f32,
+  pub emissive_texture: Option<GltfTextureRef>,
+  pub emissive_factor: Vec3,
+  pub alpha_mode: GltfAlphaMode,
+  pub alpha_cutoff: f32,
+  pub double_sided: bool,
+}
+impl Default for GltfMaterial {
+  fn default() -> Self {
+    Self {
+      name: String::new(),
+      base_color_factor: Vec4::ONE,
+      base_color_texture: None,
+      metallic_factor: 1.0,
+      roughness_factor: 1.0,
+      metallic_roughness_texture: None,
+      normal_texture: None,
+      normal_scale: 1.0,
+      occlusion_texture: None,
+      occlusion_strength: 1.0,
+      emissive_texture: None,
+      emissive_factor: Vec3::ZERO,
+      alpha_mode: GltfAlphaMode::Opaque,
+      alpha_cutoff: 0.5,
+      double_sided: false,
+    }
+  }
+}
+#[derive(Clone, Copy, Debug)]
+pub struct GltfTexture {
+  pub source: Option<usize>,
+  pub sampler: Option<usize>,
+}
+// filter / wrap は GL の定数そのまま
+#[derive(Clone, Copy, Debug)]
+pub struct GltfSampler {
+  pub mag_filter: Option<u32>,
+  pub min_filter: Option<u32>,
+  pub wrap_s: u32,
+  pub wrap_t: u32,
+}
+#[derive(Clone, Debug)]
+pub enum GltfImage {
+  // 相対パスは .gltf の場所から解決する
+  Uri(String),
+  // data URI や bufferView に入っているもの. png / jpeg のまま
+  Embedded { mime_type: String, data: Vec<u8> },
+}
+
+#[derive(Clone, Debug)]
+pub struct Gltf {
+  pub scene: Option<usize>,
+  pub scenes: Vec<GltfScene>,
+  pub nodes: Vec<GltfNode>,
+  pub meshes: Vec<GltfMesh>,
+  pub materials: Vec<GltfMaterial>,
+  pub textures: Vec<GltfTexture>,
+  pub images: Vec<GltfImage>,
+  pub samplers: Vec<GltfSampler>,
+  pub skins: Vec<GltfSkin>,
+  // channel の target は node の index
+  pub animations: Vec<AnimationClip>,
+}
+
+impl Gltf {
+  pub fn parse_glb(data: &[u8]) -> Result<Self, GltfError> {
+    let u32_at = |offset: usize| -> Result<u32, GltfError> {
+      let end = offset.checked_add(4).ok_or(GltfError::InvalidGlb)?;
+      let bytes = data.get(offset..end).ok_or(GltfError::InvalidGlb)?;
+      Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    };
+    if u32_at(0)? != GLB_MAGIC || u32_at(4)? != 2 {
+      return Err(GltfError::InvalidGlb);
+    }
+    let length = (u32_at(8)? as usize).min(data.len());
+    let mut json = None;
+    let mut bin = None;
+    let mut offset = 12;
+    // wasm32 では chunk_length が大きいと桁あふれするので checked で足す
+    while length.saturating_sub(offset) >= 8 {
+      let chunk_length = u32_at(offset)? as usize;
+      let chunk_type = u32_at(offset + 4)?;
+      let chunk_end = (offset + 8)
+        .checked_add(chunk_length)
+        .filter(|end| *end <= length)
+        .ok_or(GltfError::InvalidGlb)?;
+      let chunk = &data[offset + 8..chunk_end];
+      match chunk_type {
+        GLB_CHUNK_JSON => json = Some(chunk),
+        GLB_CHUNK_BIN => bin = Some(chunk),
+        _ => {}
+      }
+      // chunk は 4byte 境界に揃っている
+      offset = chunk_end.checked_add(3).ok_or(GltfError::InvalidGlb)? & !3;
+    }
+    let json = json.ok_or(GltfError::InvalidGlb)?;
+    let json = std::str::from_utf8(json).map_err(|_| GltfError::InvalidGlb)?;
+    Self::parse_impl(json, Some(bin.unwrap_or(&[])), &|_| None)
+  }
+  // 外部の .bin などは load_uri で渡す. uri は percent-decode していないそのままの文字列
+  pub fn parse_gltf(
+    json: &str,
+    load_uri: &dyn Fn(&str) -> Option<Vec<u8>>,
+  ) -> Result<Self, GltfError> {
+    Self::parse_impl(json, None, load_uri)
+  }
+  // 先に読み込んでおく必要のある buffer の uri. data URI は含まない
+  pub fn external_buffer_uris(json: &str) -> Result<Vec<String>, GltfError> {
+    let root = Json::parse(json)?;
+    Ok(
+      root
+        .get("buffers")
+        .members()
+        .iter()
+        .filter_map(|buffer| buffer.get("uri").as_str())
+        .filter(|uri| !uri.starts_with("data:"))
+        .map(|uri| uri.to_string())
+        .collect(),
+    )
+  }
+  fn parse_impl(
+    json: &str,
+    glb_bin: Option<&[u8]>,
+    load_uri: &dyn Fn(&str) -> Option<Vec<u8>>,
+  ) -> Result<Self, GltfError> {
+    let root = Json::parse(json)?;
+    let version = root.get("asset").get("version").as_str().unwrap_or("");
+    if !version.starts_with("2.") {
+      return Err(GltfError::UnsupportedVersion(version.to_string()));
+    }
+    let mut buffers = Vec::new();
+    for (i, buffer) in root.get("buffers").members().iter().enumerate() {
+      let data = match buffer.get("uri").as_str() {
+        Some(uri) => match decode_data_uri(uri) {
+          Some(decoded) => decoded?.1,
+          None => load_uri(uri).ok_or(GltfError::MissingBuffer(i))?,
+        },
+        // uri のない最初の buffer は GLB の BIN chunk
+        None => glb_bin
+          .filter(|_| i == 0)
+          .ok_or(GltfError::MissingBuffer(i))?
+          .to_vec(),
+      };
+      buffers.push(data);
+    }
+    let reader = AccessorReader {
+      root: &root,
+      buffers: &buffers,
+    };
+    let mut meshes = Vec::new();
+    for mesh in root.get("meshes").members() {
+      let mut primitives = Vec::new();
+      for primitive in mesh.get("primitives").members() {
+        primitives.push(reader.primitive(primitive)?);
+      }
+      let target_count = primitives.iter().map(|p| p.targets.len()).max();
+      let mut weights = f32_array(mesh.get("weights"));
+      weights.resize(target_count.unwrap_or(0).max(weights.len()), 0.0);
+      meshes.push(GltfMesh {
+        name: name_of(mesh),
+        primitives,
+        weights,
+      });
+    }
+    let mut images = Vec::new();
+    for image in root.get("images").members() {
+      images.push(reader.image(image)?);
+    }
+    let mut skins = Vec::new();
+    for skin in root.get("skins").members() {
+      skins.push(reader.skin(skin)?);
+    }
+    let mut animations = Vec::new();
+    for animation in root.get("animations").members() {
+      animations.push(reader.animation(animation)?);
+    }
+    Ok(Self {
+      scene: root.get("scene").as_usize(),
+      scenes: root
+        .get("scenes")
+        .members()
+        .iter()
+        .map(|scene| GltfScene {
+          name: name_of(scene),
+          nodes: usize_array(scene.get("nodes")),
+        })
+        .collect(),
+      nodes: root.get("nodes").members().iter().map(parse_node).collect(),
+      meshes,
+      materials: root
+        .get("materials")
+        .members()
+        .iter()
+        .map(parse_material)
+        .collect(),
+      textures: root
+        .get("textures")
+        .members()
+        .iter()
+        .map(|texture| GltfTexture {
+          source: texture.get("source").as_usize(),
+          sampler: texture.get("sampler").as_usize(),
+        })
+        .collect(),
+      images,
+      samplers: root
+        .get("samplers")
+        .members()
+        .iter()
+        .map(|sampler| GltfSampler {
+          mag_filter: sampler.get("magFilter").as_usize().map(|x| x as u32),
+          min_filter: sampler.get("minFilter").as_usize().map(|x| x as u32),
+          wrap_s: sampler.get("wrapS").as_usize().unwrap_or(10497) as u32,
+          wrap_t: sampler.get("wrapT").as_usize().unwrap_or(10497) as u32,
+        })
+        .collect(),
+      skins,
+      animations,
+    })
+  }
+  // 表示するシーンの root node. scene がなければ誰の子でもない node 全部
+  pub fn root_nodes(&self) -> Vec<usize> {
+    if let Some(scene) = self.scenes.get(self.scene.unwrap_or(0)) {
+      return scene.nodes.clone();
+    }
+    let mut is_child = vec![false; self.nodes.len()];
+    for node in &self.nodes {
+      for &child in &node.children {
+        if let Some(x) = is_child.get_mut(child) {
+          *x = true;
+        }
+      }
+    }
+    (0..self.nodes.len()).filter(|&i| !is_child[i]).collect()
+  }
+  // node の morph target の初期の重み. node で指定がなければ mesh のもの
+  pub fn morph_weights(&self, node: usize) -> Vec<f32> {
+    let node = match self.nodes.get(node) {
+      Some(node) => node,
+      None => return Vec::new(),
+    };
+    if !node.weights.is_empty() {
+      return node.weights.clone();
+    }
+    node
+      .mesh
+      .and_then(|mesh| self.meshes.get(mesh))
+      .map(|mesh| mesh.weights.clone())
+      .unwrap_or_default()
+  }
+  // 各 node のワールド行列. root_nodes から辿れないものは None
+  pub fn world_matrices(&self) -> Vec<Option<Mat4>> {
+    let mut result = vec![None; self.nodes.len()];
+    let mut stack: Vec<(usize, Mat4)> = self
+      .root_nodes()
+      .into_iter()
+      .map(|i| (i, Mat4::IDENTITY))
+      .collect();
+    while let Some((i, parent)) = stack.pop() {
+      let node = match self.nodes.get(i) {
+        Some(node) => node,
+        None => continue,
+      };
+      // 循環していたら打ち切る
+      if result[i].is_some() {
+        continue;
+      }
+      let world = parent * node.local_matrix();
+      result[i] = Some(world);
+      for &child in &node.children {
+        stack.push((child, world));
+      }
+    }
+    result
+  }
+  // skin の joint を並びそのままで Skeleton にする. 親は一番近い joint の祖先
+  // 骨格の外側の祖先の行列は root_matrix に入る
+  pub fn skeleton(&self, skin: usize) -> Option<Skeleton> {
+    let skin = self.skins.get(skin)?;
+    let mut parents: Vec<Option<usize>> = vec![None; self.nodes.len()];
+    for (i, node) in self.nodes.iter().enumerate() {
+      for &child in &node.children {
+        if let Some(parent) = parents.get_mut(child) {
+          *parent = Some(i);
+        }
+      }
+    }
+    let joint_of = |node: usize| skin.joints.iter().position(|j| *j == node);
+    let mut joints = Vec::new();
+    let mut root_parent = None;
+    let mut visited = vec![false; self.nodes.len()];
+    for (i, &node_index) in skin.joints.iter().enumerate() {
+      let node = self.nodes.get(node_index)?;
+      let mut parent = None;
+      let mut current = parents[node_index];
+      visited.iter_mut().for_each(|x| *x = false);
+      visited[node_index] = true;
+      while let Some(p) = current {
+        // 循環していたら打ち切る. 自身を親にしない
+        if visited[p] {
+          break;
+        }
+        if let Some(j) = joint_of(p) {
+          parent = Some(j);
+          break;
+        }
+        visited[p] = true;
+        current = parents[p];
+      }
+      if parent.is_none() && root_parent.is_none() {
+        root_parent = parents[node_index];
+      }
+      joints.push(Joint {
+        name: node.name.clone(),
+        parent,
+        inverse_bind_matrix: skin
+          .inverse_bind_matrices
+          .get(i)
+          .copied()
+          .unwrap_or(Mat4::IDENTITY),
+        rest_pose: Pose::new(node.translation, node.rotation, node.scale),
+      });
+    }
+    let mut skeleton = Skeleton::new(joints);
+    if let Some(p) = root_parent {
+      skeleton.root_matrix = self.world_matrices()[p].unwrap_or(Mat4::IDENTITY);
+    }
+    Some(skeleton)
+  }
+  // animation を skin の joint を target にしたものに変える
+  pub fn skin_animation(&self, skin: usize, animation: usize) -> Option<AnimationClip> {
+    let joints = &self.skins.get(skin)?.joints;
+    let clip = self.animations.get(animation)?;
+    Some(clip.retarget(|node| joints.iter().position(|j| *j == node)))
+  }
+}
+
+fn name_of(json: &Json) -> String {
+  json.get("name").as_str().unwrap_or("").to_string()
+}
+fn usize_array(json: &Json) -> Vec<usize> {
+  json.members().iter().filter_map(|x| x.as_usize()).collect()
+}
+fn f32_array(json: &Json) -> Vec<f32> {
+  json.members().iter().filter_map(|x| x.as_f32()).collect()
+}
+fn parse_node(node: &Json) -> GltfNode {
+  let (scale, rotation, translation) = match node.get("matrix").as_f32_array::<16>() {
+    // column-major
+    Some(m) => Mat4::from_cols_array(&m).to_scale_rotation_translation(),
+    None => (
+      node
+        .get("scale")
+        .as_f32_array::<3>()
+        .map(Vec3::from)
+        .unwrap_or(Vec3::ONE),
+      node
+        .get("rotation")
+        .as_f32_array::<4>()
+        .map(Quat::from_array)
+        .unwrap_or(Quat::IDENTITY),
+      node
+        .get("translation")
+        .as_f32_array::<3>()
+        .map(Vec3::from)
+        .unwrap_or(Vec3::ZERO),
+    ),
+  };
+  GltfNode {
+    name: name_of(node),
+    mesh: node.get("mesh").as_usize(),
+    skin: node.get("skin").as_usize(),
+    children: usize_array(node.get("children")),
+    weights: f32_array(node.get("weights")),
+    translation,
+    rotation,
+    scale,
+  }
+}
+fn parse_texture_ref(json: &Json) -> Option<GltfTextureRef> {
+  Some(GltfTextureRef {
+    index: json.get("index").as_usize()?,
+    tex_coord: json.get("texCoord").as_usize().unwrap_or(0),
+  })
+}
+fn parse_material(material: &Json) -> GltfMaterial {
+  let default = GltfMaterial::default();
+  let pbr = material.get("pbrMetallicRoughness");
+  GltfMaterial {
+    name: name_of(material),
+    base_color_factor: pbr
+      .get("baseColorFactor")
+      .as_f32_array::<4>()
+      .map(Vec4::from)
+      .unwrap_or(default.base_color_factor),
+    base_color_texture: parse_texture_ref(pbr.get("baseColorTexture")),
+    metallic_factor: pbr
+      .get("metallicFactor")
+      .as_f32()
+      .unwrap_or(default.metallic_factor),
+    roughness_factor: pbr
+      .get("roughnessFactor")
+      .as_f32()
+      .unwrap_or(default.roughness_factor),
+    metallic_roughness_texture: parse_texture_ref(pbr.get("metallicRoughnessTexture")),
+    normal_texture: parse_texture_ref(material.get("normalTexture")),
+    normal_scale: material
+      .get("normalTexture")
+      .get("scale")
+      .as_f32()
+      .unwrap_or(default.normal_scale),
+    occlusion_texture: parse_texture_ref(material.get("occlusionTexture")),
+    occlusion_strength: material
+      .get("occlusionTexture")
+      .get("strength")
+      .as_f32()
+      .unwrap_or(default.occlusion_strength),
+    emissive_texture: parse_texture_ref(material.get("emissiveTexture")),
+    emissive_factor: material
+      .get("emissiveFactor")
+      .as_f32_array::<3>()
+      .map(Vec3::from)
+      .unwrap_or(default.emissive_factor),
+    alpha_mode: match material.get("alphaMode").as_str() {
+      Some("MASK") => GltfAlphaMode::Mask,
+      Some("BLEND") => GltfAlphaMode::Blend,
+      _ => GltfAlphaMode::Opaque,
+    },
+    alpha_cutoff: material
+      .get("alphaCutoff")
+      .as_f32()
+      .unwrap_or(default.alpha_cutoff),
+    double_sided: material.get("doubleSided").as_bool().unwrap_or(false),
+  }
+}
+
+// data URI でなければ None
+fn decode_data_uri(uri: &str) -> Option<Result<(String, Vec<u8>), GltfError>> {
+  let rest = uri.strip_prefix("data:")?;
+  let (header, body) = match rest.split_once(',') {
+    Some(x) => x,
+    None => return Some(Err(GltfError::InvalidBase64)),
+  };
+  let mime_type = header.split(';').next().unwrap_or("").to_string();
+  if !header.ends_with(";base64") {
+    return Some(Err(GltfError::Unsupported("non-base64 data uri")));
+  }
+  Some(decode_base64(body).map(|data| (mime_type, data)))
+}
+fn decode_base64(text: &str) -> Result<Vec<u8>, GltfError> {
+  let mut result = Vec::with_capacity(text.len() / 4 * 3);
+  let mut buffer = 0u32;
+  let mut bits = 0;
+  for c in text.bytes() {
+    let value = match c {
+      b'A'..=b'Z' => c - b'A',
+      b'a'..=b'z' => c - b'a' + 26,
+      b'0'..=b'9' => c - b'0' + 52,
+      b'+' | b'-' => 62,
+      b'/' | b'_' => 63,
+      b'=' => break,
+      b' ' | b'\n' | b'\r' | b'\t' => continue,
+      _ => return Err(GltfError::InvalidBase64),
+    };
+    buffer = (buffer << 6) | value as u32;
+    bits += 6;
+    if bits >= 8 {
+      bits -= 8;
+      result.push((buffer >> bits) as u8);
+    }
+  }
+  Ok(result)
+}
+
+// bufferView のない accessor を 0 で埋めるときの上限(値の数)
+const MAX_ZERO_FILLED_VALUES: usize = 1 << 26;
+
+struct AccessorReader<'a> {
+  root: &'a Json,
+  buffers: &'a [Vec<u8>],
+}
+impl<'a> AccessorReader<'a> {
+  fn buffer_view(&self, index: usize) -> Option<(&'a [u8], Option<usize>)> {
+    let view = self.root.get("bufferViews").at(index);
+    let buffer = self.buffers.get(view.get("buffer").as_usize()?)?;
+    let offset = view.get("byteOffset").as_usize().unwrap_or(0);
+    let length = view.get("byteLength").as_usize()?;
+    let data = buffer.get(offset..offset.checked_add(length)?)?;
+    Some((data, view.get("byteStride").as_usize()))
+  }
+  // accessor の count 個の要素. 範囲外や桁あふれ, 要素より狭い byteStride なら None
+  // 最後の要素まで収まるか先に確かめるので, count が大きすぎても確保はしない
+  fn elements(
+    &self,
+    accessor: &Json,
+    view: usize,
+    count: usize,
+    element_size: usize,
+  ) -> Option<impl Iterator<Item = &'a [u8]>> {
+    let (data, stride) = self.buffer_view(view)?;
+    let offset = accessor.get("byteOffset").as_usize().unwrap_or(0);
+    let stride = stride.unwrap_or(element_size);
+    if stride < element_size {
+      return None;
+    }
+    if let Some(last) = count.checked_sub(1) {
+      let end = last
+        .checked_mul(stride)?
+        .checked_add(offset)?
+        .checked_add(element_size)?;
+      if end > data.len() {
+        return None;
+      }
+    }
+    Some((0..count).map(move |i| &data[offset + i * stride..offset + i * stride + element_size]))
+  }
+  // 要素ごとに components 個の f32. normalized な整数は 0..1 / -1..1 にする
+  fn read_f32(&self, index: usize) -> Result<(Vec<f32>, usize), GltfError> {
+    let error = GltfError::InvalidAccessor(index);
+    let accessor = self.root.get("accessors").at(index);
+    if !accessor.get("sparse").is_null() {
+      return Err(GltfError::Unsupported("sparse accessor"));
+    }
+    let count = accessor.get("count").as_usize().ok_or(error.clone())?;
+    let components = match accessor.get("type").as_str() {
+      Some("SCALAR") => 1,
+      Some("VEC2") => 2,
+      Some("VEC3") => 3,
+      Some("VEC4") | Some("MAT2") => 4,
+      Some("MAT3") => 9,
+      Some("MAT4") => 16,
+      _ => return Err(error),
+    };
+    let component_type = accessor
+      .get("componentType")
+      .as_usize()
+      .ok_or(error.clone())?;
+    let normalized = accessor.get("normalized").as_bool().unwrap_or(false);
+    let component_size = match component_type {
+      5120 | 5121 => 1,
+      5122 | 5123 => 2,
+      5125 | 5126 => 4,
+      _ => return Err(error),
+    };
+    let view = match accessor.get("bufferView").as_usize() {
+      Some(view) => view,
+      // bufferView がなければ全部 0. 元になるデータがないので count が大きすぎるものは弾く
+      None => {
+        let len = count
+          .checked_mul(components)
+          .filter(|len| *len <= MAX_ZERO_FILLED_VALUES)
+          .ok_or(error)?;
+        return Ok((vec![0.0; len], components));
+      }
+    };
+    let element_size = component_size * components;
+    let elements = self
+      .elements(accessor, view, count, element_size)
+      .ok_or(error)?;
+    let mut result = Vec::with_capacity(count * components);
+    for element in elements {
+      for c in element.chunks_exact(component_size) {
+        let value = match component_type {
+          5120 => {
+            let v = c[0] as i8 as f32;
+            if normalized {
+              (v / 127.0).max(-1.0)
+            } else {
+              v
+            }
+          }
+          5121 => {
+            let v = c[0] as f32;
+            if normalized {
+              v / 255.0
+            } else {
+              v
+            }
+          }
+          5122 => {
+            let v = i16::from_le_bytes([c[0], c[1]]) as f32;
+            if normalized {
+              (v / 32767.0).max(-1.0)
+            } else {
+              v
+            }
+          }
+          5123 => {
+            let v = u16::from_le_bytes([c[0], c[1]]) as f32;
+            if normalized {
+              v / 65535.0
+            } else {
+              v
+            }
+          }
+          5125 => u32::from_le_bytes(c.try_into().unwrap()) as f32,
+          _ => f32::from_le_bytes(c.try_into().unwrap()),
+        };
+        result.push(value);
+      }
+    }
+    Ok((result, components))
+  }
+  // index 用. f32 を経由すると 2^24 を超えた値が壊れるので別に読む
+  fn read_u32(&self, index: usize) -> Result<Vec<u32>, GltfError> {
+    let error = GltfError::InvalidAccessor(index);
+    let accessor = self.root.get("accessors").at(index);
+    let count = accessor.get("count").as_usize().ok_or(error.clone())?;
+    let component_size = match accessor.get("componentType").as_usize() {
+      Some(5121) => 1,
+      Some(5123) => 2,
+      Some(5125) => 4,
+      _ => return Err(error),
+    };
+    let view = accessor.get("bufferView").as_usize().ok_or(error.clone())?;
+    let elements = self
+      .elements(accessor, view, count, component_size)
+      .ok_or(error)?;
+    let mut result = Vec::with_capacity(count);
+    for c in elements {
+      result.push(match component_size {
+        1 => c[0] as u32,
+        2 => u16::from_le_bytes([c[0], c[1]]) as u32,
+        _ => u32::from_le_bytes(c.try_into().unwrap()),
+      });
+    }
+    Ok(result)
+  }
+  // accepted は属性として読める成分数. 違えば f に渡さずエラー
+  fn read_attribute<T>(
+    &self,
+    attributes: &Json,
+    key: &str,
+    accepted: &[usize],
+    f: impl Fn(&[f32]) -> T,
+  ) -> Result<Vec<T>, GltfError> {
+    match attributes.get(key).as_usize() {
+      Some(index) => {
+        let (values, components) = self.read_f32(index)?;
+        if !accepted.contains(&components) {
+          return Err(GltfError::InvalidAccessor(index));
+        }
+        Ok(values.chunks_exact(components).map(f).collect())
+      }
+      None => Ok(Vec::new()),
+    }
+  }
+  fn primitive(&self, primitive: &Json) -> Result<GltfPrimitive, GltfError> {
+    let attributes = primitive.get("attributes");
+    let mode = match primitive.get("mode").as_usize().unwrap_or(4) {
+      0 => GltfPrimitiveMode::Points,
+      1 => GltfPrimitiveMode::Lines,
+      2 => GltfPrimitiveMode::LineLoop,
+      3 => GltfPrimitiveMode::LineStrip,
+      5 => GltfPrimitiveMode::TriangleStrip,
+      6 => GltfPrimitiveMode::TriangleFan,
+      _ => GltfPrimitiveMode::Triangles,
+    };
+    Ok(GltfPrimitive {
+      mode,
+      positions: self.read_attribute(attributes, "POSITION", &[3], Vec3::from_slice)?,
+      normals: self.read_attribute(attributes, "NORMAL", &[3], Vec3::from_slice)?,
+      tangents: self.read_attribute(attributes, "TANGENT", &[4], Vec4::from_slice)?,
+      uvs: self.read_attribute(attributes, "TEXCOORD_0", &[2], Vec2::from_slice)?,
+      // VEC3 のこともある
+      colors: self.read_attribute(attributes, "COLOR_0", &[3, 4], |v| {
+        Vec4::new(v[0], v[1], v[2], v.get(3).copied().unwrap_or(1.0))
+      })?,
+      joints: self.read_attribute(attributes, "JOINTS_0", &[4], |v| {
+        [v[0] as u16, v[1] as u16, v[2] as u16, v[3] as u16]
+      })?,
+      weights: self.read_attribute(attributes, "WEIGHTS_0", &[4], Vec4::from_slice)?,
+      indices: match primitive.get("indices").as_usize() {
+        Some(index) => Some(self.read_u32(index)?),
+        None => None,
+      },
+      material: primitive.get("material").as_usize(),
+      targets: {
+        let mut targets = Vec::new();
+        for target in primitive.get("targets").members() {
+          targets.push(GltfMorphTarget {
+            positions: self.read_attribute(target, "POSITION", &[3], Vec3::from_slice)?,
+            normals: self.read_attribute(target, "NORMAL", &[3], Vec3::from_slice)?,
+          });
+        }
+        targets
+      },
+    })
+  }
+  fn skin(&self, skin: &Json) -> Result<GltfSkin, GltfError> {
+    let joints = usize_array(skin.get("joints"));
+    let inverse_bind_matrices = match skin.get("inverseBindMatrices").as_usize() {
+      Some(index) => {
+        let (values, components) = self.read_f32(index)?;
+        if components != 16 {
+          return Err(GltfError::InvalidAccessor(index));
+        }
+        values.chunks_exact(16).map(Mat4::from_cols_slice).collect()
+      }
+      None => vec![Mat4::IDENTITY; joints.len()],
+    };
+    Ok(GltfSkin {
+      name: name_of(skin),
+      joints,
+      inverse_bind_matrices,
+      skeleton: skin.get("skeleton").as_usize(),
+    })
+  }
+  // weights の target は node. その node の mesh の morph target の重みになる
+  fn animation(&self, animation: &Json) -> Result<AnimationClip, GltfError> {
+    let samplers = animation.get("samplers");
+    let mut channels = Vec::new();
+    for channel in animation.get("channels").members() {
+      let target = channel.get("target");
+      let node = match target.get("node").as_usize() {
+        Some(node) => node,
+        None => continue,
+      };
+      let sampler = samplers.at(channel.get("sampler").as_usize().unwrap_or(usize::MAX));
+      let (input, output) = match (
+        sampler.get("input").as_usize(),
+        sampler.get("output").as_usize(),
+      ) {
+        (Some(input), Some(output)) => (input, output),
+        _ => continue,
+      };
+      let interpolation = match sampler.get("interpolation").as_str() {
+        Some("STEP") => Interpolation::Step,
+        Some("CUBICSPLINE") => Interpolation::CubicSpline,
+        _ => Interpolation::Linear,
+      };
+      let (times, _) = self.read_f32(input)?;
+      let (values, components) = self.read_f32(output)?;
+      let values = match (target.get("path").as_str(), components) {
+        (Some("translation"), 3) => {
+          AnimationValues::Translation(values.chunks_exact(3).map(Vec3::from_slice).collect())
+        }
+        (Some("scale"), 3) => {
+          AnimationValues::Scale(values.chunks_exact(3).map(Vec3::from_slice).collect())
+        }
+        (Some("rotation"), 4) => {
+          AnimationValues::Rotation(values.chunks_exact(4).map(Quat::from_slice).collect())
+        }
+        (Some("weights"), 1) => AnimationValues::Weights(values),
+        _ => continue,
+      };
+      channels.push(AnimationChannel {
+        target: node,
+        interpolation,
+        times,
+        values,
+      });
+    }
+    Ok(AnimationClip {
+      name: name_of(animation),
+      channels,
+    })
+  }
+  fn image(&self, image: &Json) -> Result<GltfImage, GltfError> {
+    if let Some(uri) = image.get("uri").as_str() {
+      return match decode_data_uri(uri) {
+        Some(decoded) => {
+          let (mime_type, data) = decoded?;
+          Ok(GltfImage::Embedded { mime_type, data })
+        }
+        None => Ok(GltfImage::Uri(uri.to_string())),
+      };
+    }
+    let view = image
+      .get("bufferView")
+      .as_usize()
+      .ok_or(GltfError::Unsupported("image without uri and bufferView"))?;
+    let (data, _) = self
+      .buffer_view(view)
+      .ok_or(GltfError::Unsupported("invalid image bufferView"))?;
+    Ok(GltfImage::Embedded {
+      mime_type: image.get("mimeType").as_str().unwrap_or("").to_string(),
+      data: data.to_vec(),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn base64(data: &[u8]) -> String {
+    const TABLE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut result = String::new();
+    for chunk in data.chunks(3) {
+      let b = [
+        chunk[0],
+        *chunk.get(1).unwrap_or(&0),
+        *chunk.get(2).unwrap_or(&0),
+      ];
+      let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+      for i in 0..4 {
+        if i <= chunk.len() {
+          result.push(TABLE[(n >> (18 - 6 * i) & 63) as usize] as char);
+        } else {
+          result.push('=');
+        }
+      }
+    }
+    result
+  }
+  fn f32_bytes(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|x| x.to_le_bytes()).collect()
+  }
+  fn chunk(kind: u32, data: &[u8], pad: u8) -> Vec<u8> {
+    let mut data = data.to_vec();
+    data.resize((data.len() + 3) & !3, pad);
+    let mut result = (data.len() as u32).to_le_bytes().to_vec();
+    result.extend_from_slice(&kind.to_le_bytes());
+    result.extend_from_slice(&data);
+    result
+  }
+  fn glb(chunks: &[Vec<u8>]) -> Vec<u8> {
+    let body: Vec<u8> = chunks.concat();
+    let mut result = GLB_MAGIC.to_le_bytes().to_vec();
+    result.extend_from_slice(&2u32.to_le_bytes());
+    result.extend_from_slice(&(12 + body.len() as u32).to_le_bytes());
+    result.extend_from_slice(&body);
+    result
+  }
+  const TRIANGLE_JSON: &str = r#"{
+    "asset": {"version": "2.0"},
+    "buffers": [{"byteLength": 36}],
+    "bufferViews": [{"buffer": 0, "byteLength": 36}],
+    "accessors": [{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"}],
+    "meshes": [{"primitives": [{"attributes": {"POSITION": 0}}]}]
+  }"#;
+  fn triangle_bin() -> Vec<u8> {
+    f32_bytes(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0])
+  }
+  fn reader_test<R>(json: &str, buffer: &[u8], f: impl FnOnce(&AccessorReader) -> R) -> R {
+    let root = Json::parse(json).unwrap();
+    let buffers = vec![buffer.to_vec()];
+    f(&AccessorReader {
+      root: &root,
+      buffers: &buffers,
+    })
+  }
+
+  #[test]
+  fn glb_chunks() {
+    // 知らない chunk は飛ばす. JSON の詰め物は空白
+    let data = glb(&[
+      chunk(GLB_CHUNK_JSON, TRIANGLE_JSON.as_bytes(), b' '),
+      chunk(0x1234_5678, &[1, 2, 3], 0),
+      chunk(GLB_CHUNK_BIN, &triangle_bin(), 0),
+    ]);
+    let gltf = Gltf::parse_glb(&data).unwrap();
+    let positions = &gltf.meshes[0].primitives[0].positions;
+    assert_eq!(positions, &vec![Vec3::ZERO, Vec3::X, Vec3::Y]);
+    assert_eq!(
+      gltf.meshes[0].primitives[0].mode,
+      GltfPrimitiveMode::Triangles
+    );
+    assert!(gltf.meshes[0].primitives[0].indices.is_none());
+  }
+
+  #[test]
+  fn glb_invalid() {
+    let json = chunk(GLB_CHUNK_JSON, TRIANGLE_JSON.as_bytes(), b' ');
+    let bin = chunk(GLB_CHUNK_BIN, &triangle_bin(), 0);
+    let valid = glb(&[json.clone(), bin.clone()]);
+    let parse = |data: &[u8]| Gltf::parse_glb(data).err();
+    let mut wrong_magic = valid.clone();
+    wrong_magic[0] = b'x';
+    assert_eq!(parse(&wrong_magic), Some(GltfError::InvalidGlb));
+    let mut wrong_version = valid.clone();
+    wrong_version[4] = 1;
+    assert_eq!(parse(&wrong_version), Some(GltfError::InvalidGlb));
+    assert_eq!(parse(&valid[..8]), Some(GltfError::InvalidGlb));
+    // chunk が途中で切れている
+    assert_eq!(
+      parse(&valid[..valid.len() - 4]),
+      Some(GltfError::InvalidGlb)
+    );
+    // header の長さより外の chunk
+    let mut short_header = valid.clone();
+    short_header[8..12].copy_from_slice(&(12 + json.len() as u32 + 8).to_le_bytes());
+    assert_eq!(parse(&short_header), Some(GltfError::InvalidGlb));
+    // 桁あふれするほど大きな chunk_length
+    for length in [u32::MAX, u32::MAX - 7, 0x8000_0000] {
+      let mut oversized = valid.clone();
+      oversized[12..16].copy_from_slice(&length.to_le_bytes());
+      assert_eq!(parse(&oversized), Some(GltfError::InvalidGlb));
+    }
+    // JSON chunk がない
+    assert_eq!(parse(&glb(&[bin])), Some(GltfError::InvalidGlb));
+    assert_eq!(
+      parse(&glb(&[chunk(GLB_CHUNK_JSON, &[0xff, 0xfe], b' ')])),
+      Some(GltfError::InvalidGlb)
+    );
+    // BIN が足りなければ accessor のエラー
+    let small_bin = glb(&[json, chunk(GLB_CHUNK_BIN, &[0; 8], 0)]);
+    assert_eq!(parse(&small_bin), Some(GltfError::InvalidAccessor(0)));
+  }
+
+  #[test]
+  fn attribute_components() {
+    let json = |attribute: &str, kind: &str| {
+      let attributes = if attribute == "POSITION" {
+        r#""POSITION": 1"#.to_string()
+      } else {
+        format!(r#""POSITION": 0, "{}": 1"#, attribute)
+      };
+      format!(
+        r#"{{
+          "asset": {{"version": "2.0"}},
+          "buffers": [{{"byteLength": 48}}],
+          "bufferViews": [{{"buffer": 0, "byteLength": 48}}],
+          "accessors": [
+            {{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"}},
+            {{"bufferView": 0, "componentType": 5126, "count": 3, "type": "{}"}}
+          ],
+          "meshes": [{{"primitives": [{{"attributes": {{{}}}}}]}}]
+        }}"#,
+        kind, attributes
+      )
+    };
+    let parse = |attribute: &str, kind: &str| {
+      let data = glb(&[
+        chunk(GLB_CHUNK_JSON, json(attribute, kind).as_bytes(), b' '),
+        chunk(GLB_CHUNK_BIN, &[0; 48], 0),
+      ]);
+      Gltf::parse_glb(&data).map(|gltf| gltf.meshes[0].primitives[0].clone())
+    };
+    // 成分数が足りないものは panic せずにエラー
+    for (attribute, kind) in [
+      ("POSITION", "SCALAR"),
+      ("NORMAL", "VEC2"),
+      ("TANGENT", "VEC3"),
+      ("TEXCOORD_0", "SCALAR"),
+      ("COLOR_0", "VEC2"),
+      ("JOINTS_0", "VEC3"),
+      ("WEIGHTS_0", "SCALAR"),
+    ] {
+      assert_eq!(
+        parse(attribute, kind).err(),
+        Some(GltfError::InvalidAccessor(1)),
+        "{} {}",
+        attribute,
+        kind
+      );
+    }
+    let colors = parse("COLOR_0", "VEC3").unwrap().colors;
+    assert_eq!(colors, vec![Vec4::new(0.0, 0.0, 0.0, 1.0); 3]);
+    assert_eq!(parse("COLOR_0", "VEC4").unwrap().colors.len(), 3);
+    assert_eq!(parse("TEXCOORD_0", "VEC2").unwrap().uvs.len(), 3);
+  }
+
+  #[test]
+  fn skeleton_cycle() {
+    let json = r#"{
+      "asset": {"version": "2.0"},
+      "nodes": [
+        {"name": "a", "children": [1, 2]},
+        {"name": "b", "children": [0]},
+        {"name": "leaf", "translation": [1, 0, 0]},
+        {"name": "c", "children": [4]},
+        {"name": "d", "children": [3, 5]},
+        {"name": "joint"}
+      ],
+      "skins": [{"joints": [2]}, {"joints": [5, 3]}]
+    }"#;
+    let gltf = Gltf::parse_gltf(json, &|_| None).unwrap();
+    // joint でない祖先が循環していても止まる
+    let skeleton = gltf.skeleton(0).unwrap();
+    assert_eq!(skeleton.joints.len(), 1);
+    assert_eq!(skeleton.joints[0].parent, None);
+    let skeleton = gltf.skeleton(1).unwrap();
+    assert_eq!(skeleton.joints[0].parent, Some(1));
+    assert_eq!(skeleton.joints[1].parent, None);
+    assert_eq!(skeleton.world_matrices(&skeleton.rest_poses()).len(), 2);
+    assert!(gltf.skeleton(2).is_none());
+  }
+
+  #[test]
+  fn version() {
+    let json = TRIANGLE_JSON.replace("2.0", "1.0");
+    assert_eq!(
+      Gltf::parse_gltf(&json, &|_| None).err(),
+      Some(GltfError::UnsupportedVersion("1.0".to_string()))
+    );
+  }
+
+  #[test]
+  fn interleaved_stride() {
+    // position(vec3) + uv(vec2) を 20byte ごとに. bufferView は 4byte 先から
+    let mut buffer = vec![0xaa; 4];
+    for i in 0..3 {
+      let i = i as f32;
+      buffer.extend(f32_bytes(&[i, i + 0.5, i + 0.25, i * 2.0, i * 3.0]));
+    }
+    let json = r#"{
+      "bufferViews": [{"buffer": 0, "byteOffset": 4, "byteLength": 60, "byteStride": 20}],
+      "accessors": [
+        {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"},
+        {"bufferView": 0, "byteOffset": 12, "componentType": 5126, "count": 3, "type": "VEC2"},
+        {"bufferView": 0, "byteOffset": 12, "componentType": 5126, "count": 4, "type": "VEC2"},
+        {"bufferView": 0, "componentType": 5126, "count": 3, "type": "MAT4"}
+      ]
+    }"#;
+    reader_test(json, &buffer, |reader| {
+      let (positions, components) = reader.read_f32(0).unwrap();
+      assert_eq!(components, 3);
+      assert_eq!(
+        positions,
+        vec![0.0, 0.5, 0.25, 1.0, 1.5, 1.25, 2.0, 2.5, 2.25]
+      );
+      let (uvs, _) = reader.read_f32(1).unwrap();
+      assert_eq!(uvs, vec![0.0, 0.0, 2.0, 3.0, 4.0, 6.0]);
+      // 4 つ目は bufferView の外
+      assert_eq!(reader.read_f32(2), Err(GltfError::InvalidAccessor(2)));
+      // 要素(64byte)より狭い byteStride
+      assert_eq!(reader.read_f32(3), Err(GltfError::InvalidAccessor(3)));
+    });
+  }
+
+  #[test]
+  fn normalized_integers() {
+    let mut buffer = vec![0x80, 0x81, 0x00, 0x7f, 0x00, 0xff, 0x05, 0x00];
+    for v in [i16::MIN, i16::MAX] {
+      buffer.extend(v.to_le_bytes());
+    }
+    for v in [0u16, u16::MAX] {
+      buffer.extend(v.to_le_bytes());
+    }
+    let json = r#"{
+      "bufferViews": [{"buffer": 0, "byteLength": 16}],
+      "accessors": [
+        {"bufferView": 0, "componentType": 5120, "normalized": true, "count": 4, "type": "SCALAR"},
+        {"bufferView": 0, "byteOffset": 4, "componentType": 5121, "normalized": true, "count": 2, "type": "SCALAR"},
+        {"bufferView": 0, "byteOffset": 6, "componentType": 5121, "count": 1, "type": "SCALAR"},
+        {"bufferView": 0, "byteOffset": 8, "componentType": 5122, "normalized": true, "count": 1, "type": "VEC2"},
+        {"bufferView": 0, "byteOffset": 12, "componentType": 5123, "normalized": true, "count": 1, "type": "VEC2"},
+        {"bufferView": 0, "componentType": 5124, "count": 1, "type": "SCALAR"}
+      ]
+    }"#;
+    reader_test(json, &buffer, |reader| {
+      assert_eq!(reader.read_f32(0).unwrap().0, vec![-1.0, -1.0, 0.0, 1.0]);
+      assert_eq!(reader.read_f32(1).unwrap().0, vec![0.0, 1.0]);
+      assert_eq!(reader.read_f32(2).unwrap().0, vec![5.0]);
+      assert_eq!(reader.read_f32(3).unwrap().0, vec![-1.0, 1.0]);
+      assert_eq!(reader.read_f32(4).unwrap().0, vec![0.0, 1.0]);
+      // 5124(int) は glTF にない
+      assert_eq!(reader.read_f32(5), Err(GltfError::InvalidAccessor(5)));
+    });
+  }
+
+  #[test]
+  fn sparse_and_missing_buffer_view() {
+    let json = r#"{
+      "bufferViews": [{"buffer": 0, "byteLength": 4}, {"buffer": 1, "byteLength": 4}],
+      "accessors": [
+        {"componentType": 5126, "count": 2, "type": "VEC3"},
+        {"bufferView": 0, "componentType": 5126, "count": 1, "type": "SCALAR",
+         "sparse": {"count": 1, "indices": {}, "values": {}}},
+        {"bufferView": 0, "componentType": 5126, "count": 1e15, "type": "VEC4"},
+        {"bufferView": 1, "componentType": 5126, "count": 1, "type": "SCALAR"},
+        {"bufferView": 0, "componentType": 5126, "count": 1, "type": "VEC9"},
+        {"bufferView": 0, "componentType": 5126, "byteOffset": 18446744073709551615, "count": 1, "type": "SCALAR"},
+        {"componentType": 5126, "count": 1e15, "type": "VEC3"}
+      ]
+    }"#;
+    reader_test(json, &[0; 4], |reader| {
+      // bufferView がなければ 0
+      assert_eq!(reader.read_f32(0).unwrap(), (vec![0.0; 6], 3));
+      assert_eq!(
+        reader.read_f32(1),
+        Err(GltfError::Unsupported("sparse accessor"))
+      );
+      // count が大きすぎても確保せずにエラー
+      assert_eq!(reader.read_f32(2), Err(GltfError::InvalidAccessor(2)));
+      // 存在しない buffer
+      assert_eq!(reader.read_f32(3), Err(GltfError::InvalidAccessor(3)));
+      assert_eq!(reader.read_f32(4), Err(GltfError::InvalidAccessor(4)));
+      assert_eq!(reader.read_f32(5), Err(GltfError::InvalidAccessor(5)));
+      assert_eq!(reader.read_f32(6), Err(GltfError::InvalidAccessor(6)));
+      // bufferView がないものも元のデータより大きくは確保しない
+      assert_eq!(reader.read_f32(7), Err(GltfError::InvalidAccessor(7)));
+    });
+  }
+
+  #[test]
+  fn indices() {
+    let mut buffer = vec![1, 2, 3, 0];
+    buffer.extend([4u16, 65535].iter().flat_map(|x| x.to_le_bytes()));
+    buffer.extend([70000u32].iter().flat_map(|x| x.to_le_bytes()));
+    let json = r#"{
+      "bufferViews": [{"buffer": 0, "byteLength": 12}],
+      "accessors": [
+        {"bufferView": 0, "componentType": 5121, "count": 3, "type": "SCALAR"},
+        {"bufferView": 0, "byteOffset": 4, "componentType": 5123, "count": 2, "type": "SCALAR"},
+        {"bufferView": 0, "byteOffset": 8, "componentType": 5125, "count": 1, "type": "SCALAR"},
+        {"bufferView": 0, "byteOffset": 8, "componentType": 5125, "count": 2, "type": "SCALAR"},
+        {"componentType": 5125, "count": 1, "type": "SCALAR"}
+      ]
+    }"#;
+    reader_test(json, &buffer, |reader| {
+      assert_eq!(reader.read_u32(0), Ok(vec![1, 2, 3]));
+      assert_eq!(reader.read_u32(1), Ok(vec![4, 65535]));
+      // f32 を経由しないので 2^24 を超えても正確
+      assert_eq!(reader.read_u32(2), Ok(vec![70000]));
+      assert_eq!(reader.read_u32(3), Err(GltfError::InvalidAccessor(3)));
+      assert_eq!(reader.read_u32(4), Err(GltfError::InvalidAccessor(4)));
+    });
+  }
+
+  #[test]
+  fn data_uris() {
+    assert_eq!(
+      decode_data_uri("data:application/octet-stream;base64,AAEC/w=="),
+      Some(Ok((
+        "application/octet-stream".to_string(),
+        vec![0, 1, 2, 255]
+      )))
+    );
+    // url-safe と改行
+    assert_eq!(
+      decode_data_uri("data:;base64,-_-_\nAA"),
+      Some(Ok((String::new(), vec![0xfb, 0xff, 0xbf, 0x00])))
+    );
+    assert_eq!(decode_data_uri("buffer.bin"), None);
+    assert_eq!(
+      decode_data_uri("data:text/plain,hello"),
+      Some(Err(GltfError::Unsupported("non-base64 data uri")))
+    );
+    assert_eq!(
+      decode_data_uri("data:;base64"),
+      Some(Err(GltfError::InvalidBase64))
+    );
+    assert_eq!(
+      decode_data_uri("data:;base64,AA*A"),
+      Some(Err(GltfError::InvalidBase64))
+    );
+    for data in [&b""[..], b"a", b"ab", b"abc", b"abcd"] {
+      assert_eq!(decode_base64(&base64(data)), Ok(data.to_vec()));
+    }
+
+    let json = format!(
+      r#"{{
+        "asset": {{"version": "2.0"}},
+        "buffers": [
+          {{"byteLength": 36, "uri": "data:application/octet-stream;base64,{}"}},
+          {{"byteLength": 4, "uri": "external.bin"}}
+        ],
+        "images": [{{"uri": "data:image/png;base64,iVBO"}}, {{"uri": "textures/a.png"}}]
+      }}"#,
+      base64(&triangle_bin())
+    );
+    assert_eq!(
+      Gltf::external_buffer_uris(&json),
+      Ok(vec!["external.bin".to_string()])
+    );
+    assert_eq!(
+      Gltf::parse_gltf(&json, &|_| None).err(),
+      Some(GltfError::MissingBuffer(1))
+    );
+    let gltf = Gltf::parse_gltf(&json, &|uri| (uri == "external.bin").then(|| vec![0; 4])).unwrap();
+    match &gltf.images[0] {
+      GltfImage::Embedded { mime_type, data } => {
+        assert_eq!(mime_type, "image/png");
+        assert_eq!(data, &vec![0x89, 0x50, 0x4e]);
+      }
+      _ => panic!("expected embedded image"),
+    }
+    assert!(matches!(&gltf.images[1], GltfImage::Uri(uri) if uri == "textures/a.png"));
+  }
+
+  #[test]
+  fn node_matrix() {
+    let translation = Vec3::new(1.0, 2.0, 3.0);
+    let rotation = Quat::from_rotation_y(std::f32::consts::FRAC_PI_2);
+    let scale = Vec3::new(2.0, 3.0, 4.0);
+    let matrix = Mat4::from_scale_rotation_translation(scale, rotation, translation);
+    let matrix: Vec<String> = matrix
+      .to_cols_array()
+      .iter()
+      .map(|x| x.to_string())
+      .collect();
+    let json = format!(
+      r#"{{
+        "asset": {{"version": "2.0"}},
+        "scenes": [{{"nodes": [0]}}],
+        "nodes": [
+          {{"name": "parent", "matrix": [{}], "children": [1]}},
+          {{"name": "child", "translation": [0, 0, 1], "children": [2]}},
+          {{"name": "cycle", "children": [1]}},
+          {{"name": "unreachable"}}
+        ]
+      }}"#,
+      matrix.join(",")
+    );
+    let gltf = Gltf::parse_gltf(&json, &|_| None).unwrap();
+    let parent = &gltf.nodes[0];
+    assert!(parent.translation.abs_diff_eq(translation, 1e-5));
+    assert!(parent.scale.abs_diff_eq(scale, 1e-5));
+    assert!(
+      parent.rotation.abs_diff_eq(rotation, 1e-5) || parent.rotation.abs_diff_eq(-rotation, 1e-5)
+    );
+    // 指定がなければ単位
+    assert_eq!(gltf.nodes[1].rotation, Quat::IDENTITY);
+    assert_eq!(gltf.nodes[1].scale, Vec3::ONE);
+    let world = gltf.world_matrices();
+    // 子の (0, 0, 1) は親の回転で +x に向き, 拡大される
+    let child = world[1].unwrap().transform_point3(Vec3::ZERO);
+    assert!(child.abs_diff_eq(translation + Vec3::new(4.0, 0.0, 0.0), 1e-4));
+    // 循環していても止まる. scene から辿れないものは None
+    assert!(world[2].is_some());
+    assert!(world[3].is_none());
+    assert_eq!(gltf.root_nodes(), vec![0]);
+  }
+}
@@ -0,0 +1,176 @@
+// 画像のエンコード
+// 依存を増やさないため deflate は無圧縮(stored block)で書く
+
+fn crc32(data: &[u8]) -> u32 {
+  let mut crc = 0xffff_ffffu32;
+  for &byte in data {
+    crc ^= byte as u32;
+    for _ in 0..8 {
+      let mask = (crc & 1).wrapping_neg();
+      crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+    }
+  }
+  !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+  const MOD: u32 = 65521;
+  let mut a = 1u32;
+  let mut b = 0u32;
+  for chunk in data.chunks(5552) {
+    for &byte in chunk {
+      a += byte as u32;
+      b += a;
+    }
+    a %= MOD;
+    b %= MOD;
+  }
+  (b << 16) | a
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+  const MAX_BLOCK: usize = 65535;
+  let mut result = vec![0x78, 0x01];
+  let mut blocks = data.chunks(MAX_BLOCK).peekable();
+  if blocks.peek().is_none() {
+    result.extend_from_slice(&[0x01, 0x00, 0x00, 0xff, 0xff]);
+  }
+  while let Some(block) = blocks.next() {
+    let is_final = blocks.peek().is_none();
+    let len = block.len() as u16;
+    result.push(is_final as u8);
+    result.extend_from_slice(&len.to_le_bytes());
+    result.extend_from_slice(&(!len).to_le_bytes());
+    result.extend_from_slice(block);
+  }
+  result.extend_from_slice(&adler32(data).to_be_bytes());
+  result
+}
+
+fn push_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+  png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+  let start = png.len();
+  png.extend_from_slice(kind);
+  png.extend_from_slice(data);
+  let crc = crc32(&png[start..]);
+  png.extend_from_slice(&crc.to_be_bytes());
+}
+
+// rgba は上の行から並んでいること
+pub fn encode_png_rgba8(width: usize, height: usize, rgba: &[u8]) -> Option<Vec<u8>> {
+  let stride = width * 4;
+  if rgba.len() != stride * height {
+    return None;
+  }
+  let mut png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+  let mut header = Vec::with_capacity(13);
+  header.extend_from_slice(&(width as u32).to_be_bytes());
+  header.extend_from_slice(&(height as u32).to_be_bytes());
+  // bit depth 8, color type 6(RGBA), compression, filter, interlace
+  header.extend_from_slice(&[8, 6, 0, 0, 0]);
+  push_chunk(&mut png, b"IHDR", &header);
+  let mut raw = Vec::with_capacity((stride + 1) * height);
+  for row in rgba.chunks(stride) {
+    raw.push(0); // filter: none
+    raw.extend_from_slice(row);
+  }
+  push_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+  push_chunk(&mut png, b"IEND", &[]);
+  Some(png)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+  }
+  // stored block だけの zlib を戻す
+  fn inflate_stored(zlib: &[u8]) -> Vec<u8> {
+    assert_eq!(&zlib[..2], &[0x78, 0x01]);
+    let mut result = Vec::new();
+    let mut offset = 2;
+    loop {
+      let header = zlib[offset];
+      let len = u16::from_le_bytes([zlib[offset + 1], zlib[offset + 2]]);
+      let nlen = u16::from_le_bytes([zlib[offset + 3], zlib[offset + 4]]);
+      assert_eq!(len, !nlen);
+      offset += 5;
+      result.extend_from_slice(&zlib[offset..offset + len as usize]);
+      offset += len as usize;
+      if header & 1 == 1 {
+        break;
+      }
+    }
+    assert_eq!(read_u32(zlib, offset), adler32(&result));
+    assert_eq!(offset + 4, zlib.len());
+    result
+  }
+
+  #[test]
+  fn checksums() {
+    assert_eq!(crc32(b""), 0);
+    assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    assert_eq!(adler32(b""), 1);
+    assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    // 5552 バイトごとの剰余が正しいか
+    let data = vec![0xff; 100_000];
+    let (mut a, mut b) = (1u64, 0u64);
+    for &x in &data {
+      a = (a + x as u64) % 65521;
+      b = (b + a) % 65521;
+    }
+    assert_eq!(adler32(&data), ((b << 16) | a) as u32);
+  }
+
+  #[test]
+  fn zlib_blocks() {
+    assert_eq!(
+      zlib_stored(&[]),
+      vec![0x78, 0x01, 0x01, 0x00, 0x00, 0xff, 0xff, 0x00, 0x00, 0x00, 0x01]
+    );
+    let data: Vec<u8> = (0..65536u32).map(|x| (x % 251) as u8).collect();
+    let zlib = zlib_stored(&data);
+    // 65535 + 1 の 2 block
+    assert_eq!(zlib[2], 0);
+    assert_eq!(&zlib[3..5], &[0xff, 0xff]);
+    assert_eq!(zlib[2 + 5 + 65535], 1);
+    assert_eq!(zlib.len(), 2 + (5 + 65535) + (5 + 1) + 4);
+    assert_eq!(inflate_stored(&zlib), data);
+  }
+
+  #[test]
+  fn png_round_trip() {
+    let (width, height) = (3, 2);
+    let rgba: Vec<u8> = (0..width * height * 4).map(|x| x as u8).collect();
+    let png = encode_png_rgba8(width, height, &rgba).unwrap();
+    assert_eq!(&png[..8], &[0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a]);
+    let mut chunks = Vec::new();
+    let mut offset = 8;
+    while offset < png.len() {
+      let len = read_u32(&png, offset) as usize;
+      let body = &png[offset + 4..offset + 8 + len];
+      assert_eq!(read_u32(&png, offset + 8 + len), crc32(body));
+      chunks.push((body[..4].to_vec(), body[4..].to_vec()));
+      offset += 12 + len;
+    }
+    assert_eq!(offset, png.len());
+    let kinds: Vec<&[u8]> = chunks.iter().map(|(k, _)| &k[..]).collect();
+    assert_eq!(kinds, vec![&b"IHDR"[..], b"IDAT", b"IEND"]);
+    assert_eq!(chunks[0].1, vec![0, 0, 0, 3, 0, 0, 0, 2, 8, 6, 0, 0, 0]);
+    let raw = inflate_stored(&chunks[1].1);
+    let mut expected = Vec::new();
+    for row in rgba.chunks(width * 4) {
+      expected.push(0);
+      expected.extend_from_slice(row);
+    }
+    assert_eq!(raw, expected);
+    assert!(chunks[2].1.is_empty());
+  }
+
+  #[test]
+  fn wrong_size() {
+    assert!(encode_png_rgba8(2, 2, &[0; 15]).is_none());
+  }
+}
@@ -0,0 +1,375 @@
+// 読み込み用の小さな JSON パーサ
+// 依存を増やさないため serde は使わない. glTF などのアセットを読む程度の用途
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Json {
+  Null,
+  Bool(bool),
+  Number(f64),
+  String(String),
+  Array(Vec<Json>),
+  // 順序を保つ. 要素数は少ないので線形探索で十分
+  Object(Vec<(String, Json)>),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum JsonError {
+  UnexpectedEof,
+  // 位置(byte)
+  UnexpectedChar(usize),
+  InvalidNumber(usize),
+  InvalidEscape(usize),
+  TrailingCharacters(usize),
+  // 入れ子が深すぎる(再帰でスタックを使い切らないように)
+  TooDeep(usize),
+}
+
+const MAX_DEPTH: usize = 128;
+
+static NULL: Json = Json::Null;
+
+impl Json {
+  pub fn parse(text: &str) -> Result<Self, JsonError> {
+    let mut parser = Parser {
+      data: text.as_bytes(),
+      pos: 0,
+      depth: 0,
+    };
+    let result = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos < parser.data.len() {
+      return Err(JsonError::TrailingCharacters(parser.pos));
+    }
+    Ok(result)
+  }
+  // Object でなかったりキーがなければ Null を返すので, 続けて辿れる
+  pub fn get(&self, key: &str) -> &Json {
+    if let Self::Object(entries) = self {
+      for (k, v) in entries {
+        if k == key {
+          return v;
+        }
+      }
+    }
+    &NULL
+  }
+  pub fn at(&self, index: usize) -> &Json {
+    match self {
+      Self::Array(values) => values.get(index).unwrap_or(&NULL),
+      _ => &NULL,
+    }
+  }
+  pub fn is_null(&self) -> bool {
+    matches!(self, Self::Null)
+  }
+  pub fn as_bool(&self) -> Option<bool> {
+    match self {
+      Self::Bool(v) => Some(*v),
+      _ => None,
+    }
+  }
+  pub fn as_f64(&self) -> Option<f64> {
+    match self {
+      Self::Number(v) => Some(*v),
+      _ => None,
+    }
+  }
+  pub fn as_f32(&self) -> Option<f32> {
+    self.as_f64().map(|v| v as f32)
+  }
+  pub fn as_usize(&self) -> Option<usize> {
+    match self {
+      Self::Number(v) if *v >= 0.0 && v.fract() == 0.0 => Some(*v as usize),
+      _ => None,
+    }
+  }
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      Self::String(v) => Some(v),
+      _ => None,
+    }
+  }
+  // Array でなければ空
+  pub fn members(&self) -> &[Json] {
+    match self {
+      Self::Array(values) => values,
+      _ => &[],
+    }
+  }
+  pub fn entries(&self) -> &[(String, Json)] {
+    match self {
+      Self::Object(entries) => entries,
+      _ => &[],
+    }
+  }
+  // 数値の配列. 長さや型が違えば None
+  pub fn as_f32_array<const N: usize>(&self) -> Option<[f32; N]> {
+    let values = self.members();
+    if values.len() != N {
+      return None;
+    }
+    let mut result = [0.0; N];
+    for (r, v) in result.iter_mut().zip(values) {
+      *r = v.as_f32()?;
+    }
+    Some(result)
+  }
+}
+
+struct Parser<'a> {
+  data: &'a [u8],
+  pos: usize,
+  depth: usize,
+}
+impl<'a> Parser<'a> {
+  fn skip_whitespace(&mut self) {
+    while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.data.get(self.pos) {
+      self.pos += 1;
+    }
+  }
+  fn peek(&self) -> Result<u8, JsonError> {
+    self
+      .data
+      .get(self.pos)
+      .copied()
+      .ok_or(JsonError::UnexpectedEof)
+  }
+  fn next(&mut self) -> Result<u8, JsonError> {
+    let c = self.peek()?;
+    self.pos += 1;
+    Ok(c)
+  }
+  fn expect(&mut self, c: u8) -> Result<(), JsonError> {
+    if self.next()? != c {
+      return Err(JsonError::UnexpectedChar(self.pos - 1));
+    }
+    Ok(())
+  }
+  fn literal(&mut self, word: &str, value: Json) -> Result<Json, JsonError> {
+    for &c in word.as_bytes() {
+      self.expect(c)?;
+    }
+    Ok(value)
+  }
+  fn value(&mut self) -> Result<Json, JsonError> {
+    self.skip_whitespace();
+    match self.peek()? {
+      b'n' => self.literal("null", Json::Null),
+      b't' => self.literal("true", Json::Bool(true)),
+      b'f' => self.literal("false", Json::Bool(false)),
+      b'"' => Ok(Json::String(self.string()?)),
+      b'[' | b'{' => {
+        if self.depth >= MAX_DEPTH {
+          return Err(JsonError::TooDeep(self.pos));
+        }
+        self.depth += 1;
+        let result = if self.peek()? == b'[' {
+          self.array()
+        } else {
+          self.object()
+        };
+        self.depth -= 1;
+        result
+      }
+      b'-' | b'0'..=b'9' => self.number(),
+      _ => Err(JsonError::UnexpectedChar(self.pos)),
+    }
+  }
+  fn array(&mut self) -> Result<Json, JsonError> {
+    self.expect(b'[')?;
+    let mut values = Vec::new();
+    self.skip_whitespace();
+    if self.peek()? == b']' {
+      self.pos += 1;
+      return Ok(Json::Array(values));
+    }
+    loop {
+      values.push(self.value()?);
+      self.skip_whitespace();
+      match self.next()? {
+        b',' => continue,
+        b']' => return Ok(Json::Array(values)),
+        _ => return Err(JsonError::UnexpectedChar(self.pos - 1)),
+      }
+    }
+  }
+  fn object(&mut self) -> Result<Json, JsonError> {
+    self.expect(b'{')?;
+    let mut entries = Vec::new();
+    self.skip_whitespace();
+    if self.peek()? == b'}' {
+      self.pos += 1;
+      return Ok(Json::Object(entries));
+    }
+    loop {
+      self.skip_whitespace();
+      let key = self.string()?;
+      self.skip_whitespace();
+      self.expect(b':')?;
+      entries.push((key, self.value()?));
+      self.skip_whitespace();
+      match self.next()? {
+        b',' => continue,
+        b'}' => return Ok(Json::Object(entries)),
+        _ => return Err(JsonError::UnexpectedChar(self.pos - 1)),
+      }
+    }
+  }
+  fn number(&mut self) -> Result<Json, JsonError> {
+    let start = self.pos;
+    while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') = self.data.get(self.pos) {
+      self.pos += 1;
+    }
+    std::str::from_utf8(&self.data[start..self.pos])
+      .ok()
+      .and_then(|s| s.parse::<f64>().ok())
+      .map(Json::Number)
+      .ok_or(JsonError::InvalidNumber(start))
+  }
+  fn hex4(&mut self) -> Result<u32, JsonError> {
+    let mut result = 0;
+    for _ in 0..4 {
+      let digit = (self.next()? as char)
+        .to_digit(16)
+        .ok_or(JsonError::InvalidEscape(self.pos - 1))?;
+      result = result * 16 + digit;
+    }
+    Ok(result)
+  }
+  fn string(&mut self) -> Result<String, JsonError> {
+    self.expect(b'"')?;
+    let mut bytes = Vec::new();
+    loop {
+      match self.next()? {
+        b'"' => break,
+        b'\\' => {
+          let c = match self.next()? {
+            b'"' => '"',
+            b'\\' => '\\',
+            b'/' => '/',
+            b'b' => '\u{8}',
+            b'f' => '\u{c}',
+            b'n' => '\n',
+            b'r' => '\r',
+            b't' => '\t',
+            b'u' => {
+              let start = self.pos;
+              let mut code = self.hex4()?;
+              // サロゲートペア
+              if (0xd800..0xdc00).contains(&code) {
+                self.expect(b'\\')?;
+                self.expect(b'u')?;
+                let low = self.hex4()?;
+                if !(0xdc00..0xe000).contains(&low) {
+                  return Err(JsonError::InvalidEscape(start));
+                }
+                code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+              }
+              char::from_u32(code).ok_or(JsonError::InvalidEscape(start))?
+            }
+            _ => return Err(JsonError::InvalidEscape(self.pos - 1)),
+          };
+          let mut buf = [0; 4];
+          bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        }
+        c => bytes.push(c),
+      }
+    }
+    // 元が &str なので壊れた UTF-8 にはならない
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn values() {
+    let json =
+      Json::parse(r#" {"a": [1, -2.5, 3e2, true, false, null], "b": {}, "c": []} "#).unwrap();
+    let a = json.get("a");
+    assert_eq!(a.at(0).as_usize(), Some(1));
+    assert_eq!(a.at(1).as_f64(), Some(-2.5));
+    assert_eq!(a.at(2).as_f64(), Some(300.0));
+    assert_eq!(a.at(3).as_bool(), Some(true));
+    assert_eq!(a.at(4).as_bool(), Some(false));
+    assert!(a.at(5).is_null());
+    assert!(a.at(6).is_null());
+    assert_eq!(json.get("b"), &Json::Object(Vec::new()));
+    assert!(json.get("c").members().is_empty());
+    // 辿れなければ Null
+    assert!(json.get("x").get("y").at(3).is_null());
+    assert_eq!(json.entries().len(), 3);
+  }
+
+  #[test]
+  fn numbers() {
+    let parse = |s: &str| Json::parse(s).map(|x| x.as_f64());
+    assert_eq!(parse("0"), Ok(Some(0.0)));
+    assert_eq!(parse("-0.125"), Ok(Some(-0.125)));
+    assert_eq!(parse("1E-2"), Ok(Some(0.01)));
+    assert_eq!(parse("12345678901"), Ok(Some(12345678901.0)));
+    assert_eq!(parse("-"), Err(JsonError::InvalidNumber(0)));
+    assert_eq!(parse("1e"), Err(JsonError::InvalidNumber(0)));
+    assert_eq!(parse("1-2"), Err(JsonError::InvalidNumber(0)));
+    assert_eq!(parse("[1, 2.5.1]"), Err(JsonError::InvalidNumber(4)));
+    // 負や小数は index にならない
+    assert_eq!(Json::Number(-1.0).as_usize(), None);
+    assert_eq!(Json::Number(1.5).as_usize(), None);
+    assert_eq!(
+      Json::parse("[1, 2, 3]").unwrap().as_f32_array::<3>(),
+      Some([1.0, 2.0, 3.0])
+    );
+    assert_eq!(Json::parse("[1, 2]").unwrap().as_f32_array::<3>(), None);
+    assert_eq!(Json::parse("[1, \"2\"]").unwrap().as_f32_array::<2>(), None);
+  }
+
+  #[test]
+  fn escapes() {
+    let parse = |s: &str| Json::parse(s).map(|x| x.as_str().map(|x| x.to_string()));
+    assert_eq!(
+      parse(r#""a\"b\\c\/d\b\f\n\r\t""#),
+      Ok(Some("a\"b\\c/d\u{8}\u{c}\n\r\t".to_string()))
+    );
+    assert_eq!(
+      parse(r#""\u0041\u00e9\u3042""#),
+      Ok(Some("Aéあ".to_string()))
+    );
+    assert_eq!(parse("\"日本語\""), Ok(Some("日本語".to_string())));
+    // サロゲートペア
+    assert_eq!(
+      parse(r#""\ud83d\ude00""#),
+      Ok(Some("\u{1f600}".to_string()))
+    );
+    assert_eq!(
+      parse(r#""\uD834\uDD1E""#),
+      Ok(Some("\u{1d11e}".to_string()))
+    );
+    // 対になっていないもの
+    assert_eq!(parse(r#""\ud83d""#), Err(JsonError::UnexpectedChar(7)));
+    assert_eq!(parse(r#""\ud83d\u0041""#), Err(JsonError::InvalidEscape(3)));
+    assert_eq!(parse(r#""\ude00""#), Err(JsonError::InvalidEscape(3)));
+    assert_eq!(parse(r#""\u12g4""#), Err(JsonError::InvalidEscape(5)));
+    assert_eq!(parse(r#""\x""#), Err(JsonError::InvalidEscape(2)));
+  }
+
+  #[test]
+  fn malformed() {
+    assert_eq!(Json::parse(""), Err(JsonError::UnexpectedEof));
+    assert_eq!(Json::parse("["), Err(JsonError::UnexpectedEof));
+    assert_eq!(Json::parse("\"abc"), Err(JsonError::UnexpectedEof));
+    assert_eq!(Json::parse("[1 2]"), Err(JsonError::UnexpectedChar(3)));
+    assert_eq!(Json::parse("[1,]"), Err(JsonError::UnexpectedChar(3)));
+    assert_eq!(Json::parse("{\"a\" 1}"), Err(JsonError::UnexpectedChar(5)));
+    assert_eq!(Json::parse("{a: 1}"), Err(JsonError::UnexpectedChar(1)));
+    assert_eq!(Json::parse("nul"), Err(JsonError::UnexpectedEof));
+    assert_eq!(Json::parse("tru e"), Err(JsonError::UnexpectedChar(3)));
+    assert_eq!(Json::parse("1 2"), Err(JsonError::TrailingCharacters(2)));
+    assert_eq!(Json::parse("{} x"), Err(JsonError::TrailingCharacters(3)));
+    let deep = "[".repeat(MAX_DEPTH + 1) + &"]".repeat(MAX_DEPTH + 1);
+    assert_eq!(Json::parse(&deep), Err(JsonError::TooDeep(MAX_DEPTH)));
+    let ok = "[".repeat(MAX_DEPTH) + &"]".repeat(MAX_DEPTH);
+    assert!(Json::parse(&ok).is_ok());
+  }
+}
@@ -0,0 +1,183 @@
+// KTX2 コンテナの読み込み
+// https://registry.khronos.org/KTX/specs/2.0/ktxspec.v2.html
+// Basis Universal / zstd などの supercompression は扱わない(中身をそのまま GPU に渡せるものだけ)
+
+const IDENTIFIER: [u8; 12] = [
+  0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a,
+];
+const HEADER_SIZE: usize = 80;
+const LEVEL_INDEX_SIZE: usize = 24;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Ktx2Error {
+  InvalidIdentifier,
+  UnexpectedEof,
+  // 0 以外(BasisLZ / zstd / zlib)
+  UnsupportedSupercompression(u32),
+  // vkFormat が 0 のもの(Basis Universal など)
+  UndefinedFormat,
+}
+
+pub struct Ktx2 {
+  // VkFormat の値
+  pub vk_format: u32,
+  pub type_size: u32,
+  pub pixel_width: usize,
+  pub pixel_height: usize,
+  pub pixel_depth: usize,
+  pub layer_count: usize,
+  pub face_count: usize,
+  // levels[0] が一番大きい. 各 level の中身は layer, face, z の順に並ぶ
+  pub levels: Vec<Vec<u8>>,
+  // levelCount が 0 のときは読み込み側で mipmap を作る
+  pub need_generate_mipmap: bool,
+}
+
+struct Reader<'a> {
+  data: &'a [u8],
+}
+impl<'a> Reader<'a> {
+  fn bytes(&self, offset: usize, len: usize) -> Result<&'a [u8], Ktx2Error> {
+    let end = offset.checked_add(len).ok_or(Ktx2Error::UnexpectedEof)?;
+    self.data.get(offset..end).ok_or(Ktx2Error::UnexpectedEof)
+  }
+  fn u32(&self, offset: usize) -> Result<u32, Ktx2Error> {
+    let bytes = self.bytes(offset, 4)?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+  }
+  // wasm32 の usize に収まらない値はデータの外を指している
+  fn u64(&self, offset: usize) -> Result<usize, Ktx2Error> {
+    let bytes = self.bytes(offset, 8)?;
+    usize::try_from(u64::from_le_bytes(bytes.try_into().unwrap()))
+      .map_err(|_| Ktx2Error::UnexpectedEof)
+  }
+}
+
+impl Ktx2 {
+  pub fn parse(data: &[u8]) -> Result<Self, Ktx2Error> {
+    let reader = Reader { data };
+    if reader.bytes(0, IDENTIFIER.len())? != IDENTIFIER {
+      return Err(Ktx2Error::InvalidIdentifier);
+    }
+    let vk_format = reader.u32(12)?;
+    let type_size = reader.u32(16)?;
+    let pixel_width = reader.u32(20)? as usize;
+    let pixel_height = reader.u32(24)? as usize;
+    let pixel_depth = reader.u32(28)? as usize;
+    let layer_count = reader.u32(32)? as usize;
+    let face_count = reader.u32(36)? as usize;
+    let level_count = reader.u32(40)? as usize;
+    let supercompression_scheme = reader.u32(44)?;
+    if supercompression_scheme != 0 {
+      return Err(Ktx2Error::UnsupportedSupercompression(
+        supercompression_scheme,
+      ));
+    }
+    if vk_format == 0 {
+      return Err(Ktx2Error::UndefinedFormat);
+    }
+    let mut levels = Vec::new();
+    for i in 0..level_count.max(1) {
+      let index = HEADER_SIZE + i * LEVEL_INDEX_SIZE;
+      let offset = reader.u64(index)?;
+      let length = reader.u64(index + 8)?;
+      levels.push(reader.bytes(offset, length)?.to_vec());
+    }
+    Ok(Self {
+      vk_format,
+      type_size,
+      pixel_width,
+      pixel_height: pixel_height.max(1),
+      pixel_depth,
+      layer_count,
+      face_count: face_count.max(1),
+      levels,
+      need_generate_mipmap: level_count == 0,
+    })
+  }
+  // 配列でも cubemap でも 3D でもないもの
+  pub fn is_2d(&self) -> bool {
+    self.pixel_depth == 0 && self.layer_count == 0 && self.face_count == 1
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const VK_FORMAT_R8G8B8A8_UNORM: u32 = 37;
+
+  // levels[0] が一番大きいもの. 中身は level index の後ろに詰める
+  fn container(vk_format: u32, size: (u32, u32), level_count: u32, levels: &[&[u8]]) -> Vec<u8> {
+    let mut header = IDENTIFIER.to_vec();
+    let fields = [vk_format, 1, size.0, size.1, 0, 0, 1, level_count, 0];
+    for x in fields {
+      header.extend_from_slice(&x.to_le_bytes());
+    }
+    // dfd / kvd / sgd の offset と長さは 0
+    header.resize(HEADER_SIZE, 0);
+    let mut offset = HEADER_SIZE + levels.len() * LEVEL_INDEX_SIZE;
+    let mut body = Vec::new();
+    for level in levels {
+      header.extend_from_slice(&(offset as u64).to_le_bytes());
+      header.extend_from_slice(&(level.len() as u64).to_le_bytes());
+      header.extend_from_slice(&(level.len() as u64).to_le_bytes());
+      offset += level.len();
+      body.extend_from_slice(level);
+    }
+    header.extend(body);
+    header
+  }
+
+  #[test]
+  fn levels() {
+    let level0 = [1u8; 16];
+    let level1 = [2u8; 4];
+    let data = container(VK_FORMAT_R8G8B8A8_UNORM, (2, 2), 2, &[&level0, &level1]);
+    let ktx2 = Ktx2::parse(&data).unwrap();
+    assert_eq!(ktx2.vk_format, VK_FORMAT_R8G8B8A8_UNORM);
+    assert_eq!((ktx2.pixel_width, ktx2.pixel_height), (2, 2));
+    assert_eq!(ktx2.levels, vec![level0.to_vec(), level1.to_vec()]);
+    assert!(!ktx2.need_generate_mipmap);
+    assert!(ktx2.is_2d());
+    // levelCount が 0 なら 1 つ読んで mipmap は作らせる. 高さ 0 は 1 扱い
+    let data = container(VK_FORMAT_R8G8B8A8_UNORM, (4, 0), 0, &[&level0]);
+    let ktx2 = Ktx2::parse(&data).unwrap();
+    assert_eq!(ktx2.levels.len(), 1);
+    assert_eq!(ktx2.pixel_height, 1);
+    assert!(ktx2.need_generate_mipmap);
+  }
+
+  #[test]
+  fn invalid() {
+    let level = [0u8; 4];
+    let valid = container(VK_FORMAT_R8G8B8A8_UNORM, (1, 1), 1, &[&level]);
+    let parse = |data: &[u8]| Ktx2::parse(data).err();
+    let mut identifier = valid.clone();
+    identifier[5] = b'1';
+    assert_eq!(parse(&identifier), Some(Ktx2Error::InvalidIdentifier));
+    assert_eq!(parse(&valid[..8]), Some(Ktx2Error::UnexpectedEof));
+    let mut supercompressed = valid.clone();
+    supercompressed[44..48].copy_from_slice(&2u32.to_le_bytes());
+    assert_eq!(
+      parse(&supercompressed),
+      Some(Ktx2Error::UnsupportedSupercompression(2))
+    );
+    let undefined = container(0, (1, 1), 1, &[&level]);
+    assert_eq!(parse(&undefined), Some(Ktx2Error::UndefinedFormat));
+    // header, level index, 中身のどこで切れても読まない
+    for len in [40, HEADER_SIZE, HEADER_SIZE + 12, valid.len() - 1] {
+      assert_eq!(parse(&valid[..len]), Some(Ktx2Error::UnexpectedEof));
+    }
+    // level index が足りない
+    let mut more_levels = valid.clone();
+    more_levels[40..44].copy_from_slice(&3u32.to_le_bytes());
+    assert_eq!(parse(&more_levels), Some(Ktx2Error::UnexpectedEof));
+    // 桁あふれする offset / 長さ
+    for (at, value) in [(HEADER_SIZE, u64::MAX), (HEADER_SIZE + 8, u64::MAX - 50)] {
+      let mut overflow = valid.clone();
+      overflow[at..at + 8].copy_from_slice(&value.to_le_bytes());
+      assert_eq!(parse(&overflow), Some(Ktx2Error::UnexpectedEof));
+    }
+  }
+}
@@ -1,6 +1,14 @@
+pub mod animation;
 pub mod collections;
+pub mod gltf;
+pub mod image;
+pub mod json;
+pub mod ktx2;
 pub mod math;
+pub mod mesh;
+pub mod obj;
 pub mod rand;
+pub mod skeleton;
 pub use once_cell::sync::OnceCell;
 pub mod owner;
 use owner::*;
@@ -52,3 +52,187 @@ pub fn from_hlsa(hlsa: Vec4) -> Vec4 {
     Vec4::new(max, min, f(360.0 - h), a)
   }
 }
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Aabb {
+  pub min: Vec3,
+  pub max: Vec3,
+}
+impl Aabb {
+  pub fn new(min: Vec3, max: Vec3) -> Self {
+    Self { min, max }
+  }
+  // 空なら None
+  pub fn from_points(points: &[Vec3]) -> Option<Self> {
+    let first = *points.first()?;
+    let mut result = Self::new(first, first);
+    for p in &points[1..] {
+      result.min = result.min.min(*p);
+      result.max = result.max.max(*p);
+    }
+    Some(result)
+  }
+  pub fn center(&self) -> Vec3 {
+    (self.min + self.max) * 0.5
+  }
+  pub fn size(&self) -> Vec3 {
+    self.max - self.min
+  }
+  pub fn union(&self, other: &Self) -> Self {
+    Self::new(self.min.min(other.min), self.max.max(other.max))
+  }
+  pub fn contains(&self, p: Vec3) -> bool {
+    p.cmpge(self.min).all() && p.cmple(self.max).all()
+  }
+  // 変換後の 8 頂点を囲むもの
+  pub fn transform(&self, mat: Mat4) -> Self {
+    let mut corners = [Vec3::ZERO; 8];
+    for (i, corner) in corners.iter_mut().enumerate() {
+      let pick = |bit: usize, min: f32, max: f32| if i & bit == 0 { min } else { max };
+      *corner = mat.transform_point3(Vec3::new(
+        pick(1, self.min.x, self.max.x),
+        pick(2, self.min.y, self.max.y),
+        pick(4, self.min.z, self.max.z),
+      ));
+    }
+    Self::from_points(&corners).unwrap_or(*self)
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BoundingSphere {
+  pub center: Vec3,
+  pub radius: f32,
+}
+impl BoundingSphere {
+  pub fn new(center: Vec3, radius: f32) -> Self {
+    Self { center, radius }
+  }
+  // Ritter の方法. 最小ではないが 1 割ほど大きい程度に収まる
+  pub fn from_points(points: &[Vec3]) -> Option<Self> {
+    let first = *points.first()?;
+    let farthest = |from: Vec3| {
+      points.iter().copied().fold(from, |a, b| {
+        if from.distance_squared(b) > from.distance_squared(a) {
+          b
+        } else {
+          a
+        }
+      })
+    };
+    let a = farthest(first);
+    let b = farthest(a);
+    let mut result = Self::new((a + b) * 0.5, a.distance(b) * 0.5);
+    for p in points {
+      let d = p.distance(result.center);
+      if d > result.radius {
+        let radius = (result.radius + d) * 0.5;
+        result.center += (*p - result.center) * ((radius - result.radius) / d);
+        result.radius = radius;
+      }
+    }
+    // 広げた時の誤差で端の点が外れないようにする
+    let max_distance = points
+      .iter()
+      .map(|p| p.distance(result.center))
+      .fold(result.radius, f32::max);
+    result.radius = max_distance * (1.0 + 4.0 * f32::EPSILON);
+    Some(result)
+  }
+  pub fn contains(&self, p: Vec3) -> bool {
+    p.distance_squared(self.center) <= self.radius * self.radius
+  }
+  // 拡大縮小が軸ごとに違うときは一番大きい倍率を使う
+  pub fn transform(&self, mat: Mat4) -> Self {
+    let scale = mat
+      .x_axis
+      .truncate()
+      .length()
+      .max(mat.y_axis.truncate().length())
+      .max(mat.z_axis.truncate().length());
+    Self::new(mat.transform_point3(self.center), self.radius * scale)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::rand::XorShift128;
+
+  fn random_points(rand: &XorShift128, count: usize, scale: Vec3, offset: Vec3) -> Vec<Vec3> {
+    (0..count)
+      .map(|_| {
+        let p = Vec3::new(
+          rand.uniform() as f32,
+          rand.uniform() as f32,
+          rand.uniform() as f32,
+        );
+        (p * 2.0 - Vec3::ONE) * scale + offset
+      })
+      .collect()
+  }
+
+  #[test]
+  fn aabb() {
+    assert_eq!(Aabb::from_points(&[]), None);
+    let points = [Vec3::new(1.0, -2.0, 3.0), Vec3::new(-1.0, 4.0, 0.0)];
+    let aabb = Aabb::from_points(&points).unwrap();
+    assert_eq!(
+      aabb,
+      Aabb::new(Vec3::new(-1.0, -2.0, 0.0), Vec3::new(1.0, 4.0, 3.0))
+    );
+    assert_eq!(aabb.center(), Vec3::new(0.0, 1.0, 1.5));
+    assert_eq!(aabb.size(), Vec3::new(2.0, 6.0, 3.0));
+    assert!(aabb.contains(aabb.min) && aabb.contains(aabb.max));
+    assert!(!aabb.contains(Vec3::new(0.0, 5.0, 1.0)));
+    let other = Aabb::new(Vec3::splat(2.0), Vec3::splat(5.0));
+    assert_eq!(
+      aabb.union(&other),
+      Aabb::new(Vec3::new(-1.0, -2.0, 0.0), Vec3::splat(5.0))
+    );
+    // 回転後の頂点を全て囲む
+    let mat = Mat4::from_rotation_translation(Quat::from_rotation_z(0.7), Vec3::X);
+    let transformed = aabb.transform(mat);
+    for i in 0..8 {
+      let corner = Vec3::select(
+        BVec3::new(i & 1 != 0, i & 2 != 0, i & 4 != 0),
+        aabb.max,
+        aabb.min,
+      );
+      assert!(transformed.contains(mat.transform_point3(corner)));
+    }
+  }
+
+  #[test]
+  fn bounding_sphere() {
+    assert_eq!(BoundingSphere::from_points(&[]), None);
+    assert_eq!(
+      BoundingSphere::from_points(&[Vec3::ONE]),
+      Some(BoundingSphere::new(Vec3::ONE, 0.0))
+    );
+    let rand = XorShift128::new(12345);
+    for i in 0..50 {
+      let scale = Vec3::new(1.0 + i as f32, 0.5, 3.0);
+      let points = random_points(&rand, 200, scale, Vec3::splat(i as f32 * 10.0));
+      let sphere = BoundingSphere::from_points(&points).unwrap();
+      for p in &points {
+        assert!(sphere.contains(*p), "{:?} is outside of {:?}", p, sphere);
+      }
+      // 中心は点の凸包の中にあるので AABB の対角線よりは小さい
+      let aabb = Aabb::from_points(&points).unwrap();
+      assert!(aabb.contains(sphere.center));
+      assert!(sphere.radius <= aabb.size().length());
+      // 拡大しても全ての点を含む
+      let mat = Mat4::from_scale_rotation_translation(
+        Vec3::new(2.0, 0.5, 1.0),
+        Quat::from_rotation_y(1.0),
+        Vec3::Y,
+      );
+      let transformed = sphere.transform(mat);
+      for p in &points {
+        let p = mat.transform_point3(*p);
+        assert!(p.distance(transformed.center) <= transformed.radius * 1.0001);
+      }
+    }
+  }
+}
@@ -0,0 +1,600 @@
+// GPU に載せる前のメッシュの加工
+// 法線 / tangent の計算, 頂点の結合, 境界, 複数メッシュの結合(静的なものを一つの Vao にまとめる用)
+use crate::gltf::{GltfPrimitive, GltfPrimitiveMode};
+use crate::math::*;
+use crate::obj::ObjSubmesh;
+use std::collections::HashMap;
+
+// 三角形リスト. 属性は空なら持っていない扱いで, あるなら positions と同じ長さ
+#[derive(Clone, Default, Debug)]
+pub struct Mesh {
+  pub positions: Vec<Vec3>,
+  pub normals: Vec<Vec3>,
+  // w は bitangent = cross(normal, tangent) * w の符号
+  pub tangents: Vec<Vec4>,
+  pub uvs: Vec<Vec2>,
+  pub colors: Vec<Vec4>,
+  pub indices: Vec<u32>,
+}
+
+impl Mesh {
+  pub fn new(positions: Vec<Vec3>, indices: Vec<u32>) -> Self {
+    Self {
+      positions,
+      indices,
+      ..Default::default()
+    }
+  }
+  pub fn new_obj_submesh(submesh: &ObjSubmesh) -> Self {
+    Self {
+      positions: submesh.positions.clone(),
+      normals: submesh.normals.clone(),
+      uvs: submesh.uvs.clone(),
+      indices: submesh.indices.clone(),
+      ..Default::default()
+    }
+  }
+  // 点や線のもの, 頂点の外を指す index があるものは None. strip / fan はリストに展開する
+  // 頂点数と長さの合わない属性は捨てる
+  pub fn new_gltf_primitive(primitive: &GltfPrimitive) -> Option<Self> {
+    let vertex_count = primitive.positions.len();
+    let indices: Vec<u32> = match &primitive.indices {
+      Some(indices) => indices.clone(),
+      None => (0..vertex_count as u32).collect(),
+    };
+    if indices.iter().any(|i| *i as usize >= vertex_count) {
+      return None;
+    }
+    let indices = match primitive.mode {
+      GltfPrimitiveMode::Triangles => indices,
+      GltfPrimitiveMode::TriangleStrip => (2..indices.len())
+        .flat_map(|i| {
+          // 偶数番目と奇数番目で向きが変わる
+          if i % 2 == 0 {
+            [indices[i - 2], indices[i - 1], indices[i]]
+          } else {
+            [indices[i - 1], indices[i - 2], indices[i]]
+          }
+        })
+        .collect(),
+      GltfPrimitiveMode::TriangleFan => (2..indices.len())
+        .flat_map(|i| [indices[0], indices[i - 1], indices[i]])
+        .collect(),
+      _ => return None,
+    };
+    fn fitted<T: Clone>(values: &[T], vertex_count: usize) -> Vec<T> {
+      if values.len() == vertex_count {
+        values.to_vec()
+      } else {
+        Vec::new()
+      }
+    }
+    Some(Self {
+      positions: primitive.positions.clone(),
+      normals: fitted(&primitive.normals, vertex_count),
+      tangents: fitted(&primitive.tangents, vertex_count),
+      uvs: fitted(&primitive.uvs, vertex_count),
+      colors: fitted(&primitive.colors, vertex_count),
+      indices,
+    })
+  }
+  pub fn vertex_count(&self) -> usize {
+    self.positions.len()
+  }
+  pub fn triangle_count(&self) -> usize {
+    self.indices.len() / 3
+  }
+  fn triangle_positions(&self, triangle: &[u32]) -> [Vec3; 3] {
+    [0, 1, 2].map(|i| self.positions[triangle[i] as usize])
+  }
+
+  // 面積で重み付けした面法線の和. 同じ位置の頂点は uv などで分かれていても同じ法線にする
+  pub fn compute_smooth_normals(&mut self) {
+    let mut sums: HashMap<[u32; 3], Vec3> = HashMap::new();
+    let key = |p: Vec3| [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()];
+    for triangle in self.indices.chunks_exact(3) {
+      let [a, b, c] = self.triangle_positions(triangle);
+      let face_normal = (b - a).cross(c - a);
+      for p in [a, b, c] {
+        *sums.entry(key(p)).or_insert(Vec3::ZERO) += face_normal;
+      }
+    }
+    self.normals = self
+      .positions
+      .iter()
+      .map(|p| {
+        sums
+          .get(&key(*p))
+          .copied()
+          .unwrap_or(Vec3::ZERO)
+          .normalize_or_zero()
+      })
+      .collect();
+  }
+  // 三角形ごとに頂点を分けて面法線にする
+  pub fn compute_flat_normals(&mut self) {
+    self.unweld();
+    let mut normals = vec![Vec3::ZERO; self.positions.len()];
+    for triangle in self.indices.chunks_exact(3) {
+      let [a, b, c] = self.triangle_positions(triangle);
+      let face_normal = (b - a).cross(c - a).normalize_or_zero();
+      for i in triangle {
+        normals[*i as usize] = face_normal;
+      }
+    }
+    self.normals = normals;
+  }
+  // index を使わず三角形ごとに頂点を持つようにする
+  pub fn unweld(&mut self) {
+    fn expand<T: Copy>(values: &[T], indices: &[u32]) -> Vec<T> {
+      if values.is_empty() {
+        return Vec::new();
+      }
+      indices.iter().map(|i| values[*i as usize]).collect()
+    }
+    self.positions = expand(&self.positions, &self.indices);
+    self.normals = expand(&self.normals, &self.indices);
+    self.tangents = expand(&self.tangents, &self.indices);
+    self.uvs = expand(&self.uvs, &self.indices);
+    self.colors = expand(&self.colors, &self.indices);
+    self.indices = (0..self.indices.len() as u32).collect();
+  }
+
+  // MikkTSpace に倣い, 三角形ごとの uv の u 方向を角度で重み付けして足す
+  // 法線がなければ先に compute_smooth_normals する. uv がない / 潰れている頂点は法線に直交する適当な向き
+  pub fn compute_tangents(&mut self) {
+    if self.normals.len() != self.positions.len() {
+      self.compute_smooth_normals();
+    }
+    let mut tangents = vec![Vec3::ZERO; self.positions.len()];
+    let mut bitangents = vec![Vec3::ZERO; self.positions.len()];
+    if self.uvs.len() == self.positions.len() {
+      for triangle in self.indices.chunks_exact(3) {
+        let p = self.triangle_positions(triangle);
+        let uv = [0, 1, 2].map(|i| self.uvs[triangle[i] as usize]);
+        let e1 = p[1] - p[0];
+        let e2 = p[2] - p[0];
+        let d1 = uv[1] - uv[0];
+        let d2 = uv[2] - uv[0];
+        let det = d1.x * d2.y - d2.x * d1.y;
+        if det.abs() < f32::EPSILON {
+          continue;
+        }
+        let tangent = ((e1 * d2.y - e2 * d1.y) / det).normalize_or_zero();
+        let bitangent = ((e2 * d1.x - e1 * d2.x) / det).normalize_or_zero();
+        for k in 0..3 {
+          let edge0 = p[(k + 1) % 3] - p[k];
+          let edge1 = p[(k + 2) % 3] - p[k];
+          let angle = edge0.angle_between(edge1);
+          if !angle.is_finite() {
+            continue;
+          }
+          tangents[triangle[k] as usize] += tangent * angle;
+          bitangents[triangle[k] as usize] += bitangent * angle;
+        }
+      }
+    }
+    self.tangents = self
+      .normals
+      .iter()
+      .zip(tangents)
+      .zip(bitangents)
+      .map(|((normal, tangent), bitangent)| {
+        let normal = *normal;
+        let mut t = (tangent - normal * normal.dot(tangent)).normalize_or_zero();
+        if t == Vec3::ZERO {
+          let axis = if normal.x.abs() < 0.9 {
+            Vec3::X
+          } else {
+            Vec3::Y
+          };
+          t = (axis - normal * normal.dot(axis)).normalize_or_zero();
+        }
+        let w = if normal.cross(t).dot(bitangent) < 0.0 {
+          -1.0
+        } else {
+          1.0
+        };
+        t.extend(w)
+      })
+      .collect();
+  }
+
+  // 全属性が epsilon の格子で一致する頂点をまとめ, 潰れた三角形を消す
+  // 格子の境目をまたぐと epsilon 以内でもまとまらないことがある
+  pub fn weld(&mut self, epsilon: f32) {
+    let epsilon = epsilon.max(f32::EPSILON);
+    let quantize = |x: f32| (x / epsilon).round() as i64;
+    let mut remap = Vec::with_capacity(self.positions.len());
+    let mut keys: HashMap<Vec<i64>, u32> = HashMap::new();
+    let mut kept = Vec::new();
+    for i in 0..self.positions.len() {
+      let mut key: Vec<i64> = self.positions[i].to_array().map(quantize).to_vec();
+      if let Some(n) = self.normals.get(i) {
+        key.extend(n.to_array().map(quantize));
+      }
+      if let Some(t) = self.tangents.get(i) {
+        key.extend(t.to_array().map(quantize));
+      }
+      if let Some(uv) = self.uvs.get(i) {
+        key.extend(uv.to_array().map(quantize));
+      }
+      if let Some(c) = self.colors.get(i) {
+        key.extend(c.to_array().map(quantize));
+      }
+      let index = *keys.entry(key).or_insert_with(|| {
+        kept.push(i);
+        (kept.len() - 1) as u32
+      });
+      remap.push(index);
+    }
+    fn pick<T: Copy>(values: &[T], kept: &[usize]) -> Vec<T> {
+      if values.is_empty() {
+        return Vec::new();
+      }
+      kept.iter().map(|i| values[*i]).collect()
+    }
+    self.positions = pick(&self.positions, &kept);
+    self.normals = pick(&self.normals, &kept);
+    self.tangents = pick(&self.tangents, &kept);
+    self.uvs = pick(&self.uvs, &kept);
+    self.colors = pick(&self.colors, &kept);
+    self.indices = self
+      .indices
+      .chunks_exact(3)
+      .map(|t| [0, 1, 2].map(|k| remap[t[k] as usize]))
+      .filter(|[a, b, c]| a != b && b != c && c != a)
+      .flatten()
+      .collect();
+  }
+
+  pub fn aabb(&self) -> Option<Aabb> {
+    Aabb::from_points(&self.positions)
+  }
+  pub fn bounding_sphere(&self) -> Option<BoundingSphere> {
+    BoundingSphere::from_points(&self.positions)
+  }
+
+  // 法線は逆転置で変換する. 裏返る変換なら三角形の向きも戻す
+  pub fn transform(&mut self, mat: Mat4) {
+    let normal_mat = Mat3::from_mat4(mat).inverse().transpose();
+    for p in &mut self.positions {
+      *p = mat.transform_point3(*p);
+    }
+    for n in &mut self.normals {
+      *n = (normal_mat * *n).normalize_or_zero();
+    }
+    for t in &mut self.tangents {
+      let xyz = mat.transform_vector3(t.truncate()).normalize_or_zero();
+      *t = xyz.extend(t.w);
+    }
+    if mat.determinant() < 0.0 {
+      for triangle in self.indices.chunks_exact_mut(3) {
+        triangle.swap(1, 2);
+      }
+      // bitangent の向きも反転するので符号を戻す
+      for t in &mut self.tangents {
+        t.w = -t.w;
+      }
+    }
+  }
+  // other を mat で変換して足す
+  // 片方にしかない属性はもう片方を既定値(法線 0, tangent +X, uv 0, 色 白)で埋める
+  pub fn append(&mut self, other: &Mesh, mat: Mat4) {
+    let mut other = other.clone();
+    other.transform(mat);
+    let self_len = self.positions.len();
+    let other_len = other.positions.len();
+    fn join<T: Copy>(a: &mut Vec<T>, a_len: usize, b: &[T], b_len: usize, default: T) {
+      if a.is_empty() && b.is_empty() {
+        return;
+      }
+      a.resize(a_len, default);
+      if b.is_empty() {
+        a.resize(a_len + b_len, default);
+      } else {
+        a.extend_from_slice(b);
+      }
+    }
+    join(
+      &mut self.normals,
+      self_len,
+      &other.normals,
+      other_len,
+      Vec3::ZERO,
+    );
+    join(
+      &mut self.tangents,
+      self_len,
+      &other.tangents,
+      other_len,
+      Vec4::new(1.0, 0.0, 0.0, 1.0),
+    );
+    join(&mut self.uvs, self_len, &other.uvs, other_len, Vec2::ZERO);
+    join(
+      &mut self.colors,
+      self_len,
+      &other.colors,
+      other_len,
+      Vec4::ONE,
+    );
+    self.positions.extend_from_slice(&other.positions);
+    let base = self_len as u32;
+    self.indices.extend(other.indices.iter().map(|i| i + base));
+  }
+  pub fn merge(parts: &[(&Mesh, Mat4)]) -> Self {
+    let mut result = Self::default();
+    for (mesh, mat) in parts {
+      result.append(mesh, *mat);
+    }
+    result
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // 一辺 2 の立方体. 面ごとに 4 頂点で uv を持つ
+  fn cube() -> Mesh {
+    let mut mesh = Mesh::default();
+    for n in [Vec3::X, -Vec3::X, Vec3::Y, -Vec3::Y, Vec3::Z, -Vec3::Z] {
+      let u = n.any_orthonormal_vector();
+      let v = n.cross(u);
+      let base = mesh.positions.len() as u32;
+      for (x, y) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+        mesh
+          .positions
+          .push(n + u * (x * 2.0 - 1.0) + v * (y * 2.0 - 1.0));
+        mesh.normals.push(n);
+        mesh.uvs.push(Vec2::new(x, y));
+      }
+      mesh
+        .indices
+        .extend([0, 1, 2, 0, 2, 3].iter().map(|i| base + i));
+    }
+    mesh
+  }
+  fn face_normal(mesh: &Mesh, triangle: &[u32]) -> Vec3 {
+    let [a, b, c] = mesh.triangle_positions(triangle);
+    (b - a).cross(c - a).normalize()
+  }
+  // 面の法線は u 方向 × v 方向. cube() の法線から求める
+  fn face_axes(mesh: &Mesh, triangle: &[u32]) -> (Vec3, Vec3) {
+    let n = mesh.normals[triangle[0] as usize];
+    let u = n.any_orthonormal_vector();
+    (u, n.cross(u))
+  }
+
+  #[test]
+  fn smooth_normals() {
+    let mut mesh = cube();
+    mesh.normals.clear();
+    mesh.compute_smooth_normals();
+    assert_eq!(mesh.normals.len(), 24);
+    for (p, n) in mesh.positions.iter().zip(&mesh.normals) {
+      assert!((n.length() - 1.0).abs() < 1e-6);
+      // 角では 3 面の平均なので外向きで全成分が位置と同じ符号
+      assert!(n.dot(*p) > 0.0);
+      assert_eq!(n.signum(), p.signum());
+    }
+    // uv で分かれていても同じ位置なら同じ法線
+    for i in 0..24 {
+      for j in 0..24 {
+        if mesh.positions[i] == mesh.positions[j] {
+          assert_eq!(mesh.normals[i], mesh.normals[j]);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn flat_normals() {
+    let mut mesh = cube();
+    let uvs = mesh.uvs.clone();
+    let indices = mesh.indices.clone();
+    mesh.compute_flat_normals();
+    assert_eq!(mesh.vertex_count(), 36);
+    assert_eq!(mesh.triangle_count(), 12);
+    assert_eq!(mesh.indices, (0..36).collect::<Vec<u32>>());
+    for (i, n) in mesh.normals.iter().enumerate() {
+      assert!((n.dot(mesh.positions[i]) - 1.0).abs() < 1e-6);
+      // 元の面法線と一致し, 他の属性も展開される
+      assert!(n.abs_diff_eq(cube().normals[indices[i] as usize], 1e-6));
+      assert_eq!(mesh.uvs[i], uvs[indices[i] as usize]);
+    }
+  }
+
+  #[test]
+  fn tangents() {
+    let mut mesh = cube();
+    mesh.compute_tangents();
+    for triangle in mesh.indices.chunks_exact(3) {
+      let (u, _) = face_axes(&mesh, triangle);
+      for i in triangle {
+        let t = mesh.tangents[*i as usize];
+        assert!(t.truncate().abs_diff_eq(u, 1e-5));
+        assert_eq!(t.w, 1.0);
+      }
+    }
+    // u を反転すると tangent も反転し, bitangent はそのままなので w が -1
+    let mut mirrored = cube();
+    for uv in &mut mirrored.uvs {
+      uv.x = 1.0 - uv.x;
+    }
+    mirrored.compute_tangents();
+    for triangle in mirrored.indices.chunks_exact(3) {
+      let (u, _) = face_axes(&mirrored, triangle);
+      for i in triangle {
+        let t = mirrored.tangents[*i as usize];
+        assert!(t.truncate().abs_diff_eq(-u, 1e-5));
+        assert_eq!(t.w, -1.0);
+      }
+    }
+  }
+
+  #[test]
+  fn tangents_without_uv() {
+    // uv が潰れていても, なくても法線に直交する単位ベクトル
+    let mut degenerate = cube();
+    for uv in &mut degenerate.uvs {
+      *uv = Vec2::ZERO;
+    }
+    let mut without_uv = cube();
+    without_uv.uvs.clear();
+    without_uv.normals.clear();
+    for mesh in [&mut degenerate, &mut without_uv] {
+      mesh.compute_tangents();
+      assert_eq!(mesh.tangents.len(), 24);
+      for (n, t) in mesh.normals.iter().zip(&mesh.tangents) {
+        assert!((t.truncate().length() - 1.0).abs() < 1e-5);
+        assert!(t.truncate().dot(*n).abs() < 1e-5);
+        assert_eq!(t.w, 1.0);
+      }
+    }
+  }
+
+  #[test]
+  fn weld() {
+    let mut mesh = cube();
+    mesh.unweld();
+    assert_eq!(mesh.vertex_count(), 36);
+    // 面ごとに法線と uv が違うので 24 頂点に戻る
+    mesh.weld(1e-4);
+    assert_eq!(mesh.vertex_count(), 24);
+    assert_eq!(mesh.triangle_count(), 12);
+    // 位置だけなら 8 頂点. epsilon 未満の誤差もまとめる
+    let mut positions_only = Mesh::new(cube().positions, cube().indices);
+    positions_only.positions[0] += Vec3::splat(1e-6);
+    positions_only.weld(1e-4);
+    assert_eq!(positions_only.vertex_count(), 8);
+    assert_eq!(positions_only.triangle_count(), 12);
+    for triangle in positions_only.indices.chunks_exact(3) {
+      let [a, b, c] = positions_only.triangle_positions(triangle);
+      assert!((b - a).cross(c - a).length() > 1.0);
+    }
+    // まとめた結果潰れる三角形は消す
+    let mut degenerate = Mesh::new(
+      vec![
+        Vec3::ZERO,
+        Vec3::X,
+        Vec3::X,
+        Vec3::Y,
+        Vec3::X + Vec3::splat(1e-7),
+      ],
+      vec![0, 1, 2, 0, 1, 3, 1, 4, 3],
+    );
+    degenerate.weld(1e-4);
+    assert_eq!(degenerate.positions, vec![Vec3::ZERO, Vec3::X, Vec3::Y]);
+    assert_eq!(degenerate.indices, vec![0, 1, 2]);
+  }
+
+  #[test]
+  fn mirror_transform() {
+    let mut mesh = cube();
+    mesh.compute_tangents();
+    let mat = Mat4::from_scale(Vec3::new(-1.0, 2.0, 1.0));
+    mesh.transform(mat);
+    for triangle in mesh.indices.chunks_exact(3) {
+      // 巻き順から求めた面法線と変換した法線が一致する
+      let n = face_normal(&mesh, triangle);
+      for i in triangle {
+        assert!(mesh.normals[*i as usize].abs_diff_eq(n, 1e-5));
+      }
+    }
+    // 変換後に計算し直したものと同じ
+    let tangents = mesh.tangents.clone();
+    mesh.compute_tangents();
+    for (a, b) in tangents.iter().zip(&mesh.tangents) {
+      assert!(a.abs_diff_eq(*b, 1e-5));
+    }
+    assert!(tangents.iter().all(|t| t.w == -1.0));
+  }
+
+  #[test]
+  fn append() {
+    let mut normals_only = Mesh::new(vec![Vec3::ZERO, Vec3::X, Vec3::Y], vec![0, 1, 2]);
+    normals_only.normals = vec![Vec3::Z; 3];
+    let mut colors_only = Mesh::new(vec![Vec3::ZERO, Vec3::Y, Vec3::X], vec![0, 1, 2]);
+    colors_only.uvs = vec![Vec2::ONE; 3];
+    colors_only.colors = vec![Vec4::new(1.0, 0.0, 0.0, 1.0); 3];
+    let offset = Mat4::from_translation(Vec3::Z);
+    let merged = Mesh::merge(&[(&normals_only, Mat4::IDENTITY), (&colors_only, offset)]);
+    assert_eq!(merged.vertex_count(), 6);
+    assert_eq!(merged.indices, vec![0, 1, 2, 3, 4, 5]);
+    assert_eq!(merged.positions[4], Vec3::new(0.0, 1.0, 1.0));
+    assert_eq!(merged.normals[..3], [Vec3::Z; 3]);
+    assert_eq!(merged.normals[3..], [Vec3::ZERO; 3]);
+    assert_eq!(merged.uvs[..3], [Vec2::ZERO; 3]);
+    assert_eq!(merged.uvs[3..], [Vec2::ONE; 3]);
+    assert_eq!(merged.colors[..3], [Vec4::ONE; 3]);
+    assert_eq!(merged.colors[3..], [Vec4::new(1.0, 0.0, 0.0, 1.0); 3]);
+    // どちらにもないものは空のまま
+    assert!(merged.tangents.is_empty());
+  }
+
+  #[test]
+  fn gltf_primitive_topology() {
+    let primitive = |mode, indices: Option<Vec<u32>>| GltfPrimitive {
+      mode,
+      positions: vec![Vec3::ZERO; 5],
+      normals: Vec::new(),
+      tangents: Vec::new(),
+      uvs: Vec::new(),
+      colors: Vec::new(),
+      joints: Vec::new(),
+      weights: Vec::new(),
+      indices,
+      material: None,
+      targets: Vec::new(),
+    };
+    let indices =
+      |mode, source| Mesh::new_gltf_primitive(&primitive(mode, source)).map(|x| x.indices);
+    assert_eq!(
+      indices(GltfPrimitiveMode::TriangleStrip, None),
+      Some(vec![0, 1, 2, 2, 1, 3, 2, 3, 4])
+    );
+    assert_eq!(
+      indices(GltfPrimitiveMode::TriangleFan, None),
+      Some(vec![0, 1, 2, 0, 2, 3, 0, 3, 4])
+    );
+    assert_eq!(
+      indices(GltfPrimitiveMode::TriangleStrip, Some(vec![4, 3, 2, 1])),
+      Some(vec![4, 3, 2, 2, 3, 1])
+    );
+    assert_eq!(
+      indices(GltfPrimitiveMode::Triangles, Some(vec![2, 1, 0])),
+      Some(vec![2, 1, 0])
+    );
+    // 三角形にならないもの
+    assert_eq!(
+      indices(GltfPrimitiveMode::TriangleFan, Some(vec![0, 1])),
+      Some(vec![])
+    );
+    assert_eq!(indices(GltfPrimitiveMode::Lines, None), None);
+    assert_eq!(indices(GltfPrimitiveMode::Points, None), None);
+    // 頂点の外を指す index
+    assert_eq!(
+      indices(GltfPrimitiveMode::Triangles, Some(vec![0, 1, 5])),
+      None
+    );
+    assert_eq!(
+      indices(GltfPrimitiveMode::TriangleFan, Some(vec![0, 1, 2, 9])),
+      None
+    );
+    // 長さの合わない属性は持たない扱い
+    let mut mismatched = primitive(GltfPrimitiveMode::Triangles, Some(vec![0, 1, 4]));
+    mismatched.normals = vec![Vec3::Z; 3];
+    mismatched.uvs = vec![Vec2::ZERO; 5];
+    mismatched.tangents = vec![Vec4::X; 6];
+    mismatched.colors = vec![Vec4::ONE; 1];
+    let mut mesh = Mesh::new_gltf_primitive(&mismatched).unwrap();
+    assert!(mesh.normals.is_empty() && mesh.tangents.is_empty() && mesh.colors.is_empty());
+    assert_eq!(mesh.uvs.len(), 5);
+    mesh.compute_tangents();
+    mesh.weld(1e-4);
+    mesh.unweld();
+    // 位置が全て同じなので潰れて消える
+    assert_eq!(mesh.triangle_count(), 0);
+  }
+}
@@ -0,0 +1,488 @@
+// Wavefront OBJ / MTL の読み込み
+// 多角形は扇形に三角形分割する. 曲面(curv / surf など)は読まない
+use crate::math::*;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ObjError {
+  // 行番号(1 始まり)
+  InvalidLine(usize),
+  InvalidIndex(usize),
+}
+
+// usemtl ごとにまとめたもの. 頂点は submesh ごとに詰め直してある
+#[derive(Clone, Debug)]
+pub struct ObjSubmesh {
+  pub material: Option<String>,
+  pub positions: Vec<Vec3>,
+  // vn がなかった頂点は面から作る
+  pub normals: Vec<Vec3>,
+  // vt が一つもなければ空
+  pub uvs: Vec<Vec2>,
+  pub indices: Vec<u32>,
+}
+#[derive(Clone, Debug)]
+pub struct Obj {
+  // mtllib で指定されたファイル名
+  pub material_libraries: Vec<String>,
+  pub submeshes: Vec<ObjSubmesh>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ObjMaterial {
+  pub name: String,
+  // Ka / Kd / Ks / Ke
+  pub ambient: Vec3,
+  pub diffuse: Vec3,
+  pub specular: Vec3,
+  pub emissive: Vec3,
+  // Ns
+  pub shininess: f32,
+  // d (Tr なら 1 - Tr)
+  pub dissolve: f32,
+  // map_Kd など. ファイル名そのまま
+  pub diffuse_map: Option<String>,
+  pub specular_map: Option<String>,
+  pub normal_map: Option<String>,
+  pub emissive_map: Option<String>,
+  pub alpha_map: Option<String>,
+}
+impl ObjMaterial {
+  fn new(name: &str) -> Self {
+    Self {
+      name: name.to_string(),
+      ambient: Vec3::ZERO,
+      diffuse: Vec3::ONE,
+      specular: Vec3::ZERO,
+      emissive: Vec3::ZERO,
+      shininess: 0.0,
+      dissolve: 1.0,
+      diffuse_map: None,
+      specular_map: None,
+      normal_map: None,
+      emissive_map: None,
+      alpha_map: None,
+    }
+  }
+  // Blinn-Phong の指数から roughness への近似
+  pub fn roughness(&self) -> f32 {
+    (2.0 / (self.shininess.max(0.0) + 2.0)).sqrt()
+  }
+}
+
+// 面の頂点. v / vt / vn の index(0 始まり)
+type FaceVertex = (usize, Option<usize>, Option<usize>);
+
+#[derive(Default)]
+struct SubmeshBuilder {
+  // 同じ v / vt / vn の組は同じ頂点にする
+  vertex_map: HashMap<FaceVertex, u32>,
+  vertices: Vec<FaceVertex>,
+  indices: Vec<u32>,
+}
+impl SubmeshBuilder {
+  fn vertex(&mut self, key: FaceVertex) -> u32 {
+    let vertices = &mut self.vertices;
+    *self.vertex_map.entry(key).or_insert_with(|| {
+      vertices.push(key);
+      (vertices.len() - 1) as u32
+    })
+  }
+  fn build(
+    self,
+    material: Option<String>,
+    positions: &[Vec3],
+    normals: &[Vec3],
+    uvs: &[Vec2],
+  ) -> ObjSubmesh {
+    let has_uv = self.vertices.iter().any(|(_, vt, _)| vt.is_some());
+    // vn のない頂点は同じ位置を共有する面の法線(面積で重み付け)を足し合わせる
+    let mut generated: HashMap<usize, Vec3> = HashMap::new();
+    if self.vertices.iter().any(|(_, _, vn)| vn.is_none()) {
+      for triangle in self.indices.chunks_exact(3) {
+        let v: Vec<usize> = triangle
+          .iter()
+          .map(|i| self.vertices[*i as usize].0)
+          .collect();
+        let face_normal =
+          (positions[v[1]] - positions[v[0]]).cross(positions[v[2]] - positions[v[0]]);
+        for (i, p) in triangle.iter().zip(v) {
+          if self.vertices[*i as usize].2.is_none() {
+            *generated.entry(p).or_insert(Vec3::ZERO) += face_normal;
+          }
+        }
+      }
+    }
+    ObjSubmesh {
+      material,
+      positions: self
+        .vertices
+        .iter()
+        .map(|(v, _, _)| positions[*v])
+        .collect(),
+      normals: self
+        .vertices
+        .iter()
+        .map(|(v, _, vn)| match vn {
+          Some(vn) => normals[*vn],
+          None => generated
+            .get(v)
+            .copied()
+            .unwrap_or(Vec3::ZERO)
+            .normalize_or_zero(),
+        })
+        .collect(),
+      uvs: if has_uv {
+        self
+          .vertices
+          .iter()
+          .map(|(_, vt, _)| vt.map(|vt| uvs[vt]).unwrap_or(Vec2::ZERO))
+          .collect()
+      } else {
+        Vec::new()
+      },
+      indices: self.indices,
+    }
+  }
+}
+
+fn parse_floats<const N: usize>(
+  args: &[&str],
+  line: usize,
+  default: [f32; N],
+) -> Result<[f32; N], ObjError> {
+  let mut result = default;
+  for (i, r) in result.iter_mut().enumerate() {
+    if let Some(arg) = args.get(i) {
+      *r = arg.parse().map_err(|_| ObjError::InvalidLine(line))?;
+    } else if i == 0 {
+      return Err(ObjError::InvalidLine(line));
+    }
+  }
+  Ok(result)
+}
+// 負の index は末尾からの相対
+fn resolve_index(arg: &str, len: usize, line: usize) -> Result<usize, ObjError> {
+  let index: i64 = arg.parse().map_err(|_| ObjError::InvalidIndex(line))?;
+  let index = if index < 0 {
+    len as i64 + index
+  } else {
+    index - 1
+  };
+  if index < 0 || index as usize >= len {
+    return Err(ObjError::InvalidIndex(line));
+  }
+  Ok(index as usize)
+}
+
+impl Obj {
+  pub fn parse(text: &str) -> Result<Self, ObjError> {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut material_libraries = Vec::new();
+    // usemtl の出てきた順
+    let mut builders: Vec<(Option<String>, SubmeshBuilder)> = Vec::new();
+    let mut current = None;
+    for (i, raw_line) in text.lines().enumerate() {
+      let line = i + 1;
+      let content = raw_line.split('#').next().unwrap_or("");
+      let mut words = content.split_whitespace();
+      let keyword = match words.next() {
+        Some(keyword) => keyword,
+        None => continue,
+      };
+      let args: Vec<&str> = words.collect();
+      match keyword {
+        "v" => positions.push(Vec3::from(parse_floats(&args, line, [0.0; 3])?)),
+        "vn" => normals.push(Vec3::from(parse_floats(&args, line, [0.0; 3])?)),
+        "vt" => uvs.push(Vec2::from(parse_floats(&args, line, [0.0; 2])?)),
+        "mtllib" => material_libraries.extend(args.iter().map(|x| x.to_string())),
+        "usemtl" => {
+          let name = args.first().map(|x| x.to_string());
+          current = builders.iter().position(|(m, _)| *m == name);
+          if current.is_none() {
+            builders.push((name, SubmeshBuilder::default()));
+            current = Some(builders.len() - 1);
+          }
+        }
+        "f" => {
+          if args.len() < 3 {
+            return Err(ObjError::InvalidLine(line));
+          }
+          let mut face = Vec::with_capacity(args.len());
+          for arg in &args {
+            let mut parts = arg.split('/');
+            let v = resolve_index(parts.next().unwrap_or(""), positions.len(), line)?;
+            let vt = match parts.next() {
+              Some(x) if !x.is_empty() => Some(resolve_index(x, uvs.len(), line)?),
+              _ => None,
+            };
+            let vn = match parts.next() {
+              Some(x) if !x.is_empty() => Some(resolve_index(x, normals.len(), line)?),
+              _ => None,
+            };
+            face.push((v, vt, vn));
+          }
+          let index = match current {
+            Some(index) => index,
+            None => {
+              builders.push((None, SubmeshBuilder::default()));
+              current = Some(builders.len() - 1);
+              builders.len() - 1
+            }
+          };
+          let builder = &mut builders[index].1;
+          let first = builder.vertex(face[0]);
+          for pair in face[1..].windows(2) {
+            let a = builder.vertex(pair[0]);
+            let b = builder.vertex(pair[1]);
+            builder.indices.extend_from_slice(&[first, a, b]);
+          }
+        }
+        // o / g / s / l / p などは使わない
+        _ => {}
+      }
+    }
+    Ok(Self {
+      material_libraries,
+      submeshes: builders
+        .into_iter()
+        .filter(|(_, builder)| !builder.indices.is_empty())
+        .map(|(material, builder)| builder.build(material, &positions, &normals, &uvs))
+        .collect(),
+    })
+  }
+}
+
+impl ObjMaterial {
+  // MTL は知らない行を無視するだけなので失敗しない
+  pub fn parse_mtl(text: &str) -> Vec<Self> {
+    let mut result: Vec<Self> = Vec::new();
+    for raw_line in text.lines() {
+      let content = raw_line.split('#').next().unwrap_or("");
+      let mut words = content.split_whitespace();
+      let keyword = match words.next() {
+        Some(keyword) => keyword,
+        None => continue,
+      };
+      let args: Vec<&str> = words.collect();
+      if keyword == "newmtl" {
+        result.push(Self::new(args.first().copied().unwrap_or("")));
+        continue;
+      }
+      let material = match result.last_mut() {
+        Some(material) => material,
+        None => continue,
+      };
+      let color = || parse_floats(&args, 0, [0.0; 3]).ok().map(Vec3::from);
+      // d -halo 0.5 のようにオプションが前に付くことがあるので最後を読む
+      let scalar = || args.last().and_then(|x| x.parse::<f32>().ok());
+      // map の前にある -bm などのオプションは読み飛ばしてファイル名だけ取る
+      let map = || args.last().map(|x| x.to_string());
+      match keyword {
+        "Ka" => material.ambient = color().unwrap_or(material.ambient),
+        "Kd" => material.diffuse = color().unwrap_or(material.diffuse),
+        "Ks" => material.specular = color().unwrap_or(material.specular),
+        "Ke" => material.emissive = color().unwrap_or(material.emissive),
+        "Ns" => material.shininess = scalar().unwrap_or(material.shininess),
+        "d" => material.dissolve = scalar().unwrap_or(material.dissolve),
+        "Tr" => material.dissolve = scalar().map(|x| 1.0 - x).unwrap_or(material.dissolve),
+        "map_Kd" => material.diffuse_map = map(),
+        "map_Ks" => material.specular_map = map(),
+        "map_Ke" => material.emissive_map = map(),
+        "map_d" => material.alpha_map = map(),
+        "map_Bump" | "map_bump" | "bump" | "norm" => material.normal_map = map(),
+        _ => {}
+      }
+    }
+    result
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn relative_indices_and_forms() {
+    // 負の index は直前までに定義されたものからの相対
+    let text = "
+      v 0 0 0
+      v 1 0 0
+      v 0 1 0
+      vt 0 0
+      vt 1 0
+      vt 0 1
+      vn 0 0 1
+      f -3/-3 -2/-2 -1/-1
+      f 1//1 2//1 3//1
+      f 1/1/1 2/2/1 3/3/1
+    ";
+    let obj = Obj::parse(text).unwrap();
+    assert_eq!(obj.submeshes.len(), 1);
+    let submesh = &obj.submeshes[0];
+    assert_eq!(submesh.material, None);
+    // v/vt, v//vn, v/vt/vn は別の頂点
+    assert_eq!(submesh.positions.len(), 9);
+    assert_eq!(submesh.indices, (0..9).collect::<Vec<u32>>());
+    assert_eq!(submesh.uvs.len(), 9);
+    assert_eq!(submesh.uvs[1], Vec2::new(1.0, 0.0));
+    // vt のない頂点の uv は 0
+    assert_eq!(submesh.uvs[4], Vec2::ZERO);
+    for normal in &submesh.normals {
+      assert_eq!(*normal, Vec3::Z);
+    }
+  }
+
+  #[test]
+  fn polygon_fan_and_dedup() {
+    let text = "
+      v 0 0 0
+      v 1 0 0
+      v 1 1 0
+      v 0 1 0
+      v 0.5 1.5 0
+      f 1 2 3 5 4
+      f 1 2 3
+    ";
+    let obj = Obj::parse(text).unwrap();
+    let submesh = &obj.submeshes[0];
+    assert!(submesh.uvs.is_empty());
+    assert_eq!(submesh.positions.len(), 5);
+    assert_eq!(submesh.indices, vec![0, 1, 2, 0, 2, 3, 0, 3, 4, 0, 1, 2]);
+  }
+
+  #[test]
+  fn material_groups() {
+    let text = "
+      mtllib a.mtl b.mtl
+      v 0 0 0
+      v 1 0 0
+      v 0 1 0
+      v 0 0 1
+      f 1 2 3
+      usemtl red
+      f 1 2 4
+      usemtl blue
+      f 1 3 4
+      usemtl red
+      f 2 3 4
+      usemtl unused
+    ";
+    let obj = Obj::parse(text).unwrap();
+    assert_eq!(obj.material_libraries, vec!["a.mtl", "b.mtl"]);
+    let materials: Vec<Option<&str>> = obj
+      .submeshes
+      .iter()
+      .map(|x| x.material.as_deref())
+      .collect();
+    assert_eq!(materials, vec![None, Some("red"), Some("blue")]);
+    // 同じ usemtl は一つにまとめ, 頂点は submesh ごとに詰め直す
+    let red = &obj.submeshes[1];
+    assert_eq!(red.indices.len(), 6);
+    assert_eq!(red.positions.len(), 4);
+    assert_eq!(obj.submeshes[2].positions.len(), 3);
+  }
+
+  #[test]
+  fn generated_normals() {
+    // 面積で重み付けした平均
+    let text = "
+      v 0 0 0
+      v 2 0 0
+      v 0 2 0
+      v 0 0 1
+      f 1 2 3
+      f 1 4 2
+    ";
+    let obj = Obj::parse(text).unwrap();
+    let submesh = &obj.submeshes[0];
+    assert_eq!(submesh.normals[2], Vec3::Z);
+    assert_eq!(submesh.normals[3], Vec3::Y);
+    // 共有している頂点は面積 2 の +z と 面積 1 の +y
+    let expected = (Vec3::Z * 2.0 + Vec3::Y).normalize();
+    assert!(submesh.normals[0].abs_diff_eq(expected, 1e-6));
+    assert!(submesh.normals[1].abs_diff_eq(expected, 1e-6));
+  }
+
+  #[test]
+  fn errors() {
+    let parse = |text: &str| Obj::parse(text).err();
+    let base = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\n";
+    assert_eq!(
+      parse(&format!("{}f 1 2 4", base)),
+      Some(ObjError::InvalidIndex(5))
+    );
+    assert_eq!(
+      parse(&format!("{}f 0 1 2", base)),
+      Some(ObjError::InvalidIndex(5))
+    );
+    assert_eq!(
+      parse(&format!("{}f -4 1 2", base)),
+      Some(ObjError::InvalidIndex(5))
+    );
+    assert_eq!(
+      parse(&format!("{}f 1/1 2 3", base)),
+      Some(ObjError::InvalidIndex(5))
+    );
+    assert_eq!(
+      parse(&format!("{}f 1//2 2 3", base)),
+      Some(ObjError::InvalidIndex(5))
+    );
+    assert_eq!(
+      parse(&format!("{}f a 2 3", base)),
+      Some(ObjError::InvalidIndex(5))
+    );
+    assert_eq!(
+      parse(&format!("{}f 1 2", base)),
+      Some(ObjError::InvalidLine(5))
+    );
+    assert_eq!(parse("v 0 x 0"), Some(ObjError::InvalidLine(1)));
+    assert_eq!(parse("# comment\nv"), Some(ObjError::InvalidLine(2)));
+    // 後ろで定義されたものは参照できない
+    assert_eq!(
+      parse("v 0 0 0\nv 1 0 0\nf 1 2 3\nv 0 1 0"),
+      Some(ObjError::InvalidIndex(3))
+    );
+  }
+
+  #[test]
+  fn mtl() {
+    let text = "
+      Kd 1 0 0
+      newmtl glass
+      Kd 0.5 0.25 1 # comment
+      Ks 1 1 1
+      Ns 98
+      Tr 0.25
+      map_Kd -bm 1 -o 0.5 0.5 textures/glass.png
+      bump -bm 2 glass_n.png
+      newmtl halo
+      d -halo 0.5
+      map_d alpha.png
+      Ke 1 x 1
+      newmtl plain
+    ";
+    let materials = ObjMaterial::parse_mtl(text);
+    let names: Vec<&str> = materials.iter().map(|x| x.name.as_str()).collect();
+    assert_eq!(names, vec!["glass", "halo", "plain"]);
+    let glass = &materials[0];
+    assert_eq!(glass.diffuse, Vec3::new(0.5, 0.25, 1.0));
+    assert_eq!(glass.specular, Vec3::ONE);
+    assert_eq!(glass.shininess, 98.0);
+    assert_eq!(glass.dissolve, 0.75);
+    assert_eq!(glass.diffuse_map.as_deref(), Some("textures/glass.png"));
+    assert_eq!(glass.normal_map.as_deref(), Some("glass_n.png"));
+    assert!((glass.roughness() - 0.1414).abs() < 1e-3);
+    let halo = &materials[1];
+    assert_eq!(halo.dissolve, 0.5);
+    assert_eq!(halo.alpha_map.as_deref(), Some("alpha.png"));
+    // 読めない値は既定のまま
+    assert_eq!(halo.emissive, Vec3::ZERO);
+    let plain = &materials[2];
+    assert_eq!(plain.diffuse, Vec3::ONE);
+    assert_eq!(plain.dissolve, 1.0);
+    assert_eq!(plain.roughness(), 1.0);
+  }
+}
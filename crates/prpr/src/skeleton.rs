@@ -0,0 +1,189 @@
+// スキニング用の骨格. 描画に依存しない CPU 側の計算まで
+use crate::math::*;
+
+// 親からの相対の姿勢
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Pose {
+  pub translation: Vec3,
+  pub rotation: Quat,
+  pub scale: Vec3,
+}
+impl Default for Pose {
+  fn default() -> Self {
+    Self {
+      translation: Vec3::ZERO,
+      rotation: Quat::IDENTITY,
+      scale: Vec3::ONE,
+    }
+  }
+}
+impl Pose {
+  pub fn new(translation: Vec3, rotation: Quat, scale: Vec3) -> Self {
+    Self {
+      translation,
+      rotation,
+      scale,
+    }
+  }
+  pub fn to_mat4(&self) -> Mat4 {
+    Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+  }
+}
+
+#[derive(Clone, Debug)]
+pub struct Joint {
+  pub name: String,
+  // skeleton.joints の index
+  pub parent: Option<usize>,
+  // モデル空間からこの joint の空間へ
+  pub inverse_bind_matrix: Mat4,
+  pub rest_pose: Pose,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Skeleton {
+  pub joints: Vec<Joint>,
+  // 親のない joint の更に親の行列(glTF で骨格の外側にある node など)
+  pub root_matrix: Mat4,
+}
+impl Skeleton {
+  pub fn new(joints: Vec<Joint>) -> Self {
+    Self {
+      joints,
+      root_matrix: Mat4::IDENTITY,
+    }
+  }
+  pub fn find(&self, name: &str) -> Option<usize> {
+    self.joints.iter().position(|joint| joint.name == name)
+  }
+  pub fn rest_poses(&self) -> Vec<Pose> {
+    self.joints.iter().map(|joint| joint.rest_pose).collect()
+  }
+  // 各 joint のワールド行列. joints は親が先に並んでいなくてもよい
+  pub fn world_matrices(&self, poses: &[Pose]) -> Vec<Mat4> {
+    let mut result: Vec<Option<Mat4>> = vec![None; self.joints.len()];
+    let mut chain = Vec::new();
+    for i in 0..self.joints.len() {
+      // 計算済みの祖先まで遡ってから下る
+      let mut current = Some(i);
+      while let Some(j) = current {
+        if result[j].is_some() || chain.contains(&j) {
+          break;
+        }
+        chain.push(j);
+        current = self.joints[j].parent.filter(|p| *p < self.joints.len());
+      }
+      while let Some(j) = chain.pop() {
+        let parent = match self.joints[j]
+          .parent
+          .and_then(|p| result.get(p).copied().flatten())
+        {
+          Some(parent) => parent,
+          None => self.root_matrix,
+        };
+        let pose = poses.get(j).unwrap_or(&self.joints[j].rest_pose);
+        result[j] = Some(parent * pose.to_mat4());
+      }
+    }
+    result
+      .into_iter()
+      .map(|x| x.unwrap_or(Mat4::IDENTITY))
+      .collect()
+  }
+  // 頂点に掛ける行列(ワールド行列 x inverse bind matrix)
+  pub fn skinning_matrices(&self, poses: &[Pose]) -> Vec<Mat4> {
+    self
+      .world_matrices(poses)
+      .into_iter()
+      .zip(&self.joints)
+      .map(|(world, joint)| world * joint.inverse_bind_matrix)
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn joint(name: &str, parent: Option<usize>, translation: Vec3) -> Joint {
+    Joint {
+      name: name.to_string(),
+      parent,
+      inverse_bind_matrix: Mat4::IDENTITY,
+      rest_pose: Pose::new(translation, Quat::IDENTITY, Vec3::ONE),
+    }
+  }
+  fn origin(mat: Mat4) -> Vec3 {
+    mat.transform_point3(Vec3::ZERO)
+  }
+
+  #[test]
+  fn out_of_order_parents() {
+    // 子が親より前にあっても親から計算する
+    let mut skeleton = Skeleton::new(vec![
+      joint("hand", Some(2), Vec3::X),
+      joint("root", None, Vec3::Y),
+      joint("arm", Some(1), Vec3::X),
+      // 存在しない親は root 扱い
+      joint("broken", Some(10), Vec3::Z),
+    ]);
+    skeleton.root_matrix = Mat4::from_translation(Vec3::Z * 10.0);
+    assert_eq!(skeleton.find("arm"), Some(2));
+    assert_eq!(skeleton.find("leg"), None);
+    let world = skeleton.world_matrices(&skeleton.rest_poses());
+    assert_eq!(origin(world[1]), Vec3::new(0.0, 1.0, 10.0));
+    assert_eq!(origin(world[2]), Vec3::new(1.0, 1.0, 10.0));
+    assert_eq!(origin(world[0]), Vec3::new(2.0, 1.0, 10.0));
+    assert_eq!(origin(world[3]), Vec3::new(0.0, 0.0, 11.0));
+    // 足りない姿勢は rest_pose. 親の回転は子に伝わる
+    let rotate = Pose::new(
+      Vec3::Y,
+      Quat::from_rotation_z(std::f32::consts::FRAC_PI_2),
+      Vec3::ONE,
+    );
+    let world = skeleton.world_matrices(&[Pose::default(), rotate]);
+    assert!(origin(world[2]).abs_diff_eq(Vec3::new(0.0, 2.0, 10.0), 1e-6));
+    assert!(origin(world[0]).abs_diff_eq(Vec3::new(0.0, 2.0, 10.0), 1e-6));
+    assert_eq!(origin(world[3]), Vec3::new(0.0, 0.0, 11.0));
+  }
+
+  #[test]
+  fn cycles() {
+    // 循環していても止まり, 全ての joint に行列が入る
+    let skeleton = Skeleton::new(vec![
+      joint("a", Some(1), Vec3::X),
+      joint("b", Some(0), Vec3::Y),
+      joint("self", Some(2), Vec3::Z),
+      joint("child", Some(0), Vec3::X),
+    ]);
+    let world = skeleton.world_matrices(&skeleton.rest_poses());
+    assert_eq!(world.len(), 4);
+    // 循環の切れ目は root 扱い
+    assert_eq!(origin(world[1]), Vec3::Y);
+    assert_eq!(origin(world[0]), Vec3::new(1.0, 1.0, 0.0));
+    assert_eq!(origin(world[2]), Vec3::Z);
+    assert_eq!(origin(world[3]), Vec3::new(2.0, 1.0, 0.0));
+  }
+
+  #[test]
+  fn skinning_matrices() {
+    // bind pose のままなら単位行列
+    let mut joints = vec![
+      joint("root", None, Vec3::Y),
+      joint("child", Some(0), Vec3::new(1.0, 0.0, 2.0)),
+    ];
+    joints[0].inverse_bind_matrix = Mat4::from_translation(-Vec3::Y);
+    joints[1].inverse_bind_matrix = Mat4::from_translation(-Vec3::new(1.0, 1.0, 2.0));
+    let skeleton = Skeleton::new(joints);
+    for mat in skeleton.skinning_matrices(&skeleton.rest_poses()) {
+      assert!(mat.abs_diff_eq(Mat4::IDENTITY, 1e-6));
+    }
+    let mut poses = skeleton.rest_poses();
+    poses[0].translation += Vec3::X;
+    let skinning = skeleton.skinning_matrices(&poses);
+    assert_eq!(
+      skinning[1].transform_point3(Vec3::new(1.0, 1.0, 2.0)),
+      Vec3::new(2.0, 1.0, 2.0)
+    );
+  }
+}
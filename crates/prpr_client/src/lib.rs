@@ -1,6 +1,4 @@
 use prpr::*;
-#[macro_use]
-extern crate assert_type_eq;
 extern crate downcast;
 // for entry_point
 pub use wasm_bindgen::prelude::wasm_bindgen as entry_point;
@@ -0,0 +1,123 @@
+use super::*;
+
+// GPU ごとの上限と対応している拡張. Instance::set で一度だけ集める
+// 拡張は対応しているかどうかだけで, 有効にするのは Instance::enable_extension
+// https://webglreport.com/?v=2 と同じもの
+#[derive(Clone, Debug)]
+pub struct Capabilities {
+  pub max_draw_buffers: usize,
+  pub max_color_attachments: usize,
+  pub max_samples: usize,
+  pub max_texture_size: usize,
+  pub max_cube_map_texture_size: usize,
+  pub max_3d_texture_size: usize,
+  pub max_array_texture_layers: usize,
+  pub max_renderbuffer_size: usize,
+  pub max_vertex_attribs: usize,
+  pub max_texture_image_units: usize,
+  pub max_vertex_texture_image_units: usize,
+  pub max_combined_texture_image_units: usize,
+  pub max_uniform_buffer_bindings: usize,
+  pub max_uniform_block_size: usize,
+  pub uniform_buffer_offset_alignment: usize,
+  // EXT_color_buffer_float: 32/16bit float に描画できる
+  pub color_buffer_float: bool,
+  // EXT_color_buffer_half_float: 16bit float にだけ描画できる
+  pub color_buffer_half_float: bool,
+  // OES_texture_float_linear: 32bit float を LINEAR で引ける
+  pub texture_float_linear: bool,
+  // EXT_texture_filter_anisotropic
+  pub max_anisotropy: Option<f32>,
+  // EXT_disjoint_timer_query_webgl2
+  pub timer_query: bool,
+}
+// WebGL2 で保証されている最小値
+const MIN_DRAW_BUFFERS: usize = 4;
+const MIN_SAMPLES: usize = 4;
+const MIN_TEXTURE_SIZE: usize = 2048;
+const MIN_3D_TEXTURE_SIZE: usize = 256;
+const MIN_ARRAY_TEXTURE_LAYERS: usize = 256;
+const MIN_VERTEX_ATTRIBS: usize = 16;
+const MIN_TEXTURE_IMAGE_UNITS: usize = 16;
+const MIN_COMBINED_TEXTURE_IMAGE_UNITS: usize = 32;
+const MIN_UNIFORM_BUFFER_BINDINGS: usize = 24;
+const MIN_UNIFORM_BLOCK_SIZE: usize = 16384;
+const DEFAULT_OFFSET_ALIGNMENT: usize = 256;
+
+impl Capabilities {
+  pub fn get() -> &'static Self {
+    Instance::capabilities()
+  }
+  pub(super) fn query(ctx: &web_sys::WebGl2RenderingContext, extensions: &mut Extensions) -> Self {
+    let parameter = |pname: u32, default: usize| -> usize {
+      ctx
+        .get_parameter(pname)
+        .ok()
+        .and_then(|v| v.as_f64())
+        .map(|v| v as usize)
+        .unwrap_or(default)
+    };
+    // 上限を取るには有効にする必要がある
+    let max_anisotropy = if extensions
+      .enable(ctx, Extension::TextureFilterAnisotropic)
+      .is_some()
+    {
+      ctx
+        .get_parameter(MAX_TEXTURE_MAX_ANISOTROPY_EXT)
+        .ok()
+        .and_then(|v| v.as_f64())
+        .map(|v| v as f32)
+    } else {
+      None
+    };
+    Self {
+      max_draw_buffers: parameter(gl::MAX_DRAW_BUFFERS, MIN_DRAW_BUFFERS),
+      max_color_attachments: parameter(gl::MAX_COLOR_ATTACHMENTS, MIN_DRAW_BUFFERS),
+      max_samples: parameter(gl::MAX_SAMPLES, MIN_SAMPLES),
+      max_texture_size: parameter(gl::MAX_TEXTURE_SIZE, MIN_TEXTURE_SIZE),
+      max_cube_map_texture_size: parameter(gl::MAX_CUBE_MAP_TEXTURE_SIZE, MIN_TEXTURE_SIZE),
+      max_3d_texture_size: parameter(gl::MAX_3D_TEXTURE_SIZE, MIN_3D_TEXTURE_SIZE),
+      max_array_texture_layers: parameter(gl::MAX_ARRAY_TEXTURE_LAYERS, MIN_ARRAY_TEXTURE_LAYERS),
+      max_renderbuffer_size: parameter(gl::MAX_RENDERBUFFER_SIZE, MIN_TEXTURE_SIZE),
+      max_vertex_attribs: parameter(gl::MAX_VERTEX_ATTRIBS, MIN_VERTEX_ATTRIBS),
+      max_texture_image_units: parameter(gl::MAX_TEXTURE_IMAGE_UNITS, MIN_TEXTURE_IMAGE_UNITS),
+      max_vertex_texture_image_units: parameter(
+        gl::MAX_VERTEX_TEXTURE_IMAGE_UNITS,
+        MIN_TEXTURE_IMAGE_UNITS,
+      ),
+      max_combined_texture_image_units: parameter(
+        gl::MAX_COMBINED_TEXTURE_IMAGE_UNITS,
+        MIN_COMBINED_TEXTURE_IMAGE_UNITS,
+      ),
+      max_uniform_buffer_bindings: parameter(
+        gl::MAX_UNIFORM_BUFFER_BINDINGS,
+        MIN_UNIFORM_BUFFER_BINDINGS,
+      ),
+      max_uniform_block_size: parameter(gl::MAX_UNIFORM_BLOCK_SIZE, MIN_UNIFORM_BLOCK_SIZE),
+      uniform_buffer_offset_alignment: parameter(
+        gl::UNIFORM_BUFFER_OFFSET_ALIGNMENT,
+        DEFAULT_OFFSET_ALIGNMENT,
+      )
+      .max(1),
+      color_buffer_float: extensions.is_supported(Extension::ColorBufferFloat),
+      color_buffer_half_float: extensions.is_supported(Extension::ColorBufferHalfFloat),
+      texture_float_linear: extensions.is_supported(Extension::TextureFloatLinear),
+      max_anisotropy,
+      timer_query: extensions.is_supported(Extension::DisjointTimerQuery),
+    }
+  }
+  // RenderPass で使える color target の数
+  pub fn max_output_slot(&self) -> usize {
+    self
+      .max_draw_buffers
+      .min(self.max_color_attachments)
+      .min(MAX_OUTPUT_SLOT)
+  }
+  pub fn max_texture_size_of(&self, target: u32) -> usize {
+    match target {
+      gl::TEXTURE_CUBE_MAP => self.max_cube_map_texture_size,
+      gl::TEXTURE_3D => self.max_3d_texture_size,
+      _ => self.max_texture_size,
+    }
+  }
+}
@@ -1,31 +1,258 @@
 use super::*;
+use std::ops::Range;
 // xN のGpuバッファを内部に持つ？
 // -> デフォルトは1で後からResizeを設定、くらいの流れで
 // -> 差はここで吸収
 
-pub struct IndexBuffer {
-  raw_buffer: RawBuffer,
-  data: Vec<IndexBufferType>,
+#[derive(Clone, Copy, PartialEq)]
+pub enum BufferMode {
+  Static,      // 一度書いたらほぼ変更しない
+  Dynamic,     // 変更された範囲だけを bind 時に転送する
+  Ring(usize), // 毎フレーム作り直すもの(デバッグ線・UIなど). N個のGpuバッファを順番に使う
 }
-impl IndexBuffer {
-  pub fn new(data: Vec<IndexBufferType>) -> Self {
+impl BufferMode {
+  fn store_type(&self) -> u32 {
+    match self {
+      Self::Static => gl::STATIC_DRAW,
+      Self::Dynamic => gl::DYNAMIC_DRAW,
+      Self::Ring(_) => gl::STREAM_DRAW,
+    }
+  }
+  fn ring_count(&self) -> usize {
+    match self {
+      Self::Ring(count) => (*count).max(1),
+      _ => 1,
+    }
+  }
+}
+
+struct RawBufferRing {
+  raw_buffers: Vec<RawBuffer>,
+  capacity: usize, // 要素数
+  current: usize,
+  dirty: Option<Range<usize>>,
+}
+// CPU側のコピーを持ち、変更された範囲を bind 時にまとめて転送する
+// 容量が足りなくなったら倍々で確保し直す
+struct BufferStorage<T> {
+  data: Vec<T>,
+  usage: BufferUsage,
+  mode: BufferMode,
+  ring: SRwLock<RawBufferRing>,
+}
+impl<T> BufferStorage<T> {
+  fn new(data: Vec<T>, usage: BufferUsage, mode: BufferMode) -> Self {
+    let capacity = data.len().max(1);
+    let raw_buffers = Self::allocate(capacity, usage, mode);
+    raw_buffers[0].write(0, data.as_slice());
     Self {
-      raw_buffer: RawBuffer::new(data.as_slice(), BufferUsage::Index),
       data,
+      usage,
+      mode,
+      ring: SRwLock::new(RawBufferRing {
+        raw_buffers,
+        capacity,
+        current: 0,
+        dirty: None,
+      }),
+    }
+  }
+  fn allocate(capacity: usize, usage: BufferUsage, mode: BufferMode) -> Vec<RawBuffer> {
+    (0..mode.ring_count())
+      .map(|_| {
+        RawBuffer::new_uninitialized_with_store_type::<T>(capacity, usage, mode.store_type())
+      })
+      .collect()
+  }
+  fn mark_dirty(&mut self, range: Range<usize>) {
+    let mut ring = self.ring.write();
+    ring.dirty = Some(match &ring.dirty {
+      Some(dirty) => dirty.start.min(range.start)..dirty.end.max(range.end),
+      None => range,
+    });
+  }
+  fn flush(&self) {
+    let mut ring = self.ring.write();
+    let len = self.data.len();
+    // context lost 後は CPU 側のコピーから全て書き直す
+    let mut restored = false;
+    for raw_buffer in &ring.raw_buffers {
+      restored |= raw_buffer.restore();
+    }
+    if restored {
+      ring.dirty = Some(0..len);
+    }
+    if len > ring.capacity {
+      let capacity = len.max(ring.capacity * 2);
+      ring.raw_buffers = Self::allocate(capacity, self.usage, self.mode);
+      ring.capacity = capacity;
+      ring.current = 0;
+      ring.dirty = Some(0..len);
+    }
+    if let Some(dirty) = ring.dirty.take() {
+      let range = if let BufferMode::Ring(_) = self.mode {
+        // 前のフレームで使ったバッファには触らず、次のバッファに全て書く
+        ring.current = (ring.current + 1) % ring.raw_buffers.len();
+        0..len
+      } else {
+        dirty.start.min(len)..dirty.end.min(len)
+      };
+      if range.start < range.end {
+        ring.raw_buffers[ring.current].write(range.start, &self.data[range]);
+      }
+    }
+  }
+  fn raw_buffer(&self) -> SDerefable<'_, RawBuffer> {
+    SDerefable::map(self.ring.read(), |ring| &ring.raw_buffers[ring.current])
+  }
+  fn contains_buffer_id(&self, buffer_id: u64) -> bool {
+    let ring = self.ring.read();
+    ring.raw_buffers.iter().any(|x| x.buffer_id() == buffer_id)
+  }
+  fn push(&mut self, value: T) {
+    self.data.push(value);
+    self.mark_dirty(self.data.len() - 1..self.data.len());
+  }
+  fn extend<I: IntoIterator<Item = T>>(&mut self, values: I) {
+    let start = self.data.len();
+    self.data.extend(values);
+    self.mark_dirty(start..self.data.len());
+  }
+  fn modify(&mut self, index: usize, value: T) {
+    if let Some(dst) = self.get_mut(index) {
+      *dst = value;
+    }
+  }
+  fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+    if index >= self.data.len() {
+      log::error(format!(
+        "buffer index out of range: index:{}, len:{}",
+        index,
+        self.data.len()
+      ));
+      return None;
+    }
+    self.mark_dirty(index..index + 1);
+    self.data.get_mut(index)
+  }
+  fn set(&mut self, data: Vec<T>) {
+    self.data = data;
+    self.mark_dirty(0..self.data.len());
+  }
+  fn truncate(&mut self, len: usize) {
+    // 描画数が減るだけなので転送は不要
+    self.data.truncate(len);
+  }
+  fn clear(&mut self) {
+    self.data.clear();
+  }
+}
+
+// u8 / u16 / u32 が使える. 小さいメッシュは u16 などにするとメモリが減る
+pub trait IndexElement: Copy + 'static {
+  const FORMAT: IndexFormat;
+}
+impl IndexElement for u8 {
+  const FORMAT: IndexFormat = IndexFormat::u8;
+}
+impl IndexElement for u16 {
+  const FORMAT: IndexFormat = IndexFormat::u16;
+}
+impl IndexElement for u32 {
+  const FORMAT: IndexFormat = IndexFormat::u32;
+}
+
+// 頂点数が収まるなら u16 にした index
+// (u8 は ANGLE などで変換が入り遅いので自動では選ばない)
+// WebGL2 は primitive restart が常に有効なので, 型の最大値は index に使えない
+pub enum FittedIndices {
+  U16(Vec<u16>),
+  U32(Vec<u32>),
+}
+impl FittedIndices {
+  // 頂点数以上の index があれば何も描かない
+  pub fn new(vertex_count: usize, indices: &[u32]) -> Self {
+    let indices = match indices.iter().find(|i| **i as usize >= vertex_count) {
+      Some(i) => {
+        log::error(format!(
+          "index {} is out of range (vertex count: {})",
+          i, vertex_count
+        ));
+        &[]
+      }
+      None => indices,
+    };
+    if vertex_count <= u16::MAX as usize {
+      Self::U16(indices.iter().map(|i| *i as u16).collect())
+    } else {
+      Self::U32(indices.to_vec())
     }
   }
-  pub fn raw_buffer(&self) -> &RawBuffer {
-    &self.raw_buffer
+}
+
+pub struct IndexBuffer<I: IndexElement = IndexBufferType> {
+  storage: BufferStorage<I>,
+}
+impl<I: IndexElement> IndexBuffer<I> {
+  pub fn new(data: Vec<I>) -> Self {
+    Self::new_with_mode(data, BufferMode::Static)
+  }
+  pub fn new_dynamic(data: Vec<I>) -> Self {
+    Self::new_with_mode(data, BufferMode::Dynamic)
+  }
+  pub fn new_ring(data: Vec<I>, ring_count: usize) -> Self {
+    Self::new_with_mode(data, BufferMode::Ring(ring_count))
+  }
+  pub fn new_with_mode(data: Vec<I>, mode: BufferMode) -> Self {
+    Self {
+      storage: BufferStorage::new(data, BufferUsage::Index, mode),
+    }
+  }
+  // bind 時に呼ばれる. 変更があれば転送する
+  pub fn flush(&self) {
+    self.storage.flush();
+  }
+  pub fn raw_buffer(&self) -> SDerefable<'_, RawBuffer> {
+    self.storage.raw_buffer()
+  }
+  pub fn contains_buffer_id(&self, buffer_id: u64) -> bool {
+    self.storage.contains_buffer_id(buffer_id)
+  }
+  pub fn data(&self) -> &[I] {
+    &self.storage.data
   }
   pub fn len(&self) -> usize {
-    self.data.len()
+    self.storage.data.len()
+  }
+  pub fn is_empty(&self) -> bool {
+    self.storage.data.is_empty()
+  }
+  pub fn format(&self) -> IndexFormat {
+    I::FORMAT
+  }
+  pub fn push(&mut self, value: I) {
+    self.storage.push(value);
+  }
+  pub fn extend<V: IntoIterator<Item = I>>(&mut self, values: V) {
+    self.storage.extend(values);
+  }
+  pub fn modify(&mut self, index: usize, value: I) {
+    self.storage.modify(index, value);
+  }
+  pub fn set(&mut self, data: Vec<I>) {
+    self.storage.set(data);
+  }
+  pub fn truncate(&mut self, len: usize) {
+    self.storage.truncate(len);
+  }
+  pub fn clear(&mut self) {
+    self.storage.clear();
   }
 }
 
 pub struct VertexBuffer<T: BufferAttribute> {
-  raw_buffer: RawBuffer,
+  storage: BufferStorage<T>,
   template: VsInTemplate,
-  data: Vec<T>,
 }
 
 impl<T: BufferAttribute> VertexBuffer<T> {
@@ -36,19 +263,89 @@ impl<T: BufferAttribute> VertexBuffer<T> {
       Default::default()
     };
     Self {
-      raw_buffer: RawBuffer::new(data.as_slice(), BufferUsage::Vertex),
+      storage: BufferStorage::new(data, BufferUsage::Vertex, BufferMode::Static),
       template,
-      data,
     }
   }
-  pub fn raw_buffer(&self) -> &RawBuffer {
-    &self.raw_buffer
+  // bind 時に呼ばれる. 変更があれば転送する
+  pub fn flush(&self) {
+    self.storage.flush();
+  }
+  pub fn raw_buffer(&self) -> SDerefable<'_, RawBuffer> {
+    self.storage.raw_buffer()
+  }
+  pub fn contains_buffer_id(&self, buffer_id: u64) -> bool {
+    self.storage.contains_buffer_id(buffer_id)
   }
   pub fn template(&self) -> &VsInTemplate {
     &self.template
   }
+  pub fn data(&self) -> &[T] {
+    &self.storage.data
+  }
   pub fn len(&self) -> usize {
-    self.data.len()
+    self.storage.data.len()
+  }
+  pub fn is_empty(&self) -> bool {
+    self.storage.data.is_empty()
+  }
+  pub fn push(&mut self, value: T) {
+    self.storage.push(value);
+  }
+  pub fn extend<I: IntoIterator<Item = T>>(&mut self, values: I) {
+    self.storage.extend(values);
+  }
+  pub fn modify(&mut self, index: usize, value: T) {
+    self.storage.modify(index, value);
+  }
+  pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+    self.storage.get_mut(index)
+  }
+  pub fn set(&mut self, data: Vec<T>) {
+    self.storage.set(data);
+  }
+  pub fn truncate(&mut self, len: usize) {
+    self.storage.truncate(len);
+  }
+  pub fn clear(&mut self) {
+    self.storage.clear();
+  }
+}
+// 空から始められるように Default から VsInTemplate を作る
+impl<T: BufferAttribute + Default> VertexBuffer<T> {
+  pub fn new_dynamic(data: Vec<T>) -> Self {
+    Self::new_with_mode(data, BufferMode::Dynamic)
+  }
+  pub fn new_ring(data: Vec<T>, ring_count: usize) -> Self {
+    Self::new_with_mode(data, BufferMode::Ring(ring_count))
+  }
+  pub fn new_with_mode(data: Vec<T>, mode: BufferMode) -> Self {
+    Self {
+      template: T::default().vs_in_template(),
+      storage: BufferStorage::new(data, BufferUsage::Vertex, mode),
+    }
+  }
+}
+
+// Vao に追加で持たせる頂点バッファ(morph target の差分など). 型を消して持つ
+pub trait VertexStreamTrait {
+  fn flush(&self);
+  fn raw_buffer(&self) -> SDerefable<'_, RawBuffer>;
+  fn contains_buffer_id(&self, buffer_id: u64) -> bool;
+  fn template(&self) -> &VsInTemplate;
+}
+impl<T: BufferAttribute> VertexStreamTrait for VertexBuffer<T> {
+  fn flush(&self) {
+    VertexBuffer::flush(self);
+  }
+  fn raw_buffer(&self) -> SDerefable<'_, RawBuffer> {
+    VertexBuffer::raw_buffer(self)
+  }
+  fn contains_buffer_id(&self, buffer_id: u64) -> bool {
+    VertexBuffer::contains_buffer_id(self, buffer_id)
+  }
+  fn template(&self) -> &VsInTemplate {
+    VertexBuffer::template(self)
   }
 }
 
@@ -56,16 +353,18 @@ pub trait UniformBufferTrait {
   fn bind(&self, cmd: &mut Command);
 }
 pub struct UniformBuffer<T: BufferAttribute> {
-  raw_buffer: RawBuffer,
+  slice: UniformBufferSlice,
   data: T,
   name: &'static str,
   is_dirty: SRwLock<bool>,
 }
 impl<T: BufferAttribute> UniformBuffer<T> {
   pub fn new(data: T) -> Self {
+    let slice = UniformBufferSlice::new(data.ub_data().len());
+    slice.write(data.ub_data());
     Self {
       name: data.name(),
-      raw_buffer: RawBuffer::new_untyped(data.ub_data(), BufferUsage::Uniform),
+      slice,
       is_dirty: SRwLock::new(false),
       data,
     }
@@ -90,13 +389,13 @@ impl<T: BufferAttribute> UniformBufferTrait for UniformBuffer<T> {
     {
       let mut is_dirty_lock = self.is_dirty.write();
       if *is_dirty_lock {
-        self.raw_buffer.write_untyped(0, self.data.ub_data());
+        self.slice.write(self.data.ub_data());
         *is_dirty_lock = false;
       }
     }
     if let Some(shader) = cmd.current_shader() {
       if let Some(index) = shader.uniform_block_index(self.name) {
-        cmd.set_ubo(&self.raw_buffer, index);
+        self.slice.bind(cmd, index);
       }
     }
   }
@@ -126,7 +425,7 @@ pub trait RefInto<T> {
   fn ref_into(&self) -> T;
 }
 pub struct IntoUniformBuffer<T: BufferAttribute, I: RefInto<T>> {
-  raw_buffer: RawBuffer,
+  slice: UniformBufferSlice,
   name: &'static str,
   phantom_data: std::marker::PhantomData<T>,
   into: I,
@@ -137,7 +436,7 @@ impl<T: BufferAttribute, I: RefInto<T>> IntoUniformBuffer<T, I> {
     let data = (&into).ref_into();
     Self {
       name: data.name(),
-      raw_buffer: RawBuffer::new_untyped(data.ub_data(), BufferUsage::Uniform),
+      slice: UniformBufferSlice::new(data.ub_data().len()),
       is_dirty: SRwLock::new(true),
       phantom_data: std::marker::PhantomData,
       into: into,
@@ -163,13 +462,13 @@ impl<T: BufferAttribute, I: RefInto<T>> UniformBufferTrait for IntoUniformBuffer
       let mut is_dirty_lock = self.is_dirty.write();
       if *is_dirty_lock {
         let data: T = self.into.ref_into();
-        self.raw_buffer.write_untyped(0, data.ub_data());
+        self.slice.write(data.ub_data());
         *is_dirty_lock = false;
       }
     }
     if let Some(shader) = cmd.current_shader() {
       if let Some(index) = shader.uniform_block_index(self.name) {
-        cmd.set_ubo(&self.raw_buffer, index);
+        self.slice.bind(cmd, index);
       }
     }
   }
@@ -1,46 +1,36 @@
 use super::*;
 
-const MAX_UNIFORM_BUFFER_BINDINGS: usize = 64;
-const MAX_UNIFORM_TEXTURE_BINDINGS: usize = 64;
-
 pub struct Command {
-  depth_func: Option<DepthFunc>,
-  cull_mode: Option<CullMode>,
+  pipeline_state: Option<PipelineStateObject>,
   shader: Option<SRc<Shader>>,
   vao: Option<u64>,
   // NOTE: この２つは同じものを取らない...はず
-  uniform_buffers: [Option<u64>; MAX_UNIFORM_BUFFER_BINDINGS],
-  uniform_textures: [Option<u64>; MAX_UNIFORM_TEXTURE_BINDINGS],
+  // (buffer_id, offset)
+  // 長さは Capabilities の上限に合わせる
+  uniform_buffers: Vec<Option<(u64, i32)>>,
+  uniform_textures: Vec<Option<u64>>,
 }
 
 impl Command {
   pub fn new() -> Self {
+    let capabilities = Instance::capabilities();
     Self {
-      depth_func: None,
-      cull_mode: None,
+      pipeline_state: None,
       shader: None,
       vao: None,
-      uniform_buffers: [None; MAX_UNIFORM_BUFFER_BINDINGS],
-      uniform_textures: [None; MAX_UNIFORM_TEXTURE_BINDINGS],
-    }
-  }
-  pub fn set_depth_func(&mut self, v: DepthFunc) {
-    if let Some(pre) = self.depth_func {
-      if pre == v {
-        return;
-      }
+      uniform_buffers: vec![None; capabilities.max_uniform_buffer_bindings],
+      uniform_textures: vec![None; capabilities.max_combined_texture_image_units],
     }
-    v.apply();
-    self.depth_func = Some(v);
   }
-  pub fn set_cull_mode(&mut self, v: CullMode) {
-    if let Some(pre) = self.cull_mode {
-      if pre == v {
+  pub fn set_pipeline_state(&mut self, v: &PipelineStateObject) {
+    if let Some(pre) = &self.pipeline_state {
+      if pre.id() == v.id() {
         return;
       }
     }
-    v.apply();
-    self.cull_mode = Some(v);
+    let pre = self.pipeline_state.take();
+    v.state().apply_diff(pre.as_ref().map(|p| p.state()));
+    self.pipeline_state = Some(v.clone());
   }
   pub fn set_draw_command(&mut self, v: &DrawCommand, t: PrimitiveToporogy) {
     v.apply(t);
@@ -63,28 +53,45 @@ impl Command {
         return;
       }
     }
-    let ctx = Instance::ctx();
-    ctx.bind_vertex_array(Some(vao.raw_vao()));
+    Instance::bind_vertex_array(Some(&vao.raw_vao()));
     self.vao = Some(vao.vao_id());
   }
   pub fn set_ubo(&mut self, ubo: &RawBuffer, index: u32) {
-    if index as usize >= self.uniform_buffers.len() {
-      log::error("uniform buffer length exceeded");
+    if self.is_same_ubo(ubo, index, 0) {
+      return;
     }
-    if let Some(pre) = self.uniform_buffers[index as usize] {
-      if pre == ubo.buffer_id() {
-        return;
-      }
+    let ctx = Instance::ctx();
+    ctx.bind_buffer_base(gl::UNIFORM_BUFFER, index, Some(&ubo.raw_buffer()));
+    self.uniform_buffers[index as usize] = Some((ubo.buffer_id(), 0));
+  }
+  pub fn set_ubo_range(&mut self, ubo: &RawBuffer, index: u32, offset: i32, size: i32) {
+    if self.is_same_ubo(ubo, index, offset) {
+      return;
     }
     let ctx = Instance::ctx();
-    ctx.bind_buffer_base(gl::UNIFORM_BUFFER, index, Some(ubo.raw_buffer()));
-    self.uniform_buffers[index as usize] = Some(ubo.buffer_id());
+    ctx.bind_buffer_range_with_i32_and_i32(
+      gl::UNIFORM_BUFFER,
+      index,
+      Some(&ubo.raw_buffer()),
+      offset,
+      size,
+    );
+    self.uniform_buffers[index as usize] = Some((ubo.buffer_id(), offset));
+  }
+  // 範囲外なら true を返して bind させない
+  fn is_same_ubo(&self, ubo: &RawBuffer, index: u32, offset: i32) -> bool {
+    if index as usize >= self.uniform_buffers.len() {
+      log::error("uniform buffer length exceeded");
+      return true;
+    }
+    self.uniform_buffers[index as usize] == Some((ubo.buffer_id(), offset))
   }
   pub fn set_uniform_texture(&mut self, texture: &RawTexture, utl: &UniformTextureLocation) {
     let (location, index) = utl;
     let index = *index as usize;
     if index >= self.uniform_textures.len() {
       log::error("texture binding index exceeded");
+      return;
     }
     let ctx = Instance::ctx();
     if let Some(pre) = self.uniform_textures[index] {
@@ -26,6 +26,9 @@ impl Descriptor {
   pub fn add_texture_mapping(&mut self, mapping: Box<dyn TextureMappingTrait>) {
     self.u_mappings.push(mapping);
   }
+  pub fn vao_draw_command(&self) -> Option<DrawCommand> {
+    self.vao.as_ref().map(|vao| vao.draw_command())
+  }
 }
 pub enum DescriptorContext {
   Cons {
@@ -90,7 +90,12 @@ impl RenderPassExecuterImpl {
     self.add(&pass, order);
     self.owns.push(pass);
   }
+  // context lost 中は何も描かない. restore 後は最初に restore hook を呼ぶ
   pub fn execute(&mut self) {
+    if Instance::is_context_lost() {
+      return;
+    }
+    Instance::process_restore();
     if self.need_sort {
       self.passes.sort_by(|a, b| a.order.cmp(&b.order));
       self.need_sort = false;
@@ -19,8 +19,12 @@ mod descriptorset;
 pub use self::descriptorset::*;
 mod buffer;
 pub use self::buffer::*;
+mod uniform_buffer_arena;
+pub use self::uniform_buffer_arena::*;
 mod texture;
 pub use self::texture::*;
+mod readback;
+pub use self::readback::*;
 mod shader;
 pub use self::shader::*;
 mod template;
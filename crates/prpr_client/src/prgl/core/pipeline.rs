@@ -2,10 +2,10 @@ use super::*;
 
 pub struct Pipeline {
   // states
-  depth_func: DepthFunc,
+  state: PipelineStateObject,
   draw_command: Option<DrawCommand>,
-  cull_mode: CullMode,
-  primitive_topology: PrimitiveToporogy,
+  // true なら描画数は Vao の現在の長さに従う(動的なバッファ用)
+  use_vao_draw_command: bool,
   shader: Option<SRc<Shader>>,
   invisible_reasons: collections::BitSet64,
   descriptor: SOwner<Descriptor>,
@@ -14,10 +14,9 @@ pub struct Pipeline {
 impl Pipeline {
   pub fn new() -> Self {
     Self {
-      depth_func: DepthFunc::Less,
+      state: PipelineStateObject::default(),
       draw_command: None,
-      cull_mode: CullMode::Back,
-      primitive_topology: PrimitiveToporogy::Triangles,
+      use_vao_draw_command: false,
       shader: None,
       invisible_reasons: collections::BitSet64::new(),
       descriptor: SOwner::new(Descriptor::new()),
@@ -35,10 +34,14 @@ impl Pipeline {
       // log::error("No Shader Program");
       return;
     }
-    cmd.set_depth_func(self.depth_func);
-    cmd.set_cull_mode(self.cull_mode);
-    if let Some(draw_command) = &self.draw_command {
-      cmd.set_draw_command(draw_command, self.primitive_topology);
+    cmd.set_pipeline_state(&self.state);
+    let draw_command = if self.use_vao_draw_command {
+      self.descriptor.read().vao_draw_command()
+    } else {
+      self.draw_command
+    };
+    if let Some(draw_command) = &draw_command {
+      cmd.set_draw_command(draw_command, self.state.state().topology);
     } else {
       log::error("No Draw Command");
       return;
@@ -49,13 +52,19 @@ impl Pipeline {
   pub fn set_shader(&mut self, shader: &SRc<Shader>) {
     self.shader = Some(SRc::clone(shader));
   }
-  pub fn set_vao<T: BufferAttribute + 'static>(&mut self, vao: &dyn SReaderTrait<Vao<T>>) {
+  pub fn set_vao<T: BufferAttribute + 'static, I: IndexElement>(
+    &mut self,
+    vao: &dyn SReaderTrait<Vao<T, I>>,
+  ) {
     let mut descriptor = self.descriptor.write();
     descriptor.set_vao(Box::new(vao.clone_reader()) as Box<dyn VaoTrait>);
   }
-  pub fn set_draw_vao<T: BufferAttribute + 'static>(&mut self, vao: &dyn SReaderTrait<Vao<T>>) {
+  pub fn set_draw_vao<T: BufferAttribute + 'static, I: IndexElement>(
+    &mut self,
+    vao: &dyn SReaderTrait<Vao<T, I>>,
+  ) {
     self.set_vao(vao);
-    self.set_draw_command(vao.read().draw_command());
+    self.use_vao_draw_command = true;
   }
   pub fn add_uniform_buffer_trait(&mut self, buffer: Box<dyn UniformBufferTrait>) {
     let mut descriptor = self.descriptor.write();
@@ -100,18 +109,38 @@ impl Pipeline {
   ) {
     self.add_texture_mapping(mapping);
   }
+  // state
+  pub fn set_state(&mut self, state: &PipelineStateObject) {
+    self.state = state.clone();
+  }
+  pub fn state(&self) -> &PipelineStateObject {
+    &self.state
+  }
   pub fn set_cull_mode(&mut self, mode: CullMode) {
-    self.cull_mode = mode;
+    self.state = self.state.to_builder().cull_mode(mode).build();
   }
   // draw
   pub fn set_draw_command(&mut self, command: DrawCommand) {
     self.draw_command = Some(command);
+    self.use_vao_draw_command = false;
   }
   pub fn set_depth_func(&mut self, depth_func: DepthFunc) {
-    self.depth_func = depth_func;
+    self.state = self.state.to_builder().depth_func(depth_func).build();
+  }
+  pub fn set_depth_write(&mut self, depth_write: bool) {
+    self.state = self.state.to_builder().depth_write(depth_write).build();
+  }
+  pub fn set_depth_range(&mut self, near: f32, far: f32) {
+    let depth_range = DepthRange::new(near, far);
+    self.state = self.state.to_builder().depth_range(depth_range).build();
+  }
+  // None で無効. decal などの z-fighting 回避用
+  pub fn set_polygon_offset(&mut self, polygon_offset: Option<PolygonOffset>) {
+    let builder = self.state.to_builder().polygon_offset(polygon_offset);
+    self.state = builder.build();
   }
   pub fn set_draw_mode(&mut self, primitive_topology: PrimitiveToporogy) {
-    self.primitive_topology = primitive_topology;
+    self.state = self.state.to_builder().topology(primitive_topology).build();
   }
   pub fn set_invisible(&mut self, invisible: bool, reason: usize) {
     self.invisible_reasons.set(reason, invisible);
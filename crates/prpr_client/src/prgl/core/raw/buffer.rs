@@ -7,6 +7,7 @@ pub enum BufferUsage {
   TransformFeedback = gl::TRANSFORM_FEEDBACK_BUFFER as isize,
   TransferSrc = gl::COPY_READ_BUFFER as isize,
   TransferDst = gl::COPY_WRITE_BUFFER as isize,
+  PixelPack = gl::PIXEL_PACK_BUFFER as isize,
 }
 fn usage_to_store_type(usage: BufferUsage) -> u32 {
   // https://developer.mozilla.org/ja/docs/Web/API/WebGLRenderingContext/bufferData
@@ -17,14 +18,16 @@ fn usage_to_store_type(usage: BufferUsage) -> u32 {
     BufferUsage::TransformFeedback => gl::STREAM_COPY,
     BufferUsage::TransferSrc => gl::STATIC_DRAW,
     BufferUsage::TransferDst => gl::STATIC_READ,
+    BufferUsage::PixelPack => gl::STREAM_READ,
   }
 }
 use std::sync::atomic::{AtomicUsize, Ordering};
 static ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
 pub struct RawBuffer {
-  buffer: web_sys::WebGlBuffer,
+  buffer: RawHandle<web_sys::WebGlBuffer>,
   size: i32,
   usage: BufferUsage,
+  store_type: u32,
   buffer_id: u64,
 }
 impl RawBuffer {
@@ -43,20 +46,58 @@ impl RawBuffer {
     Self::new_uninitialized_untyped(u8_size as i32, usage)
   }
   pub fn new_uninitialized_untyped(size: i32, usage: BufferUsage) -> Self {
+    Self::new_uninitialized_untyped_with_store_type(size, usage, usage_to_store_type(usage))
+  }
+  pub fn new_uninitialized_with_store_type<T>(
+    count: usize,
+    usage: BufferUsage,
+    store_type: u32,
+  ) -> Self {
+    let u8_size = std::mem::size_of::<T>() * count;
+    Self::new_uninitialized_untyped_with_store_type(u8_size as i32, usage, store_type)
+  }
+  pub fn new_uninitialized_untyped_with_store_type(
+    size: i32,
+    usage: BufferUsage,
+    store_type: u32,
+  ) -> Self {
+    Self {
+      buffer: RawHandle::new_or_lost(Self::create(size, usage, store_type)),
+      size,
+      usage,
+      store_type,
+      buffer_id: ID_COUNTER.fetch_add(1, Ordering::SeqCst) as u64,
+    }
+  }
+  // context lost 中は None
+  fn create(size: i32, usage: BufferUsage, store_type: u32) -> Option<web_sys::WebGlBuffer> {
     let ctx = Instance::ctx();
-    let buffer = ctx.create_buffer().expect("failed to craete buffer");
+    let buffer = ctx.create_buffer()?;
     let target = usage as u32;
+    // ELEMENT_ARRAY_BUFFER は VAO の状態なので、bind中のVAOを書き換えないように外しておく
+    let bound_vao = if usage == BufferUsage::Index {
+      Self::unbind_vao()
+    } else {
+      None
+    };
     ctx.bind_buffer(target, Some(&buffer));
-    ctx.buffer_data_with_i32(target, size, usage_to_store_type(usage));
+    ctx.buffer_data_with_i32(target, size, store_type);
     if SET_BIND_NONE_AFTER_WORK {
       ctx.bind_buffer(target, None);
     }
-    Self {
-      buffer,
-      size,
-      usage,
-      buffer_id: ID_COUNTER.fetch_add(1, Ordering::SeqCst) as u64,
+    if usage == BufferUsage::Index {
+      Instance::bind_vertex_array(bound_vao.as_ref());
     }
+    Some(buffer)
+  }
+  // context lost で無効になっていれば同じ大きさで作り直す. 中身は持ち主が書き直すこと
+  pub fn restore(&self) -> bool {
+    self
+      .buffer
+      .restore(|| Self::create(self.size, self.usage, self.store_type))
+  }
+  pub fn is_lost(&self) -> bool {
+    self.buffer.is_lost()
   }
   pub fn write<T>(&self, offset: usize, data: &[T]) {
     let u8_size = ::std::mem::size_of::<T>() * data.len();
@@ -74,16 +115,47 @@ impl RawBuffer {
       ));
       return;
     }
-    let target = self.usage as u32;
+    let target = self.write_target();
     let ctx = Instance::ctx();
-    ctx.bind_buffer(target, Some(&self.buffer));
+    ctx.bind_buffer(target, Some(&self.buffer.get()));
     ctx.buffer_sub_data_with_i32_and_u8_array(target, offset, data);
     if SET_BIND_NONE_AFTER_WORK {
       ctx.bind_buffer(target, None);
     }
   }
-  pub fn raw_buffer(&self) -> &web_sys::WebGlBuffer {
-    &self.buffer
+  pub fn read_untyped(&self, offset: i32, data: &mut [u8]) {
+    let size = offset + data.len() as i32;
+    if offset < 0 || size > self.size {
+      log::error(format!(
+        "invalid buffer read size: offset:{}, size:{}, reserved:{}",
+        offset, size, self.size
+      ));
+      return;
+    }
+    let target = self.usage as u32;
+    let ctx = Instance::ctx();
+    ctx.bind_buffer(target, Some(&self.buffer.get()));
+    ctx.get_buffer_sub_data_with_i32_and_u8_array(target, offset, data);
+    // PIXEL_PACK_BUFFER が bind されたままだと同期の readPixels ができない
+    ctx.bind_buffer(target, None);
+  }
+  // 作成後の Index Buffer は COPY_WRITE_BUFFER 経由で書く(bind中のVAOに影響させない)
+  fn write_target(&self) -> u32 {
+    if self.usage == BufferUsage::Index {
+      gl::COPY_WRITE_BUFFER
+    } else {
+      self.usage as u32
+    }
+  }
+  fn unbind_vao() -> Option<web_sys::WebGlVertexArrayObject> {
+    let bound_vao = Instance::bound_vertex_array();
+    if bound_vao.is_some() {
+      Instance::bind_vertex_array(None);
+    }
+    bound_vao
+  }
+  pub fn raw_buffer(&self) -> web_sys::WebGlBuffer {
+    self.buffer.get()
   }
   pub fn raw_target(&self) -> u32 {
     self.usage as u32
@@ -95,6 +167,6 @@ impl RawBuffer {
 impl Drop for RawBuffer {
   fn drop(&mut self) {
     let ctx = Instance::ctx();
-    ctx.delete_buffer(Some(&self.buffer));
+    self.buffer.delete(|x| ctx.delete_buffer(Some(x)));
   }
 }
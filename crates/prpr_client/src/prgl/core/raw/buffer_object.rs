@@ -3,12 +3,11 @@ pub struct RawRenderBuffer {
   raw_renderbuffer: web_sys::WebGlRenderbuffer,
 }
 impl RawRenderBuffer {
-  pub fn new() -> Self {
+  // context lost 中は None
+  pub fn new() -> Option<Self> {
     let ctx = Instance::ctx();
-    let raw_renderbuffer = ctx
-      .create_renderbuffer()
-      .expect("failed to create render buffer");
-    Self { raw_renderbuffer }
+    let raw_renderbuffer = ctx.create_renderbuffer()?;
+    Some(Self { raw_renderbuffer })
   }
   pub fn raw_renderbuffer(&self) -> &web_sys::WebGlRenderbuffer {
     &self.raw_renderbuffer
@@ -22,24 +21,57 @@ impl Drop for RawRenderBuffer {
 }
 
 pub struct RawFrameBuffer {
-  raw_framebuffer: web_sys::WebGlFramebuffer,
+  raw_framebuffer: RawHandle<web_sys::WebGlFramebuffer>,
 }
 impl RawFrameBuffer {
   pub fn new() -> Self {
+    Self {
+      raw_framebuffer: RawHandle::new_or_lost(Self::create()),
+    }
+  }
+  fn create() -> Option<web_sys::WebGlFramebuffer> {
     let ctx = Instance::ctx();
-    let raw_framebuffer = ctx
-      .create_framebuffer()
-      .expect("failed to create frame buffer");
-    Self { raw_framebuffer }
+    ctx.create_framebuffer()
   }
-  pub fn raw_framebuffer(&self) -> &web_sys::WebGlFramebuffer {
-    &self.raw_framebuffer
+  // 作り直したら attachment を付け直すこと
+  pub fn restore(&self) -> bool {
+    self.raw_framebuffer.restore(Self::create)
   }
+  pub fn raw_framebuffer(&self) -> web_sys::WebGlFramebuffer {
+    self.raw_framebuffer.get()
+  }
+  // bind されている状態で呼ぶ
+  pub fn check_status(&self) -> Result<(), FramebufferStatus> {
+    let ctx = Instance::ctx();
+    match ctx.check_framebuffer_status(gl::FRAMEBUFFER) {
+      gl::FRAMEBUFFER_COMPLETE => Ok(()),
+      gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => Err(FramebufferStatus::IncompleteAttachment),
+      gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => {
+        Err(FramebufferStatus::IncompleteMissingAttachment)
+      }
+      gl::FRAMEBUFFER_INCOMPLETE_DIMENSIONS => Err(FramebufferStatus::IncompleteDimensions),
+      gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => Err(FramebufferStatus::IncompleteMultisample),
+      gl::FRAMEBUFFER_UNSUPPORTED => Err(FramebufferStatus::Unsupported),
+      status => Err(FramebufferStatus::Unknown(status)),
+    }
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FramebufferStatus {
+  IncompleteAttachment,
+  IncompleteMissingAttachment,
+  IncompleteDimensions,
+  IncompleteMultisample,
+  Unsupported,
+  Unknown(u32),
 }
 impl Drop for RawFrameBuffer {
   fn drop(&mut self) {
     let ctx = Instance::ctx();
-    ctx.delete_framebuffer(Some(&self.raw_framebuffer));
+    self
+      .raw_framebuffer
+      .delete(|x| ctx.delete_framebuffer(Some(x)));
   }
 }
 
@@ -0,0 +1,55 @@
+use super::*;
+use wasm_bindgen::JsCast;
+
+// 作れなかったハンドルの generation. どの generation とも一致しないので常に無効
+const LOST_GENERATION: u64 = u64::MAX;
+
+// web_sys の GL オブジェクトを作られたときの generation と一緒に持つ
+// context が restore されると generation が変わり, 古いハンドルは無効になる
+pub struct RawHandle<T: Clone + JsCast> {
+  inner: SRwLock<(T, u64)>,
+}
+impl<T: Clone + JsCast> RawHandle<T> {
+  pub fn new(handle: T) -> Self {
+    Self {
+      inner: SRwLock::new((handle, Instance::generation())),
+    }
+  }
+  // context lost 中は作れない(null が返る)ので, 無効なハンドルにしておき restore で作る
+  pub fn lost() -> Self {
+    Self {
+      inner: SRwLock::new((
+        wasm_bindgen::JsValue::NULL.unchecked_into(),
+        LOST_GENERATION,
+      )),
+    }
+  }
+  pub fn new_or_lost(handle: Option<T>) -> Self {
+    handle.map(Self::new).unwrap_or_else(Self::lost)
+  }
+  pub fn is_lost(&self) -> bool {
+    self.inner.read().1 != Instance::generation()
+  }
+  pub fn get(&self) -> T {
+    self.inner.read().0.clone()
+  }
+  // 無効になっていれば作り直す. 作り直したら true(context lost 中で作れなければ false)
+  pub fn restore(&self, create: impl FnOnce() -> Option<T>) -> bool {
+    if !self.is_lost() {
+      return false;
+    }
+    match create() {
+      Some(handle) => {
+        *self.inner.write() = (handle, Instance::generation());
+        true
+      }
+      None => false,
+    }
+  }
+  // 別の context のオブジェクトを消すとエラーになるので, 無効なものは何もしない
+  pub fn delete(&self, delete: impl FnOnce(&T)) {
+    if !self.is_lost() {
+      delete(&self.inner.read().0);
+    }
+  }
+}
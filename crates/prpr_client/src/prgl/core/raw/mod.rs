@@ -1,5 +1,7 @@
 use super::*;
 // - web_sys::*を持ち公開する＋通常は外側のユーザーは使用しない(公開しない)
+pub mod handle;
+pub use self::handle::*;
 pub mod buffer;
 pub use self::buffer::*;
 pub mod texture;
@@ -17,9 +17,7 @@ impl RawShader {
       ShaderType::FragmentShader => gl::FRAGMENT_SHADER,
     };
     let ctx = Instance::ctx();
-    let shader = ctx
-      .create_shader(create_flag)
-      .expect("failed to create shader");
+    let shader = ctx.create_shader(create_flag)?;
     ctx.shader_source(&shader, code);
     ctx.compile_shader(&shader);
     if ctx
@@ -57,7 +55,7 @@ impl Drop for RawShader {
 use std::sync::atomic::{AtomicUsize, Ordering};
 static ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
 pub struct RawShaderProgram {
-  program: web_sys::WebGlProgram,
+  program: RawHandle<web_sys::WebGlProgram>,
   program_id: u64,
 }
 pub struct RawShaderProgramContents {
@@ -66,6 +64,10 @@ pub struct RawShaderProgramContents {
 }
 impl RawShaderProgram {
   pub fn new(template: &ShaderTemplate) -> Option<Self> {
+    // context lost 中は compile できないので, 無効なまま返して restore 後に link し直させる
+    if Instance::is_context_lost() {
+      return Some(Self::lost());
+    }
     let vs_code = template.vs_code();
     let fs_code = template.fs_code();
     let vertex_shader = RawShader::new(vs_code.as_str(), ShaderType::VertexShader);
@@ -77,9 +79,10 @@ impl RawShaderProgram {
   }
   pub fn new_from_raw_shaders(shaders: &RawShaderProgramContents) -> Option<Self> {
     let ctx = Instance::ctx();
-    let program = ctx
-      .create_program()
-      .expect("failed to create shader program");
+    let program = match ctx.create_program() {
+      Some(program) => program,
+      None => return Some(Self::lost()),
+    };
     if let Some(shader) = &shaders.vertex_shader {
       if shader.shader_type != ShaderType::VertexShader {
         log::error("Not Vertex Shader");
@@ -117,16 +120,26 @@ impl RawShaderProgram {
       return None;
     }
     return Some(Self {
-      program,
+      program: RawHandle::new(program),
       program_id: ID_COUNTER.fetch_add(1, Ordering::SeqCst) as u64,
     });
   }
+  fn lost() -> Self {
+    Self {
+      program: RawHandle::lost(),
+      program_id: ID_COUNTER.fetch_add(1, Ordering::SeqCst) as u64,
+    }
+  }
   pub fn use_program(&self) {
     let ctx = Instance::ctx();
-    ctx.use_program(Some(&self.program));
+    ctx.use_program(Some(&self.program.get()));
+  }
+  pub fn raw_program(&self) -> web_sys::WebGlProgram {
+    self.program.get()
   }
-  pub fn raw_program(&self) -> &web_sys::WebGlProgram {
-    &self.program
+  // 作り直しには shader のコードが要るので Shader 側で行う
+  pub fn is_lost(&self) -> bool {
+    self.program.is_lost()
   }
   pub fn program_id(&self) -> u64 {
     self.program_id
@@ -135,6 +148,6 @@ impl RawShaderProgram {
 impl Drop for RawShaderProgram {
   fn drop(&mut self) {
     let ctx = Instance::ctx();
-    ctx.delete_program(Some(&self.program));
+    self.program.delete(|x| ctx.delete_program(Some(x)));
   }
 }
@@ -7,6 +7,7 @@ pub enum RawPixelFormatSimple {
   Rg = gl::RG as isize,
   Rgb = gl::RGB as isize,
   Rgba = gl::RGBA as isize,
+  RInteger = gl::RED_INTEGER as isize,
   Depth = gl::DEPTH_COMPONENT as isize,
   DepthStencil = gl::DEPTH_STENCIL as isize,
 }
@@ -17,6 +18,7 @@ impl RawPixelFormatSimple {
       Self::Rg => 2,
       Self::Rgb => 3,
       Self::Rgba => 4,
+      Self::RInteger => 1,
       Self::Depth => 1,
       Self::DepthStencil => 2,
     }
@@ -31,7 +33,7 @@ pub enum PixelType {
   f32 = gl::FLOAT as isize,
 }
 // = internalFormat
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum RawPixelFormat {
   // color renderble & texture filterable
   R8 = gl::R8 as isize,
@@ -59,12 +61,227 @@ pub enum RawPixelFormat {
   R32G32F = gl::RG32F as isize,
   R32G32B32F = gl::RGB32F as isize,
   R32G32B32A32F = gl::RGBA32F as isize,
+  // color renderable (integer)
+  R32UI = gl::R32UI as isize,
   // depth
   Depth24 = gl::DEPTH_COMPONENT24 as isize,
   Depth32F = gl::DEPTH_COMPONENT32F as isize,
   Depth24Stencil8 = gl::DEPTH24_STENCIL8 as isize,
+  // compressed (拡張が必要). 描画先にはできない
+  Bc1Rgb = 0x83F0,
+  Bc1Rgba = 0x83F1,
+  Bc2 = 0x83F2,
+  Bc3 = 0x83F3,
+  Bc1RgbaSrgb = 0x8C4D,
+  Bc2Srgb = 0x8C4E,
+  Bc3Srgb = 0x8C4F,
+  Bc4 = 0x8DBB,
+  Bc5 = 0x8DBD,
+  Bc6hUfloat = 0x8E8F,
+  Bc7 = 0x8E8C,
+  Bc7Srgb = 0x8E8D,
+  EacR11 = 0x9270,
+  EacRg11 = 0x9272,
+  Etc2Rgb8 = 0x9274,
+  Etc2Rgb8Srgb = 0x9275,
+  Etc2Rgb8A1 = 0x9276,
+  Etc2Rgba8 = 0x9278,
+  Etc2Rgba8Srgb = 0x9279,
+  Astc4x4 = 0x93B0,
+  Astc6x6 = 0x93B4,
+  Astc8x8 = 0x93B7,
+  Astc4x4Srgb = 0x93D0,
+  Astc6x6Srgb = 0x93D4,
+  Astc8x8Srgb = 0x93D7,
 }
 impl RawPixelFormat {
+  // 上のグループ分けに従う
+  pub fn is_color_renderable(&self) -> bool {
+    matches!(
+      self,
+      Self::R8
+        | Self::R8G8
+        | Self::R8G8B8
+        | Self::R8G8B8A8
+        | Self::R8G8B8A8Srgb
+        | Self::R4G4B4A4
+        | Self::R5G6B5
+        | Self::R5G5B5A1
+        | Self::R10G10B10A2
+        | Self::R32UI
+    )
+  }
+  // EXT_color_buffer_float があれば描画可能
+  pub fn is_float_color_renderable(&self) -> bool {
+    matches!(
+      self,
+      Self::R16F
+        | Self::R16G16F
+        | Self::R16G16B16A16F
+        | Self::R11G11B10F
+        | Self::R32F
+        | Self::R32G32F
+        | Self::R32G32B32A32F
+    )
+  }
+  // 描画先にするにはこのどれかが要る
+  pub fn render_extensions(&self) -> &'static [Extension] {
+    match self {
+      Self::R16F | Self::R16G16F | Self::R16G16B16A16F => {
+        &[Extension::ColorBufferFloat, Extension::ColorBufferHalfFloat]
+      }
+      Self::R11G11B10F | Self::R32F | Self::R32G32F | Self::R32G32B32A32F => {
+        &[Extension::ColorBufferFloat]
+      }
+      _ => &[],
+    }
+  }
+  // 拡張がなく描画先にできないときの代わり. 前から順に試す
+  // 32bit float は half float の拡張だけあれば 16bit に, それもなければ 8bit(負の値や 1 を超える値が丸められる)
+  pub fn render_fallbacks(&self) -> &'static [Self] {
+    match self {
+      Self::R16F => &[Self::R8],
+      Self::R16G16F => &[Self::R8G8],
+      Self::R16G16B16A16F => &[Self::R8G8B8A8],
+      Self::R11G11B10F => &[Self::R16G16B16A16F, Self::R8G8B8A8],
+      Self::R32F => &[Self::R16F, Self::R8],
+      Self::R32G32F => &[Self::R16G16F, Self::R8G8],
+      Self::R32G32B32A32F => &[Self::R16G16B16A16F, Self::R8G8B8A8],
+      _ => &[],
+    }
+  }
+  pub fn is_renderable(&self) -> bool {
+    self.render_extensions().is_empty() || Instance::enable_any_extension(self.render_extensions())
+  }
+  // LINEAR で引くのに要る拡張
+  pub fn filter_extension(&self) -> Option<Extension> {
+    match self {
+      Self::R32F | Self::R32G32F | Self::R32G32B32F | Self::R32G32B32A32F => {
+        Some(Extension::TextureFloatLinear)
+      }
+      _ => None,
+    }
+  }
+  // depth は compare mode のときだけ LINEAR で引ける(Sampler::is_compare)
+  pub fn is_filterable(&self) -> bool {
+    match self.filter_extension() {
+      Some(extension) => Instance::enable_extension(extension),
+      None => !matches!(self, Self::R32UI) && !self.is_depth(),
+    }
+  }
+  // (block の幅, block の高さ, block の byte 数)
+  pub fn compressed_block(&self) -> Option<(usize, usize, usize)> {
+    match self {
+      Self::Bc1Rgb | Self::Bc1Rgba | Self::Bc1RgbaSrgb | Self::Bc4 => Some((4, 4, 8)),
+      Self::Bc2 | Self::Bc2Srgb | Self::Bc3 | Self::Bc3Srgb | Self::Bc5 => Some((4, 4, 16)),
+      Self::Bc6hUfloat | Self::Bc7 | Self::Bc7Srgb => Some((4, 4, 16)),
+      Self::EacR11 | Self::Etc2Rgb8 | Self::Etc2Rgb8Srgb | Self::Etc2Rgb8A1 => Some((4, 4, 8)),
+      Self::EacRg11 | Self::Etc2Rgba8 | Self::Etc2Rgba8Srgb => Some((4, 4, 16)),
+      Self::Astc4x4 | Self::Astc4x4Srgb => Some((4, 4, 16)),
+      Self::Astc6x6 | Self::Astc6x6Srgb => Some((6, 6, 16)),
+      Self::Astc8x8 | Self::Astc8x8Srgb => Some((8, 8, 16)),
+      _ => None,
+    }
+  }
+  pub fn is_compressed(&self) -> bool {
+    self.compressed_block().is_some()
+  }
+  // 1 mip level 分の byte 数
+  pub fn data_size(&self, width: usize, height: usize) -> usize {
+    match self.compressed_block() {
+      Some((block_width, block_height, block_size)) => {
+        width.div_ceil(block_width) * height.div_ceil(block_height) * block_size
+      }
+      None => width * height * self.bpp(),
+    }
+  }
+  pub fn compression_extension(&self) -> Option<Extension> {
+    match self {
+      Self::Bc1Rgb | Self::Bc1Rgba | Self::Bc2 | Self::Bc3 => {
+        Some(Extension::CompressedTextureS3tc)
+      }
+      Self::Bc1RgbaSrgb | Self::Bc2Srgb | Self::Bc3Srgb => {
+        Some(Extension::CompressedTextureS3tcSrgb)
+      }
+      Self::Bc4 | Self::Bc5 => Some(Extension::CompressedTextureRgtc),
+      Self::Bc6hUfloat | Self::Bc7 | Self::Bc7Srgb => Some(Extension::CompressedTextureBptc),
+      Self::EacR11
+      | Self::EacRg11
+      | Self::Etc2Rgb8
+      | Self::Etc2Rgb8Srgb
+      | Self::Etc2Rgb8A1
+      | Self::Etc2Rgba8
+      | Self::Etc2Rgba8Srgb => Some(Extension::CompressedTextureEtc),
+      Self::Astc4x4
+      | Self::Astc6x6
+      | Self::Astc8x8
+      | Self::Astc4x4Srgb
+      | Self::Astc6x6Srgb
+      | Self::Astc8x8Srgb => Some(Extension::CompressedTextureAstc),
+      _ => None,
+    }
+  }
+  // テクスチャとして作れるか. 圧縮形式なら拡張を有効にする
+  pub fn is_supported(&self) -> bool {
+    match self.compression_extension() {
+      Some(extension) => Instance::enable_extension(extension),
+      None => true,
+    }
+  }
+  // 端末ごとに用意した候補から使えるものを選ぶ (例: [Astc4x4, Bc7, Etc2Rgba8, R8G8B8A8])
+  pub fn first_supported(candidates: &[Self]) -> Option<Self> {
+    candidates.iter().copied().find(|x| x.is_supported())
+  }
+  // KTX2 の vkFormat から. 対応していないものは None
+  pub fn from_vk_format(vk_format: u32) -> Option<Self> {
+    Some(match vk_format {
+      9 => Self::R8,
+      16 => Self::R8G8,
+      23 => Self::R8G8B8,
+      29 => Self::R8G8B8Srgb,
+      37 => Self::R8G8B8A8,
+      43 => Self::R8G8B8A8Srgb,
+      76 => Self::R16F,
+      83 => Self::R16G16F,
+      97 => Self::R16G16B16A16F,
+      98 => Self::R32UI,
+      100 => Self::R32F,
+      103 => Self::R32G32F,
+      109 => Self::R32G32B32A32F,
+      131 => Self::Bc1Rgb,
+      133 => Self::Bc1Rgba,
+      134 => Self::Bc1RgbaSrgb,
+      135 => Self::Bc2,
+      136 => Self::Bc2Srgb,
+      137 => Self::Bc3,
+      138 => Self::Bc3Srgb,
+      139 => Self::Bc4,
+      141 => Self::Bc5,
+      143 => Self::Bc6hUfloat,
+      145 => Self::Bc7,
+      146 => Self::Bc7Srgb,
+      147 => Self::Etc2Rgb8,
+      148 => Self::Etc2Rgb8Srgb,
+      149 => Self::Etc2Rgb8A1,
+      151 => Self::Etc2Rgba8,
+      152 => Self::Etc2Rgba8Srgb,
+      153 => Self::EacR11,
+      155 => Self::EacRg11,
+      157 => Self::Astc4x4,
+      158 => Self::Astc4x4Srgb,
+      165 => Self::Astc6x6,
+      166 => Self::Astc6x6Srgb,
+      171 => Self::Astc8x8,
+      172 => Self::Astc8x8Srgb,
+      _ => return None,
+    })
+  }
+  pub fn is_depth(&self) -> bool {
+    matches!(self, Self::Depth24 | Self::Depth32F | Self::Depth24Stencil8)
+  }
+  pub fn has_stencil(&self) -> bool {
+    *self == Self::Depth24Stencil8
+  }
   // bit per pixel
   pub fn bpp(&self) -> usize {
     match self {
@@ -95,9 +312,12 @@ impl RawPixelFormat {
       Self::R4G4B4A4 => 2,
       Self::R5G5B5A1 => 2,
       Self::R10G10B10A2 => 4,
+      Self::R32UI => 4,
       Self::Depth24 => 3,
       Self::Depth32F => 4,
       Self::Depth24Stencil8 => 4,
+      // compressed は data_size を使う
+      _ => 0,
     }
   }
   pub fn to_simple_format(&self) -> RawPixelFormatSimple {
@@ -129,9 +349,17 @@ impl RawPixelFormat {
       Self::R4G4B4A4 => RawPixelFormatSimple::Rgba,
       Self::R5G5B5A1 => RawPixelFormatSimple::Rgba, // non-u
       Self::R10G10B10A2 => RawPixelFormatSimple::Rgba, // non-u
+      Self::R32UI => RawPixelFormatSimple::RInteger,
       Self::Depth24 => RawPixelFormatSimple::Depth,
       Self::Depth32F => RawPixelFormatSimple::Depth,
       Self::Depth24Stencil8 => RawPixelFormatSimple::DepthStencil,
+      // compressed
+      Self::Bc4 | Self::EacR11 => RawPixelFormatSimple::R,
+      Self::Bc5 | Self::EacRg11 => RawPixelFormatSimple::Rg,
+      Self::Bc1Rgb | Self::Bc6hUfloat | Self::Etc2Rgb8 | Self::Etc2Rgb8Srgb => {
+        RawPixelFormatSimple::Rgb
+      }
+      _ => RawPixelFormatSimple::Rgba,
     }
   }
   pub fn to_writable_uniform_type(&self) -> PixelType {
@@ -161,13 +389,16 @@ impl RawPixelFormat {
       // f32(-)
       Self::R11G11B10F => PixelType::f32, // may UNSIGNED_INT_10F_11F_11F_REV
       // u8 ?
-      Self::R8Snorm => PixelType::u8,         // not specified
-      Self::R8G8Snorm => PixelType::u8,       // not specified
-      Self::R8G8B8Snorm => PixelType::u8,     // not specified
-      Self::R8G8B8A8Snorm => PixelType::u8,   // not specified
+      Self::R8Snorm => PixelType::u8,       // not specified
+      Self::R8G8Snorm => PixelType::u8,     // not specified
+      Self::R8G8B8Snorm => PixelType::u8,   // not specified
+      Self::R8G8B8A8Snorm => PixelType::u8, // not specified
+      Self::R32UI => PixelType::u32,
       Self::Depth24 => PixelType::u32,        // not specified
       Self::Depth32F => PixelType::f32,       // not specified
       Self::Depth24Stencil8 => PixelType::u8, // not specified
+      // compressed は type を使わない
+      _ => PixelType::u8,
     }
   }
 }
@@ -189,6 +420,42 @@ pub struct RawTextureDescriptor {
   pub target: u32,
 }
 impl RawTextureDescriptor {
+  pub fn new_cube(size: usize, format: RawPixelFormat, mipmap: bool) -> Self {
+    Self {
+      format,
+      width: size,
+      height: size,
+      depth: 6,
+      mipmap,
+      target: gl::TEXTURE_CUBE_MAP,
+    }
+  }
+  pub fn new_array(
+    width: usize,
+    height: usize,
+    layers: usize,
+    format: RawPixelFormat,
+    mipmap: bool,
+  ) -> Self {
+    Self {
+      format,
+      width,
+      height,
+      depth: layers,
+      mipmap,
+      target: gl::TEXTURE_2D_ARRAY,
+    }
+  }
+  pub fn mip_levels(&self) -> usize {
+    if !self.mipmap {
+      return 1;
+    }
+    let mut size = std::cmp::max(self.width, self.height);
+    if self.target == gl::TEXTURE_3D {
+      size = std::cmp::max(size, self.depth);
+    }
+    (usize::BITS - size.max(1).leading_zeros()) as usize
+  }
   pub fn from_2d_descriptor(desc: &RawTexture2dDescriptor) -> Self {
     Self {
       format: desc.format,
@@ -215,18 +482,185 @@ pub enum TextureWriteType<'a> {
   HtmlVideoElement(&'a web_sys::HtmlVideoElement),
 }
 
+// context lost 後に作り直すために持っておく中身
+enum RawTextureContent {
+  Uninitialized,
+  Fill(u8),
+  Bytes(Vec<u8>),
+  // mip level ごとの中身. levels[0] が一番大きい. 圧縮形式もこれ
+  Levels(Vec<Vec<u8>>),
+  // tex_storage で確保したもの(中身は描画で作られる)
+  Storage,
+  // DOM から作ったもの. 参照を持っておいて restore 時に書き直す
+  Source(TextureSource),
+}
+#[derive(Clone)]
+enum TextureSource {
+  ImageBitmap(web_sys::ImageBitmap),
+  ImageData(web_sys::ImageData),
+  HtmlImageElement(web_sys::HtmlImageElement),
+  HtmlCanvasElement(web_sys::HtmlCanvasElement),
+  HtmlVideoElement(web_sys::HtmlVideoElement),
+}
+
 use std::sync::atomic::{AtomicUsize, Ordering};
 static ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
 pub struct RawTexture {
-  raw_texture: web_sys::WebGlTexture,
+  raw_texture: RawHandle<web_sys::WebGlTexture>,
   desc: RawTextureDescriptor,
+  content: RawTextureContent,
+  sampler: SRwLock<Option<Sampler>>,
+  // 描画先にできず浮動小数点でない format に落とした
+  is_format_downgraded: bool,
   texture_id: u64,
 }
 impl RawTexture {
   // pub fn new_cubemap() { target = TEXTURE_CUBE_MAP_??; }
-  pub fn new<'a>(desc: &RawTexture2dDescriptor, write_type: TextureWriteType<'a>) -> Self {
+  // 大きさが上限を超えていれば None
+  pub fn new<'a>(desc: &RawTexture2dDescriptor, write_type: TextureWriteType<'a>) -> Option<Self> {
+    let mut desc = RawTextureDescriptor::from_2d_descriptor(desc);
+    let mut is_format_downgraded = false;
+    if write_type == TextureWriteType::Uninitialized {
+      (desc.format, is_format_downgraded) = Self::resolve_render_format(desc.format);
+    }
+    let content = match write_type {
+      TextureWriteType::Uninitialized => RawTextureContent::Uninitialized,
+      TextureWriteType::Zero => RawTextureContent::Fill(0x00),
+      TextureWriteType::One => RawTextureContent::Fill(0xff),
+      TextureWriteType::u8(pixels) => RawTextureContent::Bytes(pixels.to_vec()),
+      TextureWriteType::f32(f_pixels) => {
+        let u8_size = 4 * f_pixels.len();
+        let ptr = f_pixels.as_ptr() as *const u8;
+        let u8_data: &[u8] = unsafe { ::core::slice::from_raw_parts(ptr, u8_size) };
+        RawTextureContent::Bytes(u8_data.to_vec())
+      }
+      TextureWriteType::ImageBitmap(x) => {
+        RawTextureContent::Source(TextureSource::ImageBitmap(x.clone()))
+      }
+      TextureWriteType::ImageData(x) => {
+        RawTextureContent::Source(TextureSource::ImageData(x.clone()))
+      }
+      TextureWriteType::HtmlImageElement(x) => {
+        RawTextureContent::Source(TextureSource::HtmlImageElement(x.clone()))
+      }
+      TextureWriteType::HtmlCanvasElement(x) => {
+        RawTextureContent::Source(TextureSource::HtmlCanvasElement(x.clone()))
+      }
+      TextureWriteType::HtmlVideoElement(x) => {
+        RawTextureContent::Source(TextureSource::HtmlVideoElement(x.clone()))
+      }
+    };
+    Self::new_impl(desc, content, is_format_downgraded)
+  }
+  // mip level ごとに中身を渡す. levels が 1 つで desc.mipmap なら mipmap を作る(圧縮形式は不可)
+  pub fn new_levels(desc: &RawTexture2dDescriptor, levels: Vec<Vec<u8>>) -> Option<Self> {
+    let mut desc = RawTextureDescriptor::from_2d_descriptor(desc);
+    desc.mipmap = levels.len() > 1 || (desc.mipmap && !desc.format.is_compressed());
+    Self::new_impl(desc, RawTextureContent::Levels(levels), false)
+  }
+  // cubemap / 2d array / 3d 用. 中身は未初期化
+  pub fn new_layered(desc: &RawTextureDescriptor) -> Option<Self> {
+    let mut desc = desc.clone();
+    let is_format_downgraded;
+    (desc.format, is_format_downgraded) = Self::resolve_render_format(desc.format);
+    Self::new_impl(desc, RawTextureContent::Storage, is_format_downgraded)
+  }
+  // 中身のないテクスチャは描画先として使われるので, 拡張がなければ描画できる format に落とす
+  // (使う format, 浮動小数点でない format に落としたか)
+  fn resolve_render_format(format: RawPixelFormat) -> (RawPixelFormat, bool) {
+    if format.is_renderable() {
+      return (format, false);
+    }
+    let fallback = format.render_fallbacks().iter().find(|x| x.is_renderable());
+    match fallback {
+      Some(fallback) if fallback.render_extensions().is_empty() => {
+        log::error(format!(
+          "{:?} is not renderable on this device, fallback to {:?} (values are clamped to [0, 1])",
+          format, fallback
+        ));
+        (*fallback, true)
+      }
+      Some(fallback) => {
+        log::info(format!(
+          "{:?} is not renderable on this device, fallback to {:?}",
+          format, fallback
+        ));
+        (*fallback, false)
+      }
+      None => {
+        log::error(format!("{:?} is not renderable on this device", format));
+        (format, false)
+      }
+    }
+  }
+  // GPU の上限を超えるものは作っても使えないので作らない
+  fn is_within_limits(desc: &RawTextureDescriptor) -> bool {
+    let capabilities = Instance::capabilities();
+    let max_size = capabilities.max_texture_size_of(desc.target);
+    if desc.width > max_size || desc.height > max_size {
+      log::error(format!(
+        "too large texture: {}x{}, max:{}",
+        desc.width, desc.height, max_size
+      ));
+      return false;
+    }
+    let max_depth = match desc.target {
+      gl::TEXTURE_2D_ARRAY => capabilities.max_array_texture_layers,
+      gl::TEXTURE_3D => capabilities.max_3d_texture_size,
+      _ => desc.depth,
+    };
+    if desc.depth > max_depth {
+      log::error(format!(
+        "too many texture layers: {}, max:{}",
+        desc.depth, max_depth
+      ));
+      return false;
+    }
+    true
+  }
+  fn new_impl(
+    desc: RawTextureDescriptor,
+    content: RawTextureContent,
+    is_format_downgraded: bool,
+  ) -> Option<Self> {
+    if !Self::is_within_limits(&desc) {
+      return None;
+    }
+    Some(Self {
+      raw_texture: RawHandle::new_or_lost(Self::create(&desc, &content)),
+      desc,
+      content,
+      sampler: SRwLock::new(None),
+      is_format_downgraded,
+      texture_id: ID_COUNTER.fetch_add(1, Ordering::SeqCst) as u64,
+    })
+  }
+  // context lost 中は None
+  fn create(
+    desc: &RawTextureDescriptor,
+    content: &RawTextureContent,
+  ) -> Option<web_sys::WebGlTexture> {
+    let ctx = Instance::ctx();
+    let raw_texture = ctx.create_texture()?;
+    let target = desc.target;
+    ctx.bind_texture(target, Some(&raw_texture));
+    match content {
+      RawTextureContent::Storage => Self::create_storage(desc),
+      RawTextureContent::Levels(levels) => Self::create_levels(desc, levels),
+      RawTextureContent::Source(source) => Self::create_from_source(desc, source),
+      _ => Self::create_image_2d(desc, content),
+    }
+    // 拡張がなければ LINEAR で引けない(テクスチャが不完全になり黒くなる)
+    if !desc.format.is_filterable() {
+      Sampler::nearest().apply(target);
+    }
+    if SET_BIND_NONE_AFTER_WORK {
+      ctx.bind_texture(target, None);
+    }
+    Some(raw_texture)
+  }
+  fn create_image_2d(desc: &RawTextureDescriptor, content: &RawTextureContent) {
     let ctx = Instance::ctx();
-    let raw_texture = ctx.create_texture().expect("failed to create texture");
     let target = gl::TEXTURE_2D;
     let level = 0;
     let internalformat = desc.format as i32;
@@ -237,54 +671,225 @@ impl RawTexture {
     let type_ = desc.format.to_writable_uniform_type();
     let bpp = desc.format.bpp();
     let u8_array_size = bpp * width * height;
-    let tmpvec = match write_type {
-      TextureWriteType::Zero => vec![0x00; u8_array_size],
-      TextureWriteType::One => vec![0xff; u8_array_size],
+    let tmpvec = match content {
+      RawTextureContent::Fill(value) => vec![*value; u8_array_size],
       _ => Vec::new(),
     };
-    let pixels: Option<&[u8]> = match write_type {
-      TextureWriteType::Uninitialized => None,
-      TextureWriteType::Zero => Some(tmpvec.as_slice()),
-      TextureWriteType::One => Some(tmpvec.as_slice()),
-      TextureWriteType::u8(pixels) => Some(pixels),
-      TextureWriteType::f32(f_pixels) => {
-        let u8_size = 4 * f_pixels.len();
-        let ptr = f_pixels.as_ptr() as *const u8;
-        let u8_data: &[u8] = unsafe { ::core::slice::from_raw_parts(ptr, u8_size) };
-        Some(u8_data)
-      }
+    let pixels: Option<&[u8]> = match content {
+      RawTextureContent::Fill(_) => Some(tmpvec.as_slice()),
+      RawTextureContent::Bytes(pixels) => Some(pixels.as_slice()),
       _ => None,
     };
-    ctx.bind_texture(target, Some(&raw_texture));
-    if pixels.is_some() || write_type == TextureWriteType::Uninitialized {
-      ctx
-        .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+    ctx
+      .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+        target,
+        level,
+        internalformat,
+        width as i32,
+        height as i32,
+        border,
+        format as u32,
+        type_ as u32,
+        pixels,
+      )
+      .ok();
+    if desc.mipmap {
+      ctx.generate_mipmap(target);
+    }
+  }
+  fn create_from_source(desc: &RawTextureDescriptor, source: &TextureSource) {
+    let ctx = Instance::ctx();
+    let target = gl::TEXTURE_2D;
+    let level = 0;
+    let internalformat = desc.format as i32;
+    let format = desc.format.to_simple_format() as u32;
+    let type_ = desc.format.to_writable_uniform_type() as u32;
+    let result = match source {
+      TextureSource::ImageBitmap(x) => ctx.tex_image_2d_with_u32_and_u32_and_image_bitmap(
+        target,
+        level,
+        internalformat,
+        format,
+        type_,
+        x,
+      ),
+      TextureSource::ImageData(x) => ctx.tex_image_2d_with_u32_and_u32_and_image_data(
+        target,
+        level,
+        internalformat,
+        format,
+        type_,
+        x,
+      ),
+      TextureSource::HtmlImageElement(x) => ctx
+        .tex_image_2d_with_u32_and_u32_and_html_image_element(
           target,
           level,
           internalformat,
-          width as i32,
-          height as i32,
-          border,
-          format as u32,
-          type_ as u32,
-          pixels,
-        )
-        .ok();
-    } else {
-      log::error("currently not supported texture type specified.");
+          format,
+          type_,
+          x,
+        ),
+      TextureSource::HtmlCanvasElement(x) => ctx
+        .tex_image_2d_with_u32_and_u32_and_html_canvas_element(
+          target,
+          level,
+          internalformat,
+          format,
+          type_,
+          x,
+        ),
+      TextureSource::HtmlVideoElement(x) => ctx
+        .tex_image_2d_with_u32_and_u32_and_html_video_element(
+          target,
+          level,
+          internalformat,
+          format,
+          type_,
+          x,
+        ),
+    };
+    if let Err(e) = result {
+      log::error(format!("failed to upload texture: {:?}", e));
+      return;
     }
     if desc.mipmap {
       ctx.generate_mipmap(target);
     }
-    if SET_BIND_NONE_AFTER_WORK {
-      ctx.bind_texture(target, None);
+  }
+  fn create_levels(desc: &RawTextureDescriptor, levels: &[Vec<u8>]) {
+    let ctx = Instance::ctx();
+    let target = gl::TEXTURE_2D;
+    let internalformat = desc.format as u32;
+    let format = desc.format.to_simple_format();
+    // KTX2 などの 16bit float はそのまま half float で渡す
+    let type_ = match desc.format {
+      RawPixelFormat::R16F
+      | RawPixelFormat::R16G16F
+      | RawPixelFormat::R16G16B16F
+      | RawPixelFormat::R16G16B16A16F => gl::HALF_FLOAT,
+      RawPixelFormat::R8Snorm
+      | RawPixelFormat::R8G8Snorm
+      | RawPixelFormat::R8G8B8Snorm
+      | RawPixelFormat::R8G8B8A8Snorm => gl::BYTE,
+      _ => desc.format.to_writable_uniform_type() as u32,
+    };
+    // KTX2 の行は詰めて並んでいる(R8 で幅が奇数など)
+    ctx.pixel_storei(gl::UNPACK_ALIGNMENT, 1);
+    for (level, data) in levels.iter().enumerate() {
+      let width = (desc.width >> level).max(1) as i32;
+      let height = (desc.height >> level).max(1) as i32;
+      if desc.format.is_compressed() {
+        ctx.compressed_tex_image_2d_with_u8_array(
+          target,
+          level as i32,
+          internalformat,
+          width,
+          height,
+          0,
+          data,
+        );
+        continue;
+      }
+      let view = match Self::typed_array_view(type_, data) {
+        Some(view) => view,
+        None => {
+          log::error(format!(
+            "invalid texture level size: {} bytes for {:?}",
+            data.len(),
+            desc.format
+          ));
+          break;
+        }
+      };
+      let result = ctx
+        .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_array_buffer_view(
+          target,
+          level as i32,
+          internalformat as i32,
+          width,
+          height,
+          0,
+          format as u32,
+          type_,
+          Some(&view),
+        );
+      if let Err(e) = result {
+        log::error(format!("failed to upload texture: {:?}", e));
+      }
     }
-    Self {
-      raw_texture,
-      texture_id: ID_COUNTER.fetch_add(1, Ordering::SeqCst) as u64,
-      desc: RawTextureDescriptor::from_2d_descriptor(desc),
+    ctx.pixel_storei(gl::UNPACK_ALIGNMENT, 4);
+    if levels.len() > 1 {
+      // 途中までしかない mip chain でも完全なテクスチャになるように
+      ctx.tex_parameteri(target, gl::TEXTURE_MAX_LEVEL, levels.len() as i32 - 1);
+    } else if desc.mipmap {
+      ctx.generate_mipmap(target);
     }
   }
+  // type と一致した TypedArray でないと WebGL2 は受け付けない
+  fn typed_array_view(type_: u32, data: &[u8]) -> Option<js_sys::Object> {
+    let element_size = match type_ {
+      gl::HALF_FLOAT => 2,
+      gl::FLOAT | gl::UNSIGNED_INT => 4,
+      _ => 1,
+    };
+    if !data.chunks_exact(element_size).remainder().is_empty() {
+      return None;
+    }
+    // 新しく確保した ArrayBuffer に写すので先頭は揃っている
+    let bytes = js_sys::Uint8Array::from(data);
+    let buffer = bytes.buffer();
+    let length = (data.len() / element_size) as u32;
+    Some(match type_ {
+      gl::HALF_FLOAT => {
+        js_sys::Uint16Array::new_with_byte_offset_and_length(&buffer, 0, length).into()
+      }
+      gl::FLOAT => js_sys::Float32Array::new_with_byte_offset_and_length(&buffer, 0, length).into(),
+      gl::UNSIGNED_INT => {
+        js_sys::Uint32Array::new_with_byte_offset_and_length(&buffer, 0, length).into()
+      }
+      gl::BYTE => js_sys::Int8Array::new_with_byte_offset_and_length(&buffer, 0, length).into(),
+      _ => bytes.into(),
+    })
+  }
+  fn create_storage(desc: &RawTextureDescriptor) {
+    let ctx = Instance::ctx();
+    let target = desc.target;
+    let levels = desc.mip_levels() as i32;
+    let internalformat = desc.format as u32;
+    let width = desc.width as i32;
+    let height = desc.height as i32;
+    match target {
+      gl::TEXTURE_2D | gl::TEXTURE_CUBE_MAP => {
+        ctx.tex_storage_2d(target, levels, internalformat, width, height);
+      }
+      gl::TEXTURE_2D_ARRAY | gl::TEXTURE_3D => {
+        let depth = desc.depth as i32;
+        ctx.tex_storage_3d(target, levels, internalformat, width, height, depth);
+      }
+      _ => log::error(format!("not supported texture target {}", target)),
+    }
+  }
+  // context lost で無効になっていれば, 持っている中身と sampler から作り直す
+  fn handle(&self) -> web_sys::WebGlTexture {
+    self.raw_texture.restore(|| {
+      let raw_texture = Self::create(&self.desc, &self.content)?;
+      if let Some(sampler) = &*self.sampler.read() {
+        let ctx = Instance::ctx();
+        ctx.bind_texture(self.target(), Some(&raw_texture));
+        sampler.apply(self.target());
+      }
+      Some(raw_texture)
+    });
+    self.raw_texture.get()
+  }
+  pub fn is_lost(&self) -> bool {
+    self.raw_texture.is_lost()
+  }
+  // 描画先として頼んだ浮動小数点の format が使えず 8bit になった
+  pub fn is_format_downgraded(&self) -> bool {
+    self.is_format_downgraded
+  }
   pub fn write(&self) {
     log::error("not implemented(RawTexture::write)");
     // TODO:
@@ -307,6 +912,9 @@ impl RawTexture {
   pub fn format(&self) -> RawPixelFormat {
     self.desc.format
   }
+  pub fn mip_levels(&self) -> usize {
+    self.desc.mip_levels()
+  }
   pub fn desc(&self) -> &RawTextureDescriptor {
     &self.desc
   }
@@ -316,14 +924,30 @@ impl RawTexture {
   pub fn bind(&self) {
     let ctx = Instance::ctx();
     let target = self.target();
-    ctx.bind_texture(target, Some(&self.raw_texture));
+    ctx.bind_texture(target, Some(&self.handle()));
+  }
+  // bind したまま返る. LINEAR で引けない format なら NEAREST にする
+  pub fn apply_sampler(&self, sampler: &Sampler) {
+    let is_compare_depth = self.desc.format.is_depth() && sampler.is_compare();
+    let sampler = if self.desc.format.is_filterable() || is_compare_depth {
+      sampler.clone()
+    } else {
+      log::info(format!(
+        "{:?} is not filterable on this device, use nearest",
+        self.desc.format
+      ));
+      sampler.to_nearest()
+    };
+    self.bind();
+    sampler.apply(self.target());
+    *self.sampler.write() = Some(sampler);
   }
 
   pub fn channels(&self) -> usize {
     self.desc.format.to_simple_format().channels()
   }
-  pub fn raw_texture(&self) -> &web_sys::WebGlTexture {
-    &self.raw_texture
+  pub fn raw_texture(&self) -> web_sys::WebGlTexture {
+    self.handle()
   }
   pub fn texture_id(&self) -> u64 {
     self.texture_id
@@ -372,6 +996,6 @@ impl RawTexture {
 impl Drop for RawTexture {
   fn drop(&mut self) {
     let ctx = Instance::ctx();
-    ctx.delete_texture(Some(&self.raw_texture));
+    self.raw_texture.delete(|x| ctx.delete_texture(Some(x)));
   }
 }
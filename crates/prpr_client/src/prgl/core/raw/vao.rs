@@ -1,7 +1,7 @@
 use super::*;
 
 pub struct RawVao {
-  vao: web_sys::WebGlVertexArrayObject,
+  vao: RawHandle<web_sys::WebGlVertexArrayObject>,
   vao_id: u64,
 }
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -10,19 +10,28 @@ static ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
 impl RawVao {
   pub fn new(
     program: &web_sys::WebGlProgram,
-    vs_in_template_buffer: Option<(&VsInTemplate, &RawBuffer)>,
+    // 同じ名前の属性があれば後のものが使われる
+    vs_in_template_buffers: &[(&VsInTemplate, &RawBuffer)],
     i_buffer: Option<&RawBuffer>,
   ) -> Self {
     let ctx = Instance::ctx();
-    let vao = ctx.create_vertex_array().expect("failed to create vao");
-    ctx.bind_vertex_array(Some(&vao));
-    if let Some(vs_in_template_buffer) = vs_in_template_buffer {
-      let vs_in = vs_in_template_buffer.0;
-      let v_buffer = vs_in_template_buffer.1;
+    let vao_id = ID_COUNTER.fetch_add(1, Ordering::SeqCst) as u64;
+    // context lost 中は作れない. 無効なまま返し, 次の bind で作り直させる
+    let vao = match ctx.create_vertex_array() {
+      Some(vao) => vao,
+      None => {
+        return Self {
+          vao: RawHandle::lost(),
+          vao_id,
+        }
+      }
+    };
+    Instance::bind_vertex_array(Some(&vao));
+    for (vs_in, v_buffer) in vs_in_template_buffers {
       if v_buffer.raw_target() != gl::ARRAY_BUFFER {
         log::error("Not Vertex Buffer");
       }
-      ctx.bind_buffer(gl::ARRAY_BUFFER, Some(v_buffer.raw_buffer()));
+      ctx.bind_buffer(gl::ARRAY_BUFFER, Some(&v_buffer.raw_buffer()));
       assert_eq!(vs_in.offsets.len(), vs_in.keys.len());
       assert_eq!(vs_in.values.len(), vs_in.keys.len());
       for i in 0..vs_in.offsets.len() {
@@ -47,23 +56,27 @@ impl RawVao {
       if i_buffer.raw_target() != gl::ELEMENT_ARRAY_BUFFER {
         log::error("Not Index Buffer");
       }
-      ctx.bind_buffer(gl::ELEMENT_ARRAY_BUFFER, Some(i_buffer.raw_buffer()));
+      ctx.bind_buffer(gl::ELEMENT_ARRAY_BUFFER, Some(&i_buffer.raw_buffer()));
     }
     if SET_BIND_NONE_AFTER_WORK {
-      ctx.bind_vertex_array(None);
+      Instance::bind_vertex_array(None);
       ctx.bind_buffer(gl::ARRAY_BUFFER, None);
       if i_buffer.is_some() {
         ctx.bind_buffer(gl::ELEMENT_ARRAY_BUFFER, None);
       }
     }
     Self {
-      vao,
-      vao_id: ID_COUNTER.fetch_add(1, Ordering::SeqCst) as u64,
+      vao: RawHandle::new(vao),
+      vao_id,
     }
   }
 
-  pub fn raw_vao(&self) -> &web_sys::WebGlVertexArrayObject {
-    &self.vao
+  pub fn raw_vao(&self) -> web_sys::WebGlVertexArrayObject {
+    self.vao.get()
+  }
+  // 参照していたバッファも作り直されるので, 無効になったら作り直すのではなく捨てる
+  pub fn is_lost(&self) -> bool {
+    self.vao.is_lost()
   }
   pub fn vao_id(&self) -> u64 {
     self.vao_id
@@ -73,6 +86,6 @@ impl RawVao {
 impl Drop for RawVao {
   fn drop(&mut self) {
     let ctx = Instance::ctx();
-    ctx.delete_vertex_array(Some(&self.vao));
+    self.vao.delete(|x| ctx.delete_vertex_array(Some(x)));
   }
 }
@@ -0,0 +1,307 @@
+use super::*;
+
+// readPixels で読める型. 読み出しは常に 4ch
+// u8 / f32 は正規化 / 浮動小数点テクスチャ, u32 / i32 は整数テクスチャ用
+pub trait ReadPixelElement: Copy + Default + 'static {
+  const FORMAT: u32;
+  const TYPE: u32;
+  fn read_pixels_sync(rect: &Rect<i32>) -> Option<Vec<Self>>;
+  fn from_bytes(bytes: &[u8]) -> Vec<Self>;
+}
+macro_rules! impl_read_pixel_element {
+  ($t:ty, $format:expr, $type:expr, $array:ty) => {
+    impl ReadPixelElement for $t {
+      const FORMAT: u32 = $format;
+      const TYPE: u32 = $type;
+      fn read_pixels_sync(rect: &Rect<i32>) -> Option<Vec<Self>> {
+        if rect.width <= 0 || rect.height <= 0 {
+          return None;
+        }
+        let ctx = Instance::ctx();
+        // 型の一致した TypedArray でないと読めない
+        let array = <$array>::new_with_length((rect.width * rect.height * 4) as u32);
+        ctx
+          .read_pixels_with_opt_array_buffer_view(
+            rect.x,
+            rect.y,
+            rect.width,
+            rect.height,
+            <Self as ReadPixelElement>::FORMAT,
+            <Self as ReadPixelElement>::TYPE,
+            Some(&array),
+          )
+          .ok()?;
+        Some(array.to_vec())
+      }
+      fn from_bytes(bytes: &[u8]) -> Vec<Self> {
+        const SIZE: usize = std::mem::size_of::<$t>();
+        bytes
+          .chunks_exact(SIZE)
+          .map(|c| <$t>::from_ne_bytes(c.try_into().unwrap()))
+          .collect()
+      }
+    }
+  };
+}
+impl_read_pixel_element!(u8, gl::RGBA, gl::UNSIGNED_BYTE, js_sys::Uint8Array);
+impl_read_pixel_element!(f32, gl::RGBA, gl::FLOAT, js_sys::Float32Array);
+impl_read_pixel_element!(u32, gl::RGBA_INTEGER, gl::UNSIGNED_INT, js_sys::Uint32Array);
+impl_read_pixel_element!(i32, gl::RGBA_INTEGER, gl::INT, js_sys::Int32Array);
+
+// RGBA で並び, 行は下から上(GL の座標系)
+pub struct Pixels<T: ReadPixelElement> {
+  pub width: usize,
+  pub height: usize,
+  pub data: Vec<T>,
+}
+impl<T: ReadPixelElement> Pixels<T> {
+  pub fn get(&self, x: usize, y: usize) -> [T; 4] {
+    let i = (y * self.width + x) * 4;
+    [
+      self.data[i],
+      self.data[i + 1],
+      self.data[i + 2],
+      self.data[i + 3],
+    ]
+  }
+}
+
+// fenceSync で GPU の完了を待つ. 毎フレーム poll する
+pub struct PendingReadback<T: ReadPixelElement> {
+  buffer: RawBuffer,
+  sync: web_sys::WebGlSync,
+  generation: u64,
+  width: usize,
+  height: usize,
+  phantom: std::marker::PhantomData<T>,
+}
+impl<T: ReadPixelElement> PendingReadback<T> {
+  // context lost すると完了しないので作り直すこと
+  pub fn is_lost(&self) -> bool {
+    self.generation != Instance::generation()
+  }
+  pub fn is_ready(&self) -> bool {
+    if self.is_lost() {
+      return false;
+    }
+    let ctx = Instance::ctx();
+    let status = ctx.client_wait_sync_with_u32(&self.sync, 0, 0);
+    status == gl::ALREADY_SIGNALED || status == gl::CONDITION_SATISFIED
+  }
+  // 完了していなければ None
+  pub fn poll(&self) -> Option<Pixels<T>> {
+    if !self.is_ready() {
+      return None;
+    }
+    let mut bytes = vec![0; self.width * self.height * 4 * std::mem::size_of::<T>()];
+    self.buffer.read_untyped(0, &mut bytes);
+    Some(Pixels {
+      width: self.width,
+      height: self.height,
+      data: T::from_bytes(&bytes),
+    })
+  }
+}
+impl<T: ReadPixelElement> Drop for PendingReadback<T> {
+  fn drop(&mut self) {
+    if !self.is_lost() {
+      let ctx = Instance::ctx();
+      ctx.delete_sync(Some(&self.sync));
+    }
+  }
+}
+
+// 空の rect や読む対象(width x height)からはみ出す rect は読まない
+fn is_readable_rect(rect: &Rect<i32>, width: i32, height: i32) -> bool {
+  let readable = rect.width > 0
+    && rect.height > 0
+    && rect.x >= 0
+    && rect.y >= 0
+    && rect.x + rect.width <= width
+    && rect.y + rect.height <= height;
+  if !readable {
+    log::error(format!(
+      "rect ({}, {}, {}, {}) is out of the read target ({} x {})",
+      rect.x, rect.y, rect.width, rect.height, width, height
+    ));
+  }
+  readable
+}
+
+// 現在 READ_FRAMEBUFFER に bind されているもの(大きさは width x height)から読む
+// context lost 中は何も読めないので None
+fn read_pixels_impl<T: ReadPixelElement>(
+  rect: &Rect<i32>,
+  width: i32,
+  height: i32,
+) -> Option<Pixels<T>> {
+  if Instance::is_context_lost() || !is_readable_rect(rect, width, height) {
+    return None;
+  }
+  let data = T::read_pixels_sync(rect);
+  if data.is_none() {
+    log::error("failed to read pixels");
+  }
+  Some(Pixels {
+    width: rect.width as usize,
+    height: rect.height as usize,
+    data: data?,
+  })
+}
+fn read_pixels_async_impl<T: ReadPixelElement>(
+  rect: &Rect<i32>,
+  width: i32,
+  height: i32,
+) -> Option<PendingReadback<T>> {
+  if Instance::is_context_lost() || !is_readable_rect(rect, width, height) {
+    return None;
+  }
+  let ctx = Instance::ctx();
+  let width = rect.width as usize;
+  let height = rect.height as usize;
+  let size = width * height * 4 * std::mem::size_of::<T>();
+  let buffer = RawBuffer::new_uninitialized_untyped(size as i32, BufferUsage::PixelPack);
+  ctx.bind_buffer(gl::PIXEL_PACK_BUFFER, Some(&buffer.raw_buffer()));
+  let result = ctx.read_pixels_with_i32(
+    rect.x,
+    rect.y,
+    rect.width,
+    rect.height,
+    <T as ReadPixelElement>::FORMAT,
+    T::TYPE,
+    0,
+  );
+  ctx.bind_buffer(gl::PIXEL_PACK_BUFFER, None);
+  if result.is_err() {
+    log::error("failed to read pixels");
+    return None;
+  }
+  let sync = ctx.fence_sync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0)?;
+  ctx.flush();
+  Some(PendingReadback {
+    buffer,
+    sync,
+    generation: Instance::generation(),
+    width,
+    height,
+    phantom: std::marker::PhantomData,
+  })
+}
+
+// テクスチャを一時的な framebuffer に付けて読む
+// layer は cube なら面, 2d array / 3d なら層. 付けられなければ None
+fn with_texture_bound<R>(
+  texture: &Texture,
+  layer: usize,
+  f: impl FnOnce() -> Option<R>,
+) -> Option<R> {
+  if layer >= texture.depth() {
+    log::error(format!(
+      "layer {} is out of range (depth: {})",
+      layer,
+      texture.depth()
+    ));
+    return None;
+  }
+  if Instance::is_context_lost() {
+    return None;
+  }
+  let ctx = Instance::ctx();
+  let framebuffer = RawFrameBuffer::new();
+  ctx.bind_framebuffer(gl::READ_FRAMEBUFFER, Some(&framebuffer.raw_framebuffer()));
+  let raw_texture = texture.raw_texture().raw_texture();
+  let raw_texture = Some(&raw_texture);
+  match texture.target() {
+    gl::TEXTURE_2D => {
+      ctx.framebuffer_texture_2d(
+        gl::READ_FRAMEBUFFER,
+        gl::COLOR_ATTACHMENT0,
+        gl::TEXTURE_2D,
+        raw_texture,
+        0,
+      );
+    }
+    gl::TEXTURE_CUBE_MAP => {
+      let face = gl::TEXTURE_CUBE_MAP_POSITIVE_X + layer as u32;
+      ctx.framebuffer_texture_2d(
+        gl::READ_FRAMEBUFFER,
+        gl::COLOR_ATTACHMENT0,
+        face,
+        raw_texture,
+        0,
+      );
+    }
+    _ => {
+      ctx.framebuffer_texture_layer(
+        gl::READ_FRAMEBUFFER,
+        gl::COLOR_ATTACHMENT0,
+        raw_texture,
+        0,
+        layer as i32,
+      );
+    }
+  }
+  let result = if ctx.check_framebuffer_status(gl::READ_FRAMEBUFFER) == gl::FRAMEBUFFER_COMPLETE {
+    ctx.read_buffer(gl::COLOR_ATTACHMENT0);
+    f()
+  } else {
+    log::error("texture can not be read (incomplete framebuffer)");
+    None
+  };
+  ctx.bind_framebuffer(gl::READ_FRAMEBUFFER, None);
+  result
+}
+
+impl Texture {
+  pub fn read_pixels<T: ReadPixelElement>(&self, rect: &Rect<i32>) -> Option<Pixels<T>> {
+    self.read_layer_pixels(0, rect)
+  }
+  pub fn read_pixels_async<T: ReadPixelElement>(
+    &self,
+    rect: &Rect<i32>,
+  ) -> Option<PendingReadback<T>> {
+    self.read_layer_pixels_async(0, rect)
+  }
+  // cube の面や 2d array / 3d の層を読む
+  pub fn read_layer_pixels<T: ReadPixelElement>(
+    &self,
+    layer: usize,
+    rect: &Rect<i32>,
+  ) -> Option<Pixels<T>> {
+    let (width, height) = (self.width() as i32, self.height() as i32);
+    with_texture_bound(self, layer, || read_pixels_impl(rect, width, height))
+  }
+  pub fn read_layer_pixels_async<T: ReadPixelElement>(
+    &self,
+    layer: usize,
+    rect: &Rect<i32>,
+  ) -> Option<PendingReadback<T>> {
+    let (width, height) = (self.width() as i32, self.height() as i32);
+    with_texture_bound(self, layer, || read_pixels_async_impl(rect, width, height))
+  }
+}
+
+// 既定の framebuffer は合成後に破棄されるので描画と同じフレームで読むこと
+pub struct DefaultFramebuffer {}
+impl DefaultFramebuffer {
+  pub fn read_pixels<T: ReadPixelElement>(rect: &Rect<i32>) -> Option<Pixels<T>> {
+    let ctx = Instance::ctx();
+    ctx.bind_framebuffer(gl::READ_FRAMEBUFFER, None);
+    ctx.read_buffer(gl::BACK);
+    read_pixels_impl(
+      rect,
+      ctx.drawing_buffer_width(),
+      ctx.drawing_buffer_height(),
+    )
+  }
+  pub fn read_pixels_async<T: ReadPixelElement>(rect: &Rect<i32>) -> Option<PendingReadback<T>> {
+    let ctx = Instance::ctx();
+    ctx.bind_framebuffer(gl::READ_FRAMEBUFFER, None);
+    ctx.read_buffer(gl::BACK);
+    read_pixels_async_impl(
+      rect,
+      ctx.drawing_buffer_width(),
+      ctx.drawing_buffer_height(),
+    )
+  }
+}
@@ -1,15 +1,111 @@
 use super::*;
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RenderPassAttachment {
+  Color(usize),
+  Depth,
+}
+#[derive(Clone, PartialEq, Debug)]
+pub enum RenderPassError {
+  NotRenderableFormat(RenderPassAttachment),
+  InvalidLevel(RenderPassAttachment),
+  InvalidLayer(RenderPassAttachment),
+  MismatchedSize {
+    attachment: RenderPassAttachment,
+    expected: (usize, usize),
+    actual: (usize, usize),
+  },
+  IncompleteFramebuffer(FramebufferStatus),
+  // slot が Capabilities::max_output_slot を超えている
+  TooManyColorAttachments {
+    slot: i32,
+    max: usize,
+  },
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CubeFace {
+  PositiveX = 0,
+  NegativeX = 1,
+  PositiveY = 2,
+  NegativeY = 3,
+  PositiveZ = 4,
+  NegativeZ = 5,
+}
+
+// 描画先のテクスチャ. layer は 2d array / 3d なら layer, cubemap なら面
+#[derive(Clone)]
+pub struct RenderTarget {
+  pub texture: SReader<Texture>,
+  pub level: usize,
+  pub layer: usize,
+}
+impl RenderTarget {
+  pub fn new(texture: &dyn SReaderTrait<Texture>) -> Self {
+    Self {
+      texture: texture.clone_reader(),
+      level: 0,
+      layer: 0,
+    }
+  }
+  pub fn with_level(mut self, level: usize) -> Self {
+    self.level = level;
+    self
+  }
+  pub fn with_layer(mut self, layer: usize) -> Self {
+    self.layer = layer;
+    self
+  }
+  pub fn with_cube_face(self, face: CubeFace) -> Self {
+    self.with_layer(face as usize)
+  }
+  // mip level での大きさ
+  pub fn size(&self) -> (usize, usize) {
+    let texture = self.texture.read();
+    let width = std::cmp::max(texture.width() >> self.level, 1);
+    let height = std::cmp::max(texture.height() >> self.level, 1);
+    (width, height)
+  }
+  // mip level での layer の数. 3d は level ごとに奥行きも半分になる
+  pub fn layers(&self) -> usize {
+    let texture = self.texture.read();
+    if texture.target() == gl::TEXTURE_3D {
+      std::cmp::max(texture.depth() >> self.level, 1)
+    } else {
+      texture.depth()
+    }
+  }
+}
+
 struct BufferSetupInfo {
   pub is_dirty: bool,
+  pub error: Option<RenderPassError>,
   pub viewport: Option<Rect<i32>>, // ターゲットなしならBuffer=None
   pub use_default_buffer: bool,
 }
 
+// 整数テクスチャは整数で clear しないといけない
+#[derive(Clone, Copy)]
+pub enum ClearColor {
+  Float(Vec4),
+  Int(IVec4),
+  Uint(UVec4),
+}
+impl ClearColor {
+  fn apply(&self, slot: i32) {
+    let ctx = Instance::ctx();
+    match self {
+      ClearColor::Float(v) => ctx.clear_bufferfv_with_f32_array(gl::COLOR, slot, &v.to_array()),
+      ClearColor::Int(v) => ctx.clear_bufferiv_with_i32_array(gl::COLOR, slot, &v.to_array()),
+      ClearColor::Uint(v) => ctx.clear_bufferuiv_with_u32_array(gl::COLOR, slot, &v.to_array()),
+    }
+  }
+}
+
 use std::sync::atomic::{AtomicUsize, Ordering};
 static ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
 pub struct RenderPass {
-  clear_colors: [Option<Vec4>; MAX_OUTPUT_SLOT],
+  clear_colors: [Option<ClearColor>; MAX_OUTPUT_SLOT],
   clear_depth: Option<f32>,
   clear_stencil: Option<i32>,
   //
@@ -18,8 +114,8 @@ pub struct RenderPass {
   // scissor: Option<Rect<i32>>,
   //
   // None => Surface
-  color_targets: Vec<Option<SReader<Texture>>>,
-  depth_target: Option<SReader<Texture>>,
+  color_targets: Vec<Option<RenderTarget>>,
+  depth_target: Option<RenderTarget>,
   // stencil_target: Option<SReader<Texture>>,
   //
   raw_framebuffer: RawFrameBuffer,
@@ -51,6 +147,7 @@ impl RenderPass {
       // raw_renderbuffer: RawRenderBuffer::new(ctx),
       buffer_setup_info: SRwLock::new(BufferSetupInfo {
         is_dirty: true,
+        error: None,
         viewport: None,
         use_default_buffer: false,
       }),
@@ -62,41 +159,115 @@ impl RenderPass {
   }
   fn setup_framebuffer_impl(&self) {
     let mut setup_info = self.buffer_setup_info.write();
+    if self.raw_framebuffer.restore() {
+      setup_info.is_dirty = true;
+    }
     if !setup_info.is_dirty {
       return;
     }
     let ctx = Instance::ctx();
     let framebuffer = self.raw_framebuffer.raw_framebuffer();
-    ctx.bind_framebuffer(gl::FRAMEBUFFER, Some(framebuffer));
+    ctx.bind_framebuffer(gl::FRAMEBUFFER, Some(&framebuffer));
     let mut color_attachment_indices = Vec::new();
     let mut max_width: i32 = 0;
     let mut max_height: i32 = 0;
     let mut bind_count: i32 = 0;
-    let mut bind_impl = |attachment: u32, texture: &SReader<Texture>| {
-      texture.read().raw_texture().bind();
-      ctx.framebuffer_texture_2d(
-        gl::FRAMEBUFFER,
-        attachment,
-        texture.read().target(),
-        Some(texture.read().raw_texture().raw_texture()),
-        0, // must be 0
-      );
-      max_width = std::cmp::max(max_width, texture.read().width() as i32);
-      max_height = std::cmp::max(max_height, texture.read().height() as i32);
-      bind_count += 1;
-      if SET_BIND_NONE_AFTER_WORK {
-        ctx.bind_texture(gl::TEXTURE_2D, None);
+    let mut error = None;
+    let mut size: Option<(usize, usize)> = None;
+    let mut validate = |attachment: RenderPassAttachment, target: &RenderTarget| {
+      let texture = target.texture.read();
+      let format = texture.raw_texture().format();
+      let renderable = match attachment {
+        RenderPassAttachment::Color(_) => {
+          format.is_color_renderable() || Instance::enable_any_extension(format.render_extensions())
+        }
+        RenderPassAttachment::Depth => format.is_depth(),
+      };
+      if !renderable {
+        return Err(RenderPassError::NotRenderableFormat(attachment));
+      }
+      if target.level >= texture.mip_levels() {
+        return Err(RenderPassError::InvalidLevel(attachment));
+      }
+      if target.layer >= target.layers() {
+        return Err(RenderPassError::InvalidLayer(attachment));
+      }
+      let actual = target.size();
+      match size {
+        Some(expected) if expected != actual => Err(RenderPassError::MismatchedSize {
+          attachment,
+          expected,
+          actual,
+        }),
+        _ => {
+          size = Some(actual);
+          Ok(())
+        }
       }
     };
     for i in 0..MAX_OUTPUT_SLOT {
-      if let Some(texture) = &self.color_targets[i] {
-        let color_attachment_index = index_to_color_attachments_enum(i);
+      if let Some(target) = &self.color_targets[i] {
+        if let Err(e) = validate(RenderPassAttachment::Color(i), target) {
+          error.get_or_insert(e);
+        }
+      }
+    }
+    if let Some(target) = &self.depth_target {
+      if let Err(e) = validate(RenderPassAttachment::Depth, target) {
+        error.get_or_insert(e);
+      }
+    }
+    let mut bind_impl = |attachment: u32, target: &RenderTarget| {
+      let texture = target.texture.read();
+      let raw_texture = texture.raw_texture().raw_texture();
+      let raw_texture = Some(&raw_texture);
+      let level = target.level as i32;
+      match texture.target() {
+        gl::TEXTURE_2D => {
+          ctx.framebuffer_texture_2d(
+            gl::FRAMEBUFFER,
+            attachment,
+            gl::TEXTURE_2D,
+            raw_texture,
+            level,
+          );
+        }
+        gl::TEXTURE_CUBE_MAP => {
+          let face = gl::TEXTURE_CUBE_MAP_POSITIVE_X + target.layer as u32;
+          ctx.framebuffer_texture_2d(gl::FRAMEBUFFER, attachment, face, raw_texture, level);
+        }
+        _ => {
+          let layer = target.layer as i32;
+          ctx.framebuffer_texture_layer(gl::FRAMEBUFFER, attachment, raw_texture, level, layer);
+        }
+      }
+      let (width, height) = target.size();
+      max_width = std::cmp::max(max_width, width as i32);
+      max_height = std::cmp::max(max_height, height as i32);
+      bind_count += 1;
+    };
+    // 外された slot が残らないように外しておく
+    let unbind_impl = |attachment: u32| {
+      ctx.framebuffer_texture_2d(gl::FRAMEBUFFER, attachment, gl::TEXTURE_2D, None, 0);
+    };
+    // drawbuffer の index と slot を一致させるため空きは NONE で埋める
+    for i in 0..Instance::capabilities().max_output_slot() {
+      let color_attachment_index = index_to_color_attachments_enum(i);
+      if let Some(target) = &self.color_targets[i] {
+        color_attachment_indices.resize(i, gl::NONE);
         color_attachment_indices.push(color_attachment_index);
-        bind_impl(color_attachment_index, &texture);
+        bind_impl(color_attachment_index, target);
+      } else {
+        unbind_impl(color_attachment_index);
       }
     }
-    if let Some(texture) = &self.depth_target {
-      bind_impl(gl::DEPTH_ATTACHMENT, &texture);
+    unbind_impl(gl::DEPTH_STENCIL_ATTACHMENT);
+    if let Some(target) = &self.depth_target {
+      if target.texture.read().format().has_stencil() {
+        bind_impl(gl::DEPTH_STENCIL_ATTACHMENT, target);
+      } else {
+        bind_impl(gl::DEPTH_ATTACHMENT, target);
+      }
     }
 
     use wasm_bindgen::JsValue;
@@ -118,6 +289,15 @@ impl RenderPass {
       ctx.bind_renderbuffer(gl::RENDERBUFFER, None);
     }
 
+    if bind_count > 0 && error.is_none() {
+      if let Err(status) = self.raw_framebuffer.check_status() {
+        error = Some(RenderPassError::IncompleteFramebuffer(status));
+      }
+    }
+    if let Some(error) = &error {
+      log::error(format!("renderpass {}: {:?}", self.renderpass_id, error));
+    }
+    setup_info.error = error;
     setup_info.is_dirty = false;
     setup_info.viewport = if bind_count > 0 {
       Some(Rect::new(0, 0, max_width, max_height))
@@ -134,7 +314,7 @@ impl RenderPass {
         log::error("[uses default framebuffer] && [has color target]");
       }
       let framebuffer = self.raw_framebuffer.raw_framebuffer();
-      ctx.bind_framebuffer(gl::FRAMEBUFFER, Some(framebuffer));
+      ctx.bind_framebuffer(gl::FRAMEBUFFER, Some(&framebuffer));
       // let renderbuffer = self.raw_renderbuffer.raw_renderbuffer();
       // ctx.bind_renderbuffer(gl::RENDERBUFFER, Some(renderbuffer));
     } else if info.use_default_buffer {
@@ -145,26 +325,26 @@ impl RenderPass {
     }
   }
 
-  fn clear_impl(&self) {
+  fn clear_impl(&self, cmd: &mut Command) {
+    let need_clear = self.clear_colors.iter().any(|c| c.is_some())
+      || self.clear_depth.is_some()
+      || self.clear_stencil.is_some();
+    if !need_clear {
+      return;
+    }
+    // color mask / stencil mask が clear に影響しないよう既定値に戻す
+    cmd.set_pipeline_state(&PipelineStateObject::default());
     let ctx = Instance::ctx();
-    let mut clear_flag = 0;
-    for i in 0..MAX_OUTPUT_SLOT {
-      if let Some(color) = self.clear_colors[i] {
-        // TODO: clearBufferfv
-        ctx.clear_color(color.x, color.y, color.z, color.w);
-        clear_flag |= gl::COLOR_BUFFER_BIT;
+    for (i, color) in self.clear_colors.iter().enumerate() {
+      if let Some(color) = color {
+        color.apply(i as i32);
       }
     }
-    if let Some(depth) = self.clear_depth {
-      ctx.clear_depth(depth);
-      clear_flag |= gl::DEPTH_BUFFER_BIT;
-    }
-    if let Some(stencil) = self.clear_stencil {
-      ctx.clear_stencil(stencil);
-      clear_flag |= gl::STENCIL_BUFFER_BIT;
-    }
-    if clear_flag != 0 {
-      ctx.clear(clear_flag);
+    match (self.clear_depth, self.clear_stencil) {
+      (Some(depth), Some(stencil)) => ctx.clear_bufferfi(gl::DEPTH_STENCIL, 0, depth, stencil),
+      (Some(depth), None) => ctx.clear_bufferfv_with_f32_array(gl::DEPTH, 0, &[depth]),
+      (None, Some(stencil)) => ctx.clear_bufferiv_with_i32_array(gl::STENCIL, 0, &[stencil]),
+      (None, None) => {}
     }
   }
 
@@ -186,15 +366,19 @@ impl RenderPass {
       return;
     }
     self.setup_framebuffer_impl();
+    if self.buffer_setup_info.read().error.is_some() {
+      return;
+    }
     self.bind_framebuffer_impl();
     self.viewport_impl();
-    self.clear_impl();
+    self.clear_impl(cmd);
     let outer_ctx = DescriptorContext::cons(outer_ctx, &self.descriptor);
     self.executer.write().execute(cmd, &outer_ctx);
   }
 
   pub fn set_color_target(&mut self, target: Option<&dyn SReaderTrait<Texture>>) {
-    self.set_color_target_by_slot(target, 0);
+    // slot 0 は常に使える
+    self.set_color_target_by_slot(target, 0).ok();
   }
   pub fn set_clear_color(&mut self, value: Option<Vec4>) {
     self.set_clear_color_by_slot(value, 0);
@@ -213,24 +397,46 @@ impl RenderPass {
     info.use_default_buffer = use_default_buffer;
   }
   pub fn set_depth_target(&mut self, target: Option<&dyn SReaderTrait<Texture>>) {
-    self.depth_target = target.map(|target| target.clone_reader());
+    self.set_depth_attachment(target.map(RenderTarget::new));
+  }
+  pub fn set_depth_attachment(&mut self, target: Option<RenderTarget>) {
+    self.depth_target = target;
     self.buffer_setup_info.write().is_dirty = true;
   }
   pub fn set_color_target_by_slot(
     &mut self,
     target: Option<&dyn SReaderTrait<Texture>>,
     slot: i32,
-  ) {
-    if slot < 0 || slot >= MAX_OUTPUT_SLOT as i32 {
-      log::error(format!("Invalid set_color_target_by_slot {}", slot));
-      return;
+  ) -> Result<(), RenderPassError> {
+    self.set_color_attachment_by_slot(target.map(RenderTarget::new), slot)
+  }
+  // GPU で使える color attachment の数を超える slot には付けられない
+  pub fn set_color_attachment_by_slot(
+    &mut self,
+    target: Option<RenderTarget>,
+    slot: i32,
+  ) -> Result<(), RenderPassError> {
+    let max = Instance::capabilities().max_output_slot();
+    if slot < 0 || slot >= max as i32 {
+      log::error(format!("Invalid set_color_attachment_by_slot {}", slot));
+      return Err(RenderPassError::TooManyColorAttachments { slot, max });
     }
-    self.color_targets[slot as usize] = target.map(|target| target.clone_reader());
+    self.color_targets[slot as usize] = target;
     self.buffer_setup_info.write().is_dirty = true;
+    Ok(())
   }
   pub fn set_clear_color_by_slot(&mut self, value: Option<Vec4>, slot: i32) {
-    if slot < 0 || slot >= MAX_OUTPUT_SLOT as i32 {
-      log::error(format!("Invalid set_clear_color_by_slot {}", slot));
+    self.set_clear_value_by_slot(value.map(ClearColor::Float), slot);
+  }
+  pub fn set_clear_int_by_slot(&mut self, value: Option<IVec4>, slot: i32) {
+    self.set_clear_value_by_slot(value.map(ClearColor::Int), slot);
+  }
+  pub fn set_clear_uint_by_slot(&mut self, value: Option<UVec4>, slot: i32) {
+    self.set_clear_value_by_slot(value.map(ClearColor::Uint), slot);
+  }
+  pub fn set_clear_value_by_slot(&mut self, value: Option<ClearColor>, slot: i32) {
+    if slot < 0 || slot >= Instance::capabilities().max_output_slot() as i32 {
+      log::error(format!("Invalid set_clear_value_by_slot {}", slot));
       return;
     }
     self.clear_colors[slot as usize] = value;
@@ -284,10 +490,16 @@ impl RenderPass {
     self.disabled_reasons.any()
   }
 
+  // 描画先の設定に問題があれば描画されない
+  pub fn error(&self) -> Option<RenderPassError> {
+    self.buffer_setup_info.read().error.clone()
+  }
+
   pub fn renderpass_id(&self) -> u64 {
     self.renderpass_id
   }
 }
+
 impl Default for RenderPass {
   fn default() -> Self {
     Self::new()
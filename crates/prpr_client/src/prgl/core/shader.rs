@@ -1,53 +1,82 @@
 use super::*;
 
 use std::collections::HashMap;
-pub struct Shader {
-  template: ShaderTemplate,
+// program と, program ごとに取り直す必要のあるもの
+struct LinkedProgram {
   uniform_block_indices: Vec<String>,
   uniform_texture_locations: Vec<(String, UniformTextureLocation)>,
   raw_program: RawShaderProgram,
 }
+impl LinkedProgram {
+  fn new(template: &ShaderTemplate) -> Option<Self> {
+    let raw_program = RawShaderProgram::new(template)?;
+    // context lost 中に作られたもの. 引く値がないので restore 後に link し直すまで空にしておく
+    if raw_program.is_lost() {
+      return Some(Self {
+        uniform_block_indices: Vec::new(),
+        uniform_texture_locations: Vec::new(),
+        raw_program,
+      });
+    }
+    let ctx = Instance::ctx();
+    let program = raw_program.raw_program();
+    let mut max_uniform_block_indices: usize = 0;
+    let mut map_uniform_block_indices: HashMap<String, usize> = HashMap::new();
+    for name in template.uniform_blocks() {
+      let u_index = ctx.get_uniform_block_index(&program, name);
+      ctx.uniform_block_binding(&program, u_index, u_index);
+      map_uniform_block_indices.insert(String::from(*name), u_index as usize);
+      max_uniform_block_indices = max_uniform_block_indices.max(u_index as usize);
+    }
+    let mut uniform_block_indices = vec![String::from(""); max_uniform_block_indices + 1];
+    for (k, v) in map_uniform_block_indices {
+      uniform_block_indices[v] = k;
+    }
 
-impl Shader {
-  pub fn new(template: ShaderTemplate) -> Option<Self> {
-    if let Some(raw_program) = RawShaderProgram::new(&template) {
-      let ctx = Instance::ctx();
-      let mut max_uniform_block_indices: usize = 0;
-      let mut map_uniform_block_indices: HashMap<String, usize> = HashMap::new();
-      for name in template.uniform_blocks() {
-        let u_index = ctx.get_uniform_block_index(raw_program.raw_program(), name);
-        ctx.uniform_block_binding(raw_program.raw_program(), u_index, u_index);
-        map_uniform_block_indices.insert(String::from(*name), u_index as usize);
-        max_uniform_block_indices = max_uniform_block_indices.max(u_index as usize);
-      }
-      let mut uniform_block_indices = vec![String::from(""); max_uniform_block_indices + 1];
-      for (k, v) in map_uniform_block_indices {
-        uniform_block_indices[v] = k;
+    let mut map_uniform_texture_locations: HashMap<String, UniformTextureLocation> = HashMap::new();
+    let template_uniform_textures = template.uniform_textures();
+    for i in 0..template_uniform_textures.len() {
+      let name = template_uniform_textures[i];
+      let location = ctx.get_uniform_location(&program, name);
+      if let Some(location) = location {
+        map_uniform_texture_locations.insert(String::from(name), (location, i as i32));
       }
+    }
+    let mut uniform_texture_locations = Vec::new();
+    for data in map_uniform_texture_locations {
+      uniform_texture_locations.push(data);
+    }
+    Some(Self {
+      uniform_block_indices,
+      uniform_texture_locations,
+      raw_program,
+    })
+  }
+}
 
-      let mut map_uniform_texture_locations: HashMap<String, UniformTextureLocation> =
-        HashMap::new();
-      let template_uniform_textures = template.uniform_textures();
-      for i in 0..template_uniform_textures.len() {
-        let name = template_uniform_textures[i];
-        let location = ctx.get_uniform_location(raw_program.raw_program(), name);
-        if let Some(location) = location {
-          map_uniform_texture_locations.insert(String::from(name), (location, i as i32));
-        }
-      }
-      let mut uniform_texture_locations = Vec::new();
-      for data in map_uniform_texture_locations {
-        uniform_texture_locations.push(data);
+pub struct Shader {
+  template: ShaderTemplate,
+  linked: SRwLock<LinkedProgram>,
+}
+
+impl Shader {
+  pub fn new(template: ShaderTemplate) -> Option<Self> {
+    let linked = LinkedProgram::new(&template)?;
+    Some(Self {
+      template,
+      linked: SRwLock::new(linked),
+    })
+  }
+  // context lost で無効になっていれば template から link し直す
+  fn linked(&self) -> SDerefable<'_, LinkedProgram> {
+    if self.linked.read().raw_program.is_lost() {
+      if let Some(linked) = LinkedProgram::new(&self.template) {
+        *self.linked.write() = linked;
+      } else {
+        log::error("failed to relink shader after context restored");
       }
-      Some(Self {
-        uniform_block_indices,
-        uniform_texture_locations,
-        template,
-        raw_program,
-      })
-    } else {
-      None
     }
+    self.linked.read()
   }
   pub fn vs_code(&self) -> String {
     self.template.vs_code()
@@ -55,18 +84,19 @@ impl Shader {
   pub fn fs_code(&self) -> String {
     self.template.fs_code()
   }
+  // link し直すと変わる
   pub fn id(&self) -> u64 {
-    self.raw_program.program_id()
+    self.linked().raw_program.program_id()
   }
   pub fn use_program(&self) {
-    self.raw_program.use_program();
+    self.linked().raw_program.use_program();
   }
-  pub fn raw_program(&self) -> &RawShaderProgram {
-    &self.raw_program
+  pub fn raw_program(&self) -> SDerefable<'_, RawShaderProgram> {
+    SDerefable::map(self.linked(), |x| &x.raw_program)
   }
   // 数がたいてい少ないのでHashMap使うほうが遅い
   pub fn uniform_block_index(&self, name: &str) -> Option<u32> {
-    for (i, v) in self.uniform_block_indices.iter().enumerate() {
+    for (i, v) in self.linked().uniform_block_indices.iter().enumerate() {
       if v == name {
         return Some(i as u32);
       }
@@ -74,10 +104,10 @@ impl Shader {
     None
   }
   // 数がたいてい少ないのでHashMap使うほうが遅い
-  pub fn uniform_texture_location(&self, name: &str) -> Option<&UniformTextureLocation> {
-    for data in &self.uniform_texture_locations {
+  pub fn uniform_texture_location(&self, name: &str) -> Option<UniformTextureLocation> {
+    for data in &self.linked().uniform_texture_locations {
       if data.0 == name {
-        return Some(&data.1);
+        return Some(data.1.clone());
       }
     }
     None
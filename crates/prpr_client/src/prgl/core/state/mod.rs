@@ -3,3 +3,5 @@ pub mod pipeline;
 pub use self::pipeline::*;
 pub mod sampler;
 pub use self::sampler::*;
+pub mod pipeline_state;
+pub use self::pipeline_state::*;
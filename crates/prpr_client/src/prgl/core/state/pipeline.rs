@@ -1,6 +1,6 @@
 use super::*;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub enum PrimitiveToporogy {
   Points = gl::POINTS as isize,
   LineStrip = gl::LINE_STRIP as isize,
@@ -11,14 +11,39 @@ pub enum PrimitiveToporogy {
   Triangles = gl::TRIANGLES as isize,
 }
 
+#[derive(Clone, Copy, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum IndexFormat {
+  u8 = gl::UNSIGNED_BYTE as isize,
+  u16 = gl::UNSIGNED_SHORT as isize,
+  u32 = gl::UNSIGNED_INT as isize,
+}
+impl IndexFormat {
+  pub fn size(&self) -> usize {
+    match self {
+      Self::u8 => 1,
+      Self::u16 => 2,
+      Self::u32 => 4,
+    }
+  }
+}
+
+#[derive(Clone, Copy)]
 pub enum DrawCommand {
-  Draw { first: i32, count: i32 },
+  Draw {
+    first: i32,
+    count: i32,
+  },
   // DrawInstanced {
   //   first: i32,
   //   count: i32,
   //   instance_count: i32,
   // },
-  DrawIndexed { first: i32, count: i32 },
+  DrawIndexed {
+    first: i32,
+    count: i32,
+    format: IndexFormat,
+  },
   // DrawIndexedInstanced {
   //   first: i32,
   //   count: i32,
@@ -33,15 +58,20 @@ impl DrawCommand {
       DrawCommand::Draw { first, count } => {
         ctx.draw_arrays(topology, *first, *count);
       }
-      DrawCommand::DrawIndexed { first, count } => {
-        assert_type_eq!(u32, IndexBufferType);
-        ctx.draw_elements_with_i32(topology, *count, gl::UNSIGNED_INT, *first);
+      DrawCommand::DrawIndexed {
+        first,
+        count,
+        format,
+      } => {
+        // offset は byte 単位
+        let offset = *first * format.size() as i32;
+        ctx.draw_elements_with_i32(topology, *count, *format as u32, offset);
       }
     }
   }
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub enum CullMode {
   None = 0 as isize,
   Front = gl::FRONT as isize,
@@ -60,7 +90,7 @@ impl CullMode {
   }
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub enum DepthFunc {
   Never = gl::NEVER as isize,
   Less = gl::LESS as isize,
@@ -72,13 +102,266 @@ pub enum DepthFunc {
   Always = gl::ALWAYS as isize,
 }
 impl DepthFunc {
-  pub fn apply(&self) {
+  // depth test を切ると書き込みもされないので Always でも書き込むなら test は有効にする
+  pub fn apply(&self, depth_write: bool) {
     let ctx = Instance::ctx();
-    if *self == DepthFunc::Always {
+    if *self == DepthFunc::Always && !depth_write {
       ctx.disable(gl::DEPTH_TEST);
     } else {
       ctx.enable(gl::DEPTH_TEST);
       ctx.depth_func(*self as u32);
     }
+    ctx.depth_mask(depth_write);
+  }
+}
+
+// f32 を含むので Hash / Eq はビット列で比較する
+#[derive(Clone, Copy)]
+pub struct DepthRange {
+  pub near: f32,
+  pub far: f32,
+}
+impl DepthRange {
+  pub fn new(near: f32, far: f32) -> Self {
+    Self { near, far }
+  }
+  pub fn apply(&self) {
+    let ctx = Instance::ctx();
+    ctx.depth_range(self.near, self.far);
+  }
+}
+impl Default for DepthRange {
+  fn default() -> Self {
+    Self::new(0.0, 1.0)
+  }
+}
+impl PartialEq for DepthRange {
+  fn eq(&self, other: &Self) -> bool {
+    self.near.to_bits() == other.near.to_bits() && self.far.to_bits() == other.far.to_bits()
+  }
+}
+impl Eq for DepthRange {}
+impl std::hash::Hash for DepthRange {
+  fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+    self.near.to_bits().hash(state);
+    self.far.to_bits().hash(state);
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlendFactor {
+  Zero = gl::ZERO as isize,
+  One = gl::ONE as isize,
+  SrcColor = gl::SRC_COLOR as isize,
+  OneMinusSrcColor = gl::ONE_MINUS_SRC_COLOR as isize,
+  DstColor = gl::DST_COLOR as isize,
+  OneMinusDstColor = gl::ONE_MINUS_DST_COLOR as isize,
+  SrcAlpha = gl::SRC_ALPHA as isize,
+  OneMinusSrcAlpha = gl::ONE_MINUS_SRC_ALPHA as isize,
+  DstAlpha = gl::DST_ALPHA as isize,
+  OneMinusDstAlpha = gl::ONE_MINUS_DST_ALPHA as isize,
+  SrcAlphaSaturate = gl::SRC_ALPHA_SATURATE as isize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlendOp {
+  Add = gl::FUNC_ADD as isize,
+  Subtract = gl::FUNC_SUBTRACT as isize,
+  ReverseSubtract = gl::FUNC_REVERSE_SUBTRACT as isize,
+  Min = gl::MIN as isize,
+  Max = gl::MAX as isize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlendState {
+  pub src_color: BlendFactor,
+  pub dst_color: BlendFactor,
+  pub color_op: BlendOp,
+  pub src_alpha: BlendFactor,
+  pub dst_alpha: BlendFactor,
+  pub alpha_op: BlendOp,
+}
+impl BlendState {
+  pub fn new(src: BlendFactor, dst: BlendFactor, op: BlendOp) -> Self {
+    Self {
+      src_color: src,
+      dst_color: dst,
+      color_op: op,
+      src_alpha: src,
+      dst_alpha: dst,
+      alpha_op: op,
+    }
+  }
+  pub fn alpha() -> Self {
+    Self::new(
+      BlendFactor::SrcAlpha,
+      BlendFactor::OneMinusSrcAlpha,
+      BlendOp::Add,
+    )
+  }
+  pub fn premultiplied_alpha() -> Self {
+    Self::new(
+      BlendFactor::One,
+      BlendFactor::OneMinusSrcAlpha,
+      BlendOp::Add,
+    )
+  }
+  pub fn additive() -> Self {
+    Self::new(BlendFactor::One, BlendFactor::One, BlendOp::Add)
+  }
+  // None なら blend しない
+  pub fn apply(v: &Option<Self>) {
+    let ctx = Instance::ctx();
+    if let Some(v) = v {
+      ctx.enable(gl::BLEND);
+      ctx.blend_func_separate(
+        v.src_color as u32,
+        v.dst_color as u32,
+        v.src_alpha as u32,
+        v.dst_alpha as u32,
+      );
+      ctx.blend_equation_separate(v.color_op as u32, v.alpha_op as u32);
+    } else {
+      ctx.disable(gl::BLEND);
+    }
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StencilFunc {
+  Never = gl::NEVER as isize,
+  Less = gl::LESS as isize,
+  Equal = gl::EQUAL as isize,
+  LEqual = gl::LEQUAL as isize,
+  Greater = gl::GREATER as isize,
+  NotEqual = gl::NOTEQUAL as isize,
+  GEqual = gl::GEQUAL as isize,
+  Always = gl::ALWAYS as isize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StencilOp {
+  Keep = gl::KEEP as isize,
+  Zero = gl::ZERO as isize,
+  Replace = gl::REPLACE as isize,
+  Incr = gl::INCR as isize,
+  IncrWrap = gl::INCR_WRAP as isize,
+  Decr = gl::DECR as isize,
+  DecrWrap = gl::DECR_WRAP as isize,
+  Invert = gl::INVERT as isize,
+}
+
+// 表裏で同じ設定を使う
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StencilState {
+  pub func: StencilFunc,
+  pub reference: i32,
+  pub read_mask: u32,
+  pub write_mask: u32,
+  pub fail: StencilOp,
+  pub depth_fail: StencilOp,
+  pub pass: StencilOp,
+}
+impl StencilState {
+  pub fn new(func: StencilFunc, reference: i32) -> Self {
+    Self {
+      func,
+      reference,
+      read_mask: 0xff,
+      write_mask: 0xff,
+      fail: StencilOp::Keep,
+      depth_fail: StencilOp::Keep,
+      pass: StencilOp::Keep,
+    }
+  }
+  // 参照値を書き込む
+  pub fn write(reference: i32) -> Self {
+    Self {
+      pass: StencilOp::Replace,
+      ..Self::new(StencilFunc::Always, reference)
+    }
+  }
+  // None なら stencil test しない
+  pub fn apply(v: &Option<Self>) {
+    let ctx = Instance::ctx();
+    if let Some(v) = v {
+      ctx.enable(gl::STENCIL_TEST);
+      ctx.stencil_func(v.func as u32, v.reference, v.read_mask);
+      ctx.stencil_mask(v.write_mask);
+      ctx.stencil_op(v.fail as u32, v.depth_fail as u32, v.pass as u32);
+    } else {
+      ctx.disable(gl::STENCIL_TEST);
+      // clear が効くように書き込みは許可しておく
+      ctx.stencil_mask(!0);
+    }
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ColorMask {
+  pub r: bool,
+  pub g: bool,
+  pub b: bool,
+  pub a: bool,
+}
+impl ColorMask {
+  pub fn all() -> Self {
+    Self {
+      r: true,
+      g: true,
+      b: true,
+      a: true,
+    }
+  }
+  pub fn none() -> Self {
+    Self {
+      r: false,
+      g: false,
+      b: false,
+      a: false,
+    }
+  }
+  pub fn apply(&self) {
+    let ctx = Instance::ctx();
+    ctx.color_mask(self.r, self.g, self.b, self.a);
+  }
+}
+impl Default for ColorMask {
+  fn default() -> Self {
+    Self::all()
+  }
+}
+
+// f32 を含むので Hash / Eq はビット列で比較する
+#[derive(Clone, Copy)]
+pub struct PolygonOffset {
+  pub factor: f32,
+  pub units: f32,
+}
+impl PolygonOffset {
+  pub fn new(factor: f32, units: f32) -> Self {
+    Self { factor, units }
+  }
+  // None なら polygon offset しない
+  pub fn apply(v: &Option<Self>) {
+    let ctx = Instance::ctx();
+    if let Some(v) = v {
+      ctx.enable(gl::POLYGON_OFFSET_FILL);
+      ctx.polygon_offset(v.factor, v.units);
+    } else {
+      ctx.disable(gl::POLYGON_OFFSET_FILL);
+    }
+  }
+}
+impl PartialEq for PolygonOffset {
+  fn eq(&self, other: &Self) -> bool {
+    self.factor.to_bits() == other.factor.to_bits() && self.units.to_bits() == other.units.to_bits()
+  }
+}
+impl Eq for PolygonOffset {}
+impl std::hash::Hash for PolygonOffset {
+  fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+    self.factor.to_bits().hash(state);
+    self.units.to_bits().hash(state);
   }
 }
@@ -0,0 +1,186 @@
+use super::*;
+use std::collections::HashMap;
+
+// 描画に関わる固定機能の状態をまとめたもの
+// intern されるので同じ内容なら同じ id になる
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct PipelineState {
+  pub depth_func: DepthFunc,
+  pub depth_write: bool,
+  pub depth_range: DepthRange,
+  pub cull_mode: CullMode,
+  pub blend: Option<BlendState>,
+  pub stencil: Option<StencilState>,
+  pub color_mask: ColorMask,
+  pub polygon_offset: Option<PolygonOffset>,
+  pub topology: PrimitiveToporogy,
+}
+impl PipelineState {
+  pub fn builder() -> PipelineStateBuilder {
+    PipelineStateBuilder {
+      state: Self::default(),
+    }
+  }
+  pub fn intern(self) -> PipelineStateObject {
+    PipelineStateCacheImpl::write_global().intern(self)
+  }
+  // pre と異なる部分のみ反映する
+  pub fn apply_diff(&self, pre: Option<&Self>) {
+    if pre
+      .map(|p| p.depth_func != self.depth_func || p.depth_write != self.depth_write)
+      .unwrap_or(true)
+    {
+      self.depth_func.apply(self.depth_write);
+    }
+    if pre
+      .map(|p| p.depth_range != self.depth_range)
+      .unwrap_or(true)
+    {
+      self.depth_range.apply();
+    }
+    if pre.map(|p| p.cull_mode != self.cull_mode).unwrap_or(true) {
+      self.cull_mode.apply();
+    }
+    if pre.map(|p| p.blend != self.blend).unwrap_or(true) {
+      BlendState::apply(&self.blend);
+    }
+    if pre.map(|p| p.stencil != self.stencil).unwrap_or(true) {
+      StencilState::apply(&self.stencil);
+    }
+    if pre.map(|p| p.color_mask != self.color_mask).unwrap_or(true) {
+      self.color_mask.apply();
+    }
+    if pre
+      .map(|p| p.polygon_offset != self.polygon_offset)
+      .unwrap_or(true)
+    {
+      PolygonOffset::apply(&self.polygon_offset);
+    }
+  }
+}
+impl Default for PipelineState {
+  fn default() -> Self {
+    Self {
+      depth_func: DepthFunc::Less,
+      depth_write: true,
+      depth_range: DepthRange::default(),
+      cull_mode: CullMode::Back,
+      blend: None,
+      stencil: None,
+      color_mask: ColorMask::all(),
+      polygon_offset: None,
+      topology: PrimitiveToporogy::Triangles,
+    }
+  }
+}
+
+// intern 済みの PipelineState. id で比較できる
+#[derive(Clone)]
+pub struct PipelineStateObject {
+  id: u64,
+  state: SRc<PipelineState>,
+}
+impl PipelineStateObject {
+  pub fn id(&self) -> u64 {
+    self.id
+  }
+  pub fn state(&self) -> &PipelineState {
+    &self.state
+  }
+  // 差分だけ変えた variant を作る
+  pub fn to_builder(&self) -> PipelineStateBuilder {
+    PipelineStateBuilder {
+      state: self.state().clone(),
+    }
+  }
+}
+impl PartialEq for PipelineStateObject {
+  fn eq(&self, other: &Self) -> bool {
+    self.id == other.id
+  }
+}
+impl Eq for PipelineStateObject {}
+impl Default for PipelineStateObject {
+  fn default() -> Self {
+    PipelineState::default().intern()
+  }
+}
+
+pub struct PipelineStateBuilder {
+  state: PipelineState,
+}
+impl PipelineStateBuilder {
+  pub fn depth_func(mut self, v: DepthFunc) -> Self {
+    self.state.depth_func = v;
+    self
+  }
+  pub fn depth_write(mut self, v: bool) -> Self {
+    self.state.depth_write = v;
+    self
+  }
+  pub fn depth_range(mut self, v: DepthRange) -> Self {
+    self.state.depth_range = v;
+    self
+  }
+  pub fn cull_mode(mut self, v: CullMode) -> Self {
+    self.state.cull_mode = v;
+    self
+  }
+  pub fn blend(mut self, v: Option<BlendState>) -> Self {
+    self.state.blend = v;
+    self
+  }
+  pub fn stencil(mut self, v: Option<StencilState>) -> Self {
+    self.state.stencil = v;
+    self
+  }
+  pub fn color_mask(mut self, v: ColorMask) -> Self {
+    self.state.color_mask = v;
+    self
+  }
+  pub fn polygon_offset(mut self, v: Option<PolygonOffset>) -> Self {
+    self.state.polygon_offset = v;
+    self
+  }
+  pub fn topology(mut self, v: PrimitiveToporogy) -> Self {
+    self.state.topology = v;
+    self
+  }
+  pub fn build(self) -> PipelineStateObject {
+    self.state.intern()
+  }
+}
+
+static INSTANCE: OnceCell<MRwLock<PipelineStateCacheImpl>> = OnceCell::new();
+unsafe impl Send for PipelineStateCacheImpl {}
+unsafe impl Sync for PipelineStateCacheImpl {}
+pub struct PipelineStateCacheImpl {
+  objects: HashMap<PipelineState, PipelineStateObject>,
+}
+impl PipelineStateCacheImpl {
+  pub fn initialize_global() {
+    INSTANCE.set(MRwLock::new(Self::new())).ok();
+  }
+  pub fn write_global() -> MDerefMutable<'static, Self> {
+    INSTANCE
+      .get()
+      .expect("PipelineStateCache global not initialized")
+      .write()
+  }
+  fn new() -> Self {
+    Self {
+      objects: HashMap::new(),
+    }
+  }
+  fn intern(&mut self, state: PipelineState) -> PipelineStateObject {
+    if let Some(object) = self.objects.get(&state) {
+      return object.clone();
+    }
+    let object = PipelineStateObject {
+      id: self.objects.len() as u64,
+      state: SRc::new(state.clone()),
+    };
+    self.objects.insert(state, object.clone());
+    object
+  }
+}
@@ -24,6 +24,9 @@ pub struct Sampler {
   min_filter: SamplerMinFilter,
   wrap_mode_s: SamplerWrapMode,
   wrap_mode_t: SamplerWrapMode,
+  anisotropy: Option<f32>,
+  // depth texture を sampler2DShadow などで比較して引く
+  compare_func: Option<DepthFunc>,
 }
 
 impl Default for Sampler {
@@ -33,16 +36,86 @@ impl Default for Sampler {
       min_filter: SamplerMinFilter::NearestMipmapLinear,
       wrap_mode_s: SamplerWrapMode::Repeat,
       wrap_mode_t: SamplerWrapMode::Repeat,
+      anisotropy: None,
+      compare_func: None,
     }
   }
 }
 
 impl Sampler {
+  pub fn new(
+    mag_filter: SamplerMagFilter,
+    min_filter: SamplerMinFilter,
+    wrap_mode: SamplerWrapMode,
+  ) -> Self {
+    Self {
+      mag_filter,
+      min_filter,
+      wrap_mode_s: wrap_mode,
+      wrap_mode_t: wrap_mode,
+      anisotropy: None,
+      compare_func: None,
+    }
+  }
+  pub fn nearest() -> Self {
+    Self::new(
+      SamplerMagFilter::Nearest,
+      SamplerMinFilter::Nearest,
+      SamplerWrapMode::ClampToEdge,
+    )
+  }
+  // wrap mode はそのままで filter だけ NEAREST にする
+  pub fn to_nearest(&self) -> Self {
+    Self {
+      mag_filter: SamplerMagFilter::Nearest,
+      min_filter: SamplerMinFilter::Nearest,
+      anisotropy: None,
+      ..self.clone()
+    }
+  }
+  // EXT_texture_filter_anisotropic がなければ無視される
+  pub fn with_anisotropy(mut self, anisotropy: f32) -> Self {
+    self.anisotropy = Some(anisotropy);
+    self
+  }
+  // depth texture 用. LINEAR なら周囲 4 texel の比較結果が補間される
+  pub fn new_shadow() -> Self {
+    Self::new(
+      SamplerMagFilter::Linear,
+      SamplerMinFilter::Linear,
+      SamplerWrapMode::ClampToEdge,
+    )
+    .with_compare(DepthFunc::LEqual)
+  }
+  pub fn is_compare(&self) -> bool {
+    self.compare_func.is_some()
+  }
+  pub fn with_compare(mut self, compare_func: DepthFunc) -> Self {
+    self.compare_func = Some(compare_func);
+    self
+  }
   pub fn apply(&self, target: u32) {
     let ctx = Instance::ctx();
     ctx.tex_parameteri(target, gl::TEXTURE_MAG_FILTER, self.mag_filter as i32);
     ctx.tex_parameteri(target, gl::TEXTURE_MIN_FILTER, self.min_filter as i32);
     ctx.tex_parameteri(target, gl::TEXTURE_WRAP_S, self.wrap_mode_s as i32);
     ctx.tex_parameteri(target, gl::TEXTURE_WRAP_T, self.wrap_mode_t as i32);
+    match self.compare_func {
+      Some(compare_func) => {
+        ctx.tex_parameteri(
+          target,
+          gl::TEXTURE_COMPARE_MODE,
+          gl::COMPARE_REF_TO_TEXTURE as i32,
+        );
+        ctx.tex_parameteri(target, gl::TEXTURE_COMPARE_FUNC, compare_func as i32);
+      }
+      None => ctx.tex_parameteri(target, gl::TEXTURE_COMPARE_MODE, gl::NONE as i32),
+    }
+    if let Some(anisotropy) = self.anisotropy {
+      if Instance::enable_extension(Extension::TextureFilterAnisotropic) {
+        let max = Instance::capabilities().max_anisotropy.unwrap_or(1.0);
+        ctx.tex_parameterf(target, TEXTURE_MAX_ANISOTROPY_EXT, anisotropy.min(max));
+      }
+    }
   }
 }
@@ -21,6 +21,9 @@ pub type mat4 = math::Mat4;
 // Texture用, 名前だけ欲しい
 #[allow(non_camel_case_types)]
 pub type sampler2D = SReader<Texture>;
+// depth の 2d array を compare mode で引く(Sampler::new_shadow)
+#[allow(non_camel_case_types)]
+pub type sampler2DArrayShadow = SReader<Texture>;
 
 #[derive(Debug, Clone)]
 #[allow(non_camel_case_types)]
@@ -40,6 +43,7 @@ pub enum ShaderPrimitiveType {
 #[allow(dead_code)]
 pub enum ShaderSamplerType {
   sampler2D(sampler2D),
+  sampler2DArrayShadow(sampler2DArrayShadow),
 }
 
 #[allow(non_camel_case_types)]
@@ -115,6 +119,13 @@ impl ShaderTemplate {
   pub fn fs_code(&self) -> String {
     format!("{}{}", self.fs_code_definitions, self.fs_code_body)
   }
+  // Skin::vs_helper_code などの関数定義を main より前に足す
+  pub fn add_vs_helper(&mut self, code: &str) {
+    self.vs_code_body = format!("{}\n{}", code, self.vs_code_body);
+  }
+  pub fn add_fs_helper(&mut self, code: &str) {
+    self.fs_code_body = format!("{}\n{}", code, self.fs_code_body);
+  }
   pub fn uniform_blocks(&self) -> &Vec<&'static str> {
     &self.uniform_blocks
   }
@@ -1,13 +1,47 @@
 #[allow(unused_imports)]
 use super::*;
 
+// 配列(`joint_mats: mat4[64]` など)は uniform block 用
+// std140 では要素が 16byte 境界に揃うので vec4 / mat4 の配列だけが使える
+// 動的読み込み用の keys / find などには出てこない
+#[macro_export]
+macro_rules! shader_attr_field_type {
+  ($v:ident) => {
+    $v
+  };
+  ($v:ident [$n:literal]) => {
+    [$v; $n]
+  };
+}
+#[macro_export]
+macro_rules! shader_attr_field_default {
+  ($v:ident) => {
+    $v::default()
+  };
+  ($v:ident [$n:literal]) => {
+    [$v::default(); $n]
+  };
+}
+#[macro_export]
+macro_rules! shader_attr_if_scalar {
+  ([] { $($t:tt)* }) => { $($t)* };
+  ([$n:literal] { $($t:tt)* }) => {};
+}
+
 #[macro_export]
 macro_rules! shader_attr_by_type {
-  (struct $s:ident { $( $k:ident : $v:ident)* }) => {
-    #[derive(Default, Debug)]
+  (struct $s:ident { $( $k:ident : $v:ident $([$n:literal])? )* }) => {
+    #[derive(Debug)]
     #[repr(C)]
     pub struct $s {
-      $(pub $k : $v,)*
+      $(pub $k : $crate::shader_attr_field_type!($v $([$n])?),)*
+    }
+    impl Default for $s {
+      fn default() -> Self {
+        Self {
+          $($k : $crate::shader_attr_field_default!($v $([$n])?),)*
+        }
+      }
     }
 
     #[allow(unused_variables)]
@@ -19,7 +53,7 @@ macro_rules! shader_attr_by_type {
       pub fn ub_code() -> &'static str {
         concat!(
           "layout (std140) uniform ", stringify!($s), " {\n",
-            $("  ", stringify!($v) ," ", stringify!($k), ";\n",)*
+            $("  ", stringify!($v) ," ", stringify!($k), $("[", stringify!($n), "]",)? ";\n",)*
           "};"
         )
       }
@@ -48,8 +82,18 @@ macro_rules! shader_attr_by_type {
         concat!($("in ", stringify!($v) ," ", stringify!($k), ";\n",)*)
       }
       #[allow(dead_code)]
-      pub fn fs_out_code() -> &'static str {
-        concat!($("out ", stringify!($v) ," ", stringify!($k), ";\n",)*)
+      // 複数出力するときは location の指定が必要
+      #[allow(unused_assignments)]
+      pub fn fs_out_code() -> String {
+        let mut result = String::new();
+        let mut location = 0;
+        $(
+          result += &format!(
+            "layout (location = {}) out {} {};\n", location, stringify!($v), stringify!($k)
+          );
+          location += 1;
+        )*
+        result
       }
       #[allow(dead_code)]
       pub fn struct_size() -> usize {
@@ -60,10 +104,10 @@ macro_rules! shader_attr_by_type {
         let mut result = Vec::new();
         let dummy = ::core::mem::MaybeUninit::<Self>::uninit();
         let dummy_ptr = dummy.as_ptr();
-        $(
+        $($crate::shader_attr_if_scalar!([$($n)?] {
           let member_ptr = unsafe{ ::core::ptr::addr_of!((*dummy_ptr).$k) };
           result.push(member_ptr as usize - dummy_ptr as usize);
-        )*
+        });)*
         result
       }
       #[allow(dead_code)]
@@ -72,7 +116,7 @@ macro_rules! shader_attr_by_type {
       #[allow(unused_variables)]
       pub fn keys_static() -> Vec<&'static str> {
         let mut result = Vec::new();
-        $(result.push(stringify!($k));)*
+        $($crate::shader_attr_if_scalar!([$($n)?] { result.push(stringify!($k)); });)*
         result
       }
     }
@@ -95,34 +139,41 @@ macro_rules! shader_attr_by_type {
       #[allow(unused_mut)]
       fn values(&self) -> Vec<$crate::prgl::ShaderPrimitiveType> {
         let mut result = Vec::new();
-        $(result.push($crate::prgl::ShaderPrimitiveType::$v(self.$k));)*
+        $($crate::shader_attr_if_scalar!([$($n)?] {
+          result.push($crate::prgl::ShaderPrimitiveType::$v(self.$k));
+        });)*
         result
       }
       fn name(&self) -> &'static str { Self::name_static() }
+      #[allow(unused_variables)]
       fn find(&self, key: &str) -> Option<$crate::prgl::ShaderPrimitiveType> {
-        match key {
-          $(stringify!($k) => Some($crate::prgl::ShaderPrimitiveType::$v(self.$k)),)*
-          _ => None,
-        }
+        $($crate::shader_attr_if_scalar!([$($n)?] {
+          if key == stringify!($k) {
+            return Some($crate::prgl::ShaderPrimitiveType::$v(self.$k));
+          }
+        });)*
+        None
       }
       #[allow(unused_variables)]
       #[allow(unused_mut)]
       fn from_hashmap(&mut self, map: &::std::collections::HashMap<String, $crate::prgl::ShaderPrimitiveType>) -> Vec<&'static str> {
         let mut ignored = Vec::new();
-        $(
+        $($crate::shader_attr_if_scalar!([$($n)?] {
           if let Some($crate::prgl::ShaderPrimitiveType::$v(v)) = map.get(stringify!($k)) {
             self.$k = *v;
           } else {
             ignored.push(stringify!($k));
           }
-        )*
+        });)*
         ignored
       }
       #[allow(unused_variables)]
       #[allow(unused_mut)]
       fn to_hashmap(&self) -> ::std::collections::HashMap<String, $crate::prgl::ShaderPrimitiveType> {
         let mut result = ::std::collections::HashMap::new();
-        $(result.insert(String::from(stringify!($k)), $crate::prgl::ShaderPrimitiveType::$v(self.$k));)*
+        $($crate::shader_attr_if_scalar!([$($n)?] {
+          result.insert(String::from(stringify!($k)), $crate::prgl::ShaderPrimitiveType::$v(self.$k));
+        });)*
         result
       }
     }
@@ -212,8 +263,8 @@ macro_rules! shader_attr_by_type {
 
 #[macro_export]
 macro_rules! shader_attr {
-  ($( $type:ident $s:ident { $( $k:ident : $v:ident $(,)?)* } $(;)?)*) => (
-    $(shader_attr_by_type!{ $type $s { $( $k : $v )* } })*
+  ($( $type:ident $s:ident { $( $k:ident : $v:ident $([$n:literal])? $(,)?)* } $(;)?)*) => (
+    $(shader_attr_by_type!{ $type $s { $( $k : $v $([$n])? )* } })*
   );
 }
 #[macro_export]
@@ -374,7 +425,7 @@ macro_rules! shader_template {
       precision_float: &'static str,
       vs_attr: &'static str,
       fs_attr: (&'static str, &'static str), // -> vs_out_code, fs_in_code
-      out_attr : &'static str, // -> fs_out_code
+      out_attr : String, // -> fs_out_code
       attrs: (String, Vec<&'static str>, Vec<&'static str>), // -> concat!(ub_code*), uniforms, textures)
       vs_code: String,
       fs_code: String,
@@ -385,9 +436,15 @@ macro_rules! shader_template {
     $(
       template.$k = $crate::shader_template_element!($k: $v);
     )*
+    // fragment shader の int は既定が mediump なので uint の id などが溢れないように highp にする
+    // sampler2D / samplerCube 以外の sampler には既定の精度がないので書いておく
     let common = format!(
-      "#version {} es\nprecision {} float;\n",
-      template.version, template.precision_float
+      "#version {} es\nprecision {} float;\n{}",
+      template.version,
+      template.precision_float,
+      "precision highp int;\nprecision highp sampler2DShadow;\nprecision highp sampler2DArray;\n\
+       precision highp sampler2DArrayShadow;\nprecision highp samplerCubeShadow;\n\
+       precision highp sampler3D;\n"
     );
     let mut result = $crate::prgl::ShaderTemplate::new(
       template.attrs.1,
@@ -4,9 +4,15 @@ pub struct Texture {
   raw_texture: RawTexture,
 }
 pub type Texture2dDescriptor = RawTexture2dDescriptor;
+pub type TextureDescriptor = RawTextureDescriptor;
 pub type PixelFormat = RawPixelFormat;
 impl Texture {
-  pub fn new_rgba_map<F: Fn(f32, f32) -> Vec4>(width: usize, height: usize, color_fn: F) -> Self {
+  // 生成系は大きさが GPU の上限を超えていれば None
+  pub fn new_rgba_map<F: Fn(f32, f32) -> Vec4>(
+    width: usize,
+    height: usize,
+    color_fn: F,
+  ) -> Option<Self> {
     let size = width * height * 4;
     let mut data: Vec<u8> = vec![0; size];
     fn clamp(x: f32) -> u8 {
@@ -43,49 +49,126 @@ impl Texture {
       data.as_slice(),
     )
   }
-  pub fn new_bytes(desc: &Texture2dDescriptor, data: &[u8]) -> Self {
+  pub fn new_bytes(desc: &Texture2dDescriptor, data: &[u8]) -> Option<Self> {
     Self::new_impl(desc, TextureWriteType::u8(data))
   }
-  pub fn new_floats(desc: &Texture2dDescriptor, data: &[f32]) -> Self {
+  pub fn new_floats(desc: &Texture2dDescriptor, data: &[f32]) -> Option<Self> {
     Self::new_impl(desc, TextureWriteType::f32(data))
   }
-  pub fn new_uninitialized(desc: &Texture2dDescriptor) -> Self {
+  pub fn new_uninitialized(desc: &Texture2dDescriptor) -> Option<Self> {
     Self::new_impl(desc, TextureWriteType::Uninitialized)
   }
-  pub fn new_fill_zero(desc: &Texture2dDescriptor) -> Self {
+  pub fn new_fill_zero(desc: &Texture2dDescriptor) -> Option<Self> {
     Self::new_impl(desc, TextureWriteType::Zero)
   }
-  pub fn new_fill_one(desc: &Texture2dDescriptor) -> Self {
+  pub fn new_fill_one(desc: &Texture2dDescriptor) -> Option<Self> {
     Self::new_impl(desc, TextureWriteType::One)
   }
-  pub fn new_image_bitmap(desc: &Texture2dDescriptor, data: &web_sys::ImageBitmap) -> Self {
+  pub fn new_image_bitmap(desc: &Texture2dDescriptor, data: &web_sys::ImageBitmap) -> Option<Self> {
     Self::new_impl(desc, TextureWriteType::ImageBitmap(data))
   }
-  pub fn new_image_data(desc: &Texture2dDescriptor, data: &web_sys::ImageData) -> Self {
+  pub fn new_image_data(desc: &Texture2dDescriptor, data: &web_sys::ImageData) -> Option<Self> {
     Self::new_impl(desc, TextureWriteType::ImageData(data))
   }
   pub fn new_html_image_element(
     desc: &Texture2dDescriptor,
     data: &web_sys::HtmlImageElement,
-  ) -> Self {
+  ) -> Option<Self> {
     Self::new_impl(desc, TextureWriteType::HtmlImageElement(data))
   }
   pub fn new_html_canvas_element(
     desc: &Texture2dDescriptor,
     data: &web_sys::HtmlCanvasElement,
-  ) -> Self {
+  ) -> Option<Self> {
     Self::new_impl(desc, TextureWriteType::HtmlCanvasElement(data))
   }
   pub fn new_html_video_element(
     desc: &Texture2dDescriptor,
     data: &web_sys::HtmlVideoElement,
-  ) -> Self {
+  ) -> Option<Self> {
     Self::new_impl(desc, TextureWriteType::HtmlVideoElement(data))
   }
+  // mip level ごとの中身から作る. 圧縮形式で拡張がなければ None
+  pub fn new_levels(desc: &Texture2dDescriptor, levels: Vec<Vec<u8>>) -> Option<Self> {
+    if !desc.format.is_supported() {
+      log::error(format!("{:?} is not supported on this device", desc.format));
+      return None;
+    }
+    if levels.is_empty() {
+      log::error("no texture levels");
+      return None;
+    }
+    for (level, data) in levels.iter().enumerate() {
+      let width = (desc.width >> level).max(1);
+      let height = (desc.height >> level).max(1);
+      let expected = desc.format.data_size(width, height);
+      if data.len() != expected {
+        log::error(format!(
+          "invalid texture level size: level:{}, size:{}, expected:{}",
+          level,
+          data.len(),
+          expected
+        ));
+        return None;
+      }
+    }
+    Some(Self {
+      raw_texture: RawTexture::new_levels(desc, levels)?,
+    })
+  }
+  // KTX2 (supercompression なし, 2D のみ)
+  pub fn new_ktx2(data: &[u8]) -> Option<Self> {
+    let ktx2 = match prpr::ktx2::Ktx2::parse(data) {
+      Ok(ktx2) => ktx2,
+      Err(e) => {
+        log::error(format!("failed to parse ktx2: {:?}", e));
+        return None;
+      }
+    };
+    if !ktx2.is_2d() {
+      log::error("currently only 2d ktx2 is supported");
+      return None;
+    }
+    let format = match PixelFormat::from_vk_format(ktx2.vk_format) {
+      Some(format) => format,
+      None => {
+        log::error(format!("not supported ktx2 vkFormat {}", ktx2.vk_format));
+        return None;
+      }
+    };
+    let desc = Texture2dDescriptor {
+      width: ktx2.pixel_width,
+      height: ktx2.pixel_height,
+      format,
+      mipmap: ktx2.need_generate_mipmap,
+    };
+    Self::new_levels(&desc, ktx2.levels)
+  }
+  // 端末ごとに別の形式で用意した KTX2 から最初に使えるものを選ぶ
+  pub fn new_ktx2_from_candidates(candidates: &[&[u8]]) -> Option<Self> {
+    for data in candidates {
+      if let Ok(ktx2) = prpr::ktx2::Ktx2::parse(data) {
+        let supported = PixelFormat::from_vk_format(ktx2.vk_format)
+          .map(|x| x.is_supported())
+          .unwrap_or(false);
+        if supported {
+          return Self::new_ktx2(data);
+        }
+      }
+    }
+    log::error("no supported ktx2 in candidates");
+    None
+  }
+  // cubemap / 2d array など
+  pub fn new_layered_uninitialized(desc: &TextureDescriptor) -> Option<Self> {
+    Some(Self {
+      raw_texture: RawTexture::new_layered(desc)?,
+    })
+  }
+  // context lost 後に作り直すときにも使われる
   pub fn apply_sampler(&mut self, sampler: &Sampler) {
-    self.raw_texture().bind();
+    self.raw_texture.apply_sampler(sampler);
     let target = self.raw_texture.target();
-    sampler.apply(target);
     if SET_BIND_NONE_AFTER_WORK {
       let ctx = Instance::ctx();
       ctx.bind_texture(target, None);
@@ -103,19 +186,26 @@ impl Texture {
   pub fn target(&self) -> u32 {
     self.raw_texture.target()
   }
+  pub fn mip_levels(&self) -> usize {
+    self.raw_texture.mip_levels()
+  }
   pub fn format(&self) -> PixelFormat {
     self.raw_texture.desc().format
   }
   pub fn channels(&self) -> usize {
     self.raw_texture.channels()
   }
+  // 描画先の浮動小数点 format が使えず 8bit に落とされた
+  pub fn is_format_downgraded(&self) -> bool {
+    self.raw_texture.is_format_downgraded()
+  }
   pub fn raw_texture(&self) -> &RawTexture {
     &self.raw_texture
   }
-  fn new_impl<'a>(desc: &Texture2dDescriptor, write_type: TextureWriteType<'a>) -> Self {
-    Self {
-      raw_texture: RawTexture::new(desc, write_type),
-    }
+  fn new_impl<'a>(desc: &Texture2dDescriptor, write_type: TextureWriteType<'a>) -> Option<Self> {
+    Some(Self {
+      raw_texture: RawTexture::new(desc, write_type)?,
+    })
   }
 }
 
@@ -155,7 +245,8 @@ impl<T: TextureMappingAttribute> TextureMappingTrait for TextureMapping<T> {
       for i in 0..self.keys.len() {
         if let Some(utl) = shader.uniform_texture_location(self.keys[i]) {
           match &values[i] {
-            ShaderSamplerType::sampler2D(texture) => {
+            ShaderSamplerType::sampler2D(texture)
+            | ShaderSamplerType::sampler2DArrayShadow(texture) => {
               cmd.set_uniform_texture(texture.read().raw_texture(), &utl);
             }
           }
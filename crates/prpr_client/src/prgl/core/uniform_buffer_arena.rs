@@ -0,0 +1,158 @@
+use super::*;
+use std::collections::HashMap;
+use std::ops::Range;
+// 小さな UniformBuffer は大きなバッファから切り出して使う
+// - Transform 1つごとに RawBuffer を作ると数千個になるので
+// - 書き込みは CPU 側のコピーにためておき、bind 時にページごとにまとめて転送する
+const ARENA_PAGE_SIZE: usize = 65536;
+
+struct UniformBufferArenaPage {
+  raw_buffer: RawBuffer,
+  shadow: Vec<u8>,
+  used: usize,
+  dirty: Option<Range<usize>>,
+}
+impl UniformBufferArenaPage {
+  fn new(size: usize) -> Self {
+    Self {
+      raw_buffer: RawBuffer::new_uninitialized_untyped(size as i32, BufferUsage::Uniform),
+      shadow: vec![0; size],
+      used: 0,
+      dirty: None,
+    }
+  }
+  fn write(&mut self, offset: usize, data: &[u8]) {
+    let range = offset..offset + data.len();
+    self.shadow[range.clone()].copy_from_slice(data);
+    self.dirty = Some(match &self.dirty {
+      Some(dirty) => dirty.start.min(range.start)..dirty.end.max(range.end),
+      None => range,
+    });
+  }
+  fn flush(&mut self) {
+    // context lost 後は shadow から全て書き直す
+    if self.raw_buffer.restore() {
+      self.dirty = Some(0..self.used);
+    }
+    if let Some(dirty) = self.dirty.take() {
+      self
+        .raw_buffer
+        .write_untyped(dirty.start as i32, &self.shadow[dirty]);
+    }
+  }
+}
+
+// Drop されるとアリーナに返却される
+pub struct UniformBufferSlice {
+  page: usize,
+  offset: usize,
+  size: usize,
+}
+impl UniformBufferSlice {
+  pub fn new(size: usize) -> Self {
+    UniformBufferArenaImpl::write_global().allocate(size)
+  }
+  pub fn write(&self, data: &[u8]) {
+    UniformBufferArenaImpl::write_global().write(self, data);
+  }
+  pub fn bind(&self, cmd: &mut Command, index: u32) {
+    UniformBufferArenaImpl::write_global().bind(self, cmd, index);
+  }
+  pub fn size(&self) -> usize {
+    self.size
+  }
+}
+impl Drop for UniformBufferSlice {
+  fn drop(&mut self) {
+    UniformBufferArenaImpl::write_global().free(self);
+  }
+}
+
+static INSTANCE: OnceCell<MRwLock<UniformBufferArenaImpl>> = OnceCell::new();
+unsafe impl Send for UniformBufferArenaImpl {}
+unsafe impl Sync for UniformBufferArenaImpl {}
+pub struct UniformBufferArenaImpl {
+  alignment: usize,
+  pages: Vec<UniformBufferArenaPage>,
+  // aligned size => (page, offset)
+  free_slices: HashMap<usize, Vec<(usize, usize)>>,
+}
+impl UniformBufferArenaImpl {
+  pub fn initialize_global() {
+    INSTANCE.set(MRwLock::new(Self::new())).ok();
+  }
+  pub fn write_global() -> MDerefMutable<'static, Self> {
+    INSTANCE
+      .get()
+      .expect("UniformBufferArena global not initialized")
+      .write()
+  }
+  fn new() -> Self {
+    Self {
+      alignment: Instance::capabilities().uniform_buffer_offset_alignment,
+      pages: Vec::new(),
+      free_slices: HashMap::new(),
+    }
+  }
+  fn aligned_size(&self, size: usize) -> usize {
+    size.max(1).div_ceil(self.alignment) * self.alignment
+  }
+  fn allocate(&mut self, size: usize) -> UniformBufferSlice {
+    let max_size = Instance::capabilities().max_uniform_block_size;
+    if size > max_size {
+      log::error(format!(
+        "too large uniform buffer: size:{}, max:{}",
+        size, max_size
+      ));
+    }
+    let aligned_size = self.aligned_size(size);
+    if let Some(free_slices) = self.free_slices.get_mut(&aligned_size) {
+      if let Some((page, offset)) = free_slices.pop() {
+        return UniformBufferSlice { page, offset, size };
+      }
+    }
+    let has_space = self
+      .pages
+      .last()
+      .map(|page| page.used + aligned_size <= page.shadow.len())
+      .unwrap_or(false);
+    if !has_space {
+      self.pages.push(UniformBufferArenaPage::new(
+        ARENA_PAGE_SIZE.max(aligned_size),
+      ));
+    }
+    let page = self.pages.len() - 1;
+    let offset = self.pages[page].used;
+    self.pages[page].used += aligned_size;
+    UniformBufferSlice { page, offset, size }
+  }
+  fn free(&mut self, slice: &UniformBufferSlice) {
+    let aligned_size = self.aligned_size(slice.size);
+    self
+      .free_slices
+      .entry(aligned_size)
+      .or_default()
+      .push((slice.page, slice.offset));
+  }
+  fn write(&mut self, slice: &UniformBufferSlice, data: &[u8]) {
+    if data.len() > slice.size {
+      log::error(format!(
+        "invalid uniform buffer write size: size:{}, reserved:{}",
+        data.len(),
+        slice.size
+      ));
+      return;
+    }
+    self.pages[slice.page].write(slice.offset, data);
+  }
+  fn bind(&mut self, slice: &UniformBufferSlice, cmd: &mut Command, index: u32) {
+    let page = &mut self.pages[slice.page];
+    page.flush();
+    cmd.set_ubo_range(
+      &page.raw_buffer,
+      index,
+      slice.offset as i32,
+      slice.size as i32,
+    );
+  }
+}
@@ -1,26 +1,36 @@
 use super::*;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
-pub struct Vao<T: BufferAttribute> {
+use std::hash::{Hash, Hasher};
+// (shader_id, vertex buffer_id, streams の buffer_id の hash, index buffer_id)
+// bind のたびに確保しないよう streams は hash にして, 値の側に持つ buffer_id の列と照合する
+type RawVaoKey = (u64, u64, u64, Option<u64>);
+pub struct Vao<T: BufferAttribute, I: IndexElement = IndexBufferType> {
   v_buffer: VertexBuffer<T>,
-  i_buffer: Option<IndexBuffer>,
-  shader_id_to_raw_vao: SRwLock<HashMap<u64, RawVao>>,
+  // v_buffer と同じ頂点数の追加の属性
+  streams: Vec<Box<dyn VertexStreamTrait>>,
+  i_buffer: Option<IndexBuffer<I>>,
+  raw_vaos: SRwLock<HashMap<RawVaoKey, (Vec<u64>, RawVao)>>,
 }
 pub trait VaoTrait {
   fn bind(&self, cmd: &mut Command);
+  fn draw_command(&self) -> DrawCommand;
 }
-impl<T: BufferAttribute> Vao<T> {
-  pub fn new(v_buffer: VertexBuffer<T>, i_buffer: IndexBuffer) -> Self {
+impl<T: BufferAttribute, I: IndexElement> Vao<T, I> {
+  pub fn new(v_buffer: VertexBuffer<T>, i_buffer: IndexBuffer<I>) -> Self {
     Self {
       v_buffer,
+      streams: Vec::new(),
       i_buffer: Some(i_buffer),
-      shader_id_to_raw_vao: SRwLock::new(HashMap::new()),
+      raw_vaos: SRwLock::new(HashMap::new()),
     }
   }
   pub fn new_without_index_buffer(v_buffer: VertexBuffer<T>) -> Self {
     Self {
       v_buffer,
+      streams: Vec::new(),
       i_buffer: None,
-      shader_id_to_raw_vao: SRwLock::new(HashMap::new()),
+      raw_vaos: SRwLock::new(HashMap::new()),
     }
   }
   pub fn draw_command(&self) -> DrawCommand {
@@ -28,6 +38,7 @@ impl<T: BufferAttribute> Vao<T> {
       DrawCommand::DrawIndexed {
         first: 0,
         count: i_buffer.len() as i32,
+        format: i_buffer.format(),
       }
     } else {
       DrawCommand::Draw {
@@ -37,34 +48,124 @@ impl<T: BufferAttribute> Vao<T> {
     }
   }
   // pub fn draw_instanced_command() -> DrawCommand {}
+  pub fn v_buffer(&self) -> &VertexBuffer<T> {
+    &self.v_buffer
+  }
+  pub fn v_buffer_mut(&mut self) -> &mut VertexBuffer<T> {
+    &mut self.v_buffer
+  }
+  pub fn add_stream<U: BufferAttribute + 'static>(&mut self, stream: VertexBuffer<U>) {
+    if stream.len() != self.v_buffer.len() {
+      log::error(format!(
+        "vertex stream length mismatch: {} (expected: {})",
+        stream.len(),
+        self.v_buffer.len()
+      ));
+    }
+    self.streams.push(Box::new(stream));
+  }
+  pub fn clear_streams(&mut self) {
+    self.streams.clear();
+  }
+  pub fn streams(&self) -> &[Box<dyn VertexStreamTrait>] {
+    &self.streams
+  }
+  pub fn i_buffer(&self) -> Option<&IndexBuffer<I>> {
+    self.i_buffer.as_ref()
+  }
+  pub fn i_buffer_mut(&mut self) -> Option<&mut IndexBuffer<I>> {
+    self.i_buffer.as_mut()
+  }
 }
-impl<T: BufferAttribute> VaoTrait for Vao<T> {
+impl<T: BufferAttribute, I: IndexElement> VaoTrait for Vao<T, I> {
   fn bind(&self, cmd: &mut Command) {
     if let Some(shader) = cmd.current_shader() {
-      let id = shader.id();
-      let mut lock = self.shader_id_to_raw_vao.write();
-      if let Some(raw_vao) = lock.get(&id) {
-        cmd.set_vao(&raw_vao);
-        return;
+      self.v_buffer.flush();
+      for stream in &self.streams {
+        stream.flush();
+      }
+      if let Some(i_buffer) = &self.i_buffer {
+        i_buffer.flush();
+      }
+      let stream_ids = || self.streams.iter().map(|x| x.raw_buffer().buffer_id());
+      let mut hasher = DefaultHasher::new();
+      stream_ids().for_each(|id| id.hash(&mut hasher));
+      let key = (
+        shader.id(),
+        self.v_buffer.raw_buffer().buffer_id(),
+        hasher.finish(),
+        self.i_buffer.as_ref().map(|x| x.raw_buffer().buffer_id()),
+      );
+      let mut lock = self.raw_vaos.write();
+      // context lost 後はバッファも作り直されているので全て捨てる
+      if lock.values().any(|(_, x)| x.is_lost()) {
+        lock.clear();
+      }
+      if let Some((ids, raw_vao)) = lock.get(&key) {
+        if ids.iter().copied().eq(stream_ids()) {
+          cmd.set_vao(raw_vao);
+          return;
+        }
       }
+      // 再確保された古いバッファを参照しているものは捨てる
+      lock.retain(|k, (ids, _)| {
+        let i_alive = match (&self.i_buffer, k.3) {
+          (Some(i_buffer), Some(id)) => i_buffer.contains_buffer_id(id),
+          _ => true,
+        };
+        let v_alive = ids.len() == self.streams.len()
+          && self.v_buffer.contains_buffer_id(k.1)
+          && self
+            .streams
+            .iter()
+            .zip(ids.iter())
+            .all(|(stream, id)| stream.contains_buffer_id(*id));
+        v_alive && i_alive
+      });
+      let v_buffer = self.v_buffer.raw_buffer();
+      let streams: Vec<_> = self.streams.iter().map(|x| x.raw_buffer()).collect();
       let i_buffer = self.i_buffer.as_ref().map(|x| x.raw_buffer());
+      let mut vs_in_template_buffers = vec![(self.v_buffer.template(), &*v_buffer)];
+      for (stream, raw_buffer) in self.streams.iter().zip(&streams) {
+        vs_in_template_buffers.push((stream.template(), &**raw_buffer));
+      }
       let raw_vao = RawVao::new(
-        shader.raw_program().raw_program(),
-        Some((self.v_buffer.template(), self.v_buffer.raw_buffer())),
-        i_buffer,
+        &shader.raw_program().raw_program(),
+        &vs_in_template_buffers,
+        i_buffer.as_deref(),
       );
       cmd.set_vao(&raw_vao);
-      lock.insert(id, raw_vao);
+      let ids = streams.iter().map(|x| x.buffer_id()).collect();
+      lock.insert(key, (ids, raw_vao));
     }
   }
+  fn draw_command(&self) -> DrawCommand {
+    Vao::draw_command(self)
+  }
 }
-impl<T: BufferAttribute> VaoTrait for SOwner<Vao<T>> {
+impl<T: BufferAttribute, I: IndexElement> VaoTrait for SOwner<Vao<T, I>> {
   fn bind(&self, cmd: &mut Command) {
     self.read().bind(cmd);
   }
+  fn draw_command(&self) -> DrawCommand {
+    self.read().draw_command()
+  }
 }
-impl<T: BufferAttribute> VaoTrait for SReader<Vao<T>> {
+impl<T: BufferAttribute, I: IndexElement> VaoTrait for SReader<Vao<T, I>> {
   fn bind(&self, cmd: &mut Command) {
     self.read().bind(cmd);
   }
+  fn draw_command(&self) -> DrawCommand {
+    self.read().draw_command()
+  }
+}
+impl<T: BufferAttribute + 'static, I: IndexElement> PipelineBindable for SOwner<Vao<T, I>> {
+  fn bind_pipeline(&self, pipeline: &mut Pipeline) {
+    pipeline.set_draw_vao(self);
+  }
+}
+impl<T: BufferAttribute + 'static, I: IndexElement> PipelineBindable for SReader<Vao<T, I>> {
+  fn bind_pipeline(&self, pipeline: &mut Pipeline) {
+    pipeline.set_draw_vao(self);
+  }
 }
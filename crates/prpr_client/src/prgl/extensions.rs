@@ -0,0 +1,96 @@
+use super::*;
+use std::collections::HashMap;
+
+// 使う可能性のある WebGL2 拡張. getExtension するまでは有効にならない
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Extension {
+  ColorBufferFloat,
+  ColorBufferHalfFloat,
+  FloatBlend,
+  TextureFloatLinear,
+  TextureFilterAnisotropic,
+  DisjointTimerQuery,
+  CompressedTextureS3tc,
+  CompressedTextureS3tcSrgb,
+  CompressedTextureEtc,
+  CompressedTextureEtc1,
+  CompressedTextureAstc,
+  CompressedTextureBptc,
+  CompressedTextureRgtc,
+  CompressedTexturePvrtc,
+}
+impl Extension {
+  pub fn name(&self) -> &'static str {
+    match self {
+      Self::ColorBufferFloat => "EXT_color_buffer_float",
+      Self::ColorBufferHalfFloat => "EXT_color_buffer_half_float",
+      Self::FloatBlend => "EXT_float_blend",
+      Self::TextureFloatLinear => "OES_texture_float_linear",
+      Self::TextureFilterAnisotropic => "EXT_texture_filter_anisotropic",
+      Self::DisjointTimerQuery => "EXT_disjoint_timer_query_webgl2",
+      Self::CompressedTextureS3tc => "WEBGL_compressed_texture_s3tc",
+      Self::CompressedTextureS3tcSrgb => "WEBGL_compressed_texture_s3tc_srgb",
+      Self::CompressedTextureEtc => "WEBGL_compressed_texture_etc",
+      Self::CompressedTextureEtc1 => "WEBGL_compressed_texture_etc1",
+      Self::CompressedTextureAstc => "WEBGL_compressed_texture_astc",
+      Self::CompressedTextureBptc => "EXT_texture_compression_bptc",
+      Self::CompressedTextureRgtc => "EXT_texture_compression_rgtc",
+      Self::CompressedTexturePvrtc => "WEBGL_compressed_texture_pvrtc",
+    }
+  }
+}
+// EXT_texture_filter_anisotropic
+pub const TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FE;
+pub const MAX_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FF;
+
+// 対応している拡張の一覧と, 有効にした拡張オブジェクト
+pub struct Extensions {
+  supported: Vec<String>,
+  enabled: HashMap<Extension, js_sys::Object>,
+}
+impl Extensions {
+  pub(super) fn new(ctx: &web_sys::WebGl2RenderingContext) -> Self {
+    let supported = ctx
+      .get_supported_extensions()
+      .map(|names| names.iter().filter_map(|name| name.as_string()).collect())
+      .unwrap_or_default();
+    Self {
+      supported,
+      enabled: HashMap::new(),
+    }
+  }
+  pub fn is_supported(&self, extension: Extension) -> bool {
+    self.supported.iter().any(|name| name == extension.name())
+  }
+  pub fn is_enabled(&self, extension: Extension) -> bool {
+    self.enabled.contains_key(&extension)
+  }
+  pub(super) fn enable(
+    &mut self,
+    ctx: &web_sys::WebGl2RenderingContext,
+    extension: Extension,
+  ) -> Option<js_sys::Object> {
+    if let Some(object) = self.enabled.get(&extension) {
+      return Some(object.clone());
+    }
+    if !self.is_supported(extension) {
+      return None;
+    }
+    let object = ctx.get_extension(extension.name()).ok().flatten()?;
+    self.enabled.insert(extension, object.clone());
+    Some(object)
+  }
+  // context が restore されると拡張は無効に戻るので有効にし直す
+  pub(super) fn reenable(&mut self, ctx: &web_sys::WebGl2RenderingContext) {
+    let extensions: Vec<Extension> = self.enabled.keys().copied().collect();
+    self.enabled.clear();
+    for extension in extensions {
+      if self.enable(ctx, extension).is_none() {
+        log::error(format!(
+          "failed to enable {} after context restored",
+          extension.name()
+        ));
+      }
+    }
+  }
+}
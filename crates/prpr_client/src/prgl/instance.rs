@@ -1,23 +1,145 @@
 use super::*;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
 
 static INSTANCE: OnceCell<Instance> = OnceCell::new();
 unsafe impl Send for Instance {}
 unsafe impl Sync for Instance {}
 
+// webglcontextlost / restored の状態
+struct ContextState {
+  is_lost: bool,
+  // restore されるたびに増える. Raw* は作られたときの値と比べて無効か判断する
+  generation: u64,
+  need_restore_hooks: bool,
+  restore_hooks: Vec<Box<dyn FnMut()>>,
+  // bind 中の VAO. getParameter は遅いので自前で覚えておく
+  bound_vao: Option<web_sys::WebGlVertexArrayObject>,
+}
+
 pub struct Instance {
   ctx: web_sys::WebGl2RenderingContext,
+  capabilities: Capabilities,
+  extensions: SRwLock<Extensions>,
+  state: SRwLock<ContextState>,
 }
 impl Instance {
   pub fn ctx() -> &'static web_sys::WebGl2RenderingContext {
-    &INSTANCE
-      .get()
-      .expect("prgl::Instance is not initialized")
-      .ctx
+    &Self::get().ctx
+  }
+  pub fn capabilities() -> &'static Capabilities {
+    &Self::get().capabilities
+  }
+  fn get() -> &'static Self {
+    INSTANCE.get().expect("prgl::Instance is not initialized")
   }
   pub fn set(ctx: web_sys::WebGl2RenderingContext) {
-    INSTANCE.set(Self { ctx }).ok();
+    let canvas = ctx
+      .canvas()
+      .and_then(|canvas| canvas.dyn_into::<web_sys::HtmlCanvasElement>().ok());
+    let mut extensions = Extensions::new(&ctx);
+    let capabilities = Capabilities::query(&ctx, &mut extensions);
+    INSTANCE
+      .set(Self {
+        ctx,
+        capabilities,
+        extensions: SRwLock::new(extensions),
+        state: SRwLock::new(ContextState {
+          is_lost: false,
+          generation: 0,
+          need_restore_hooks: false,
+          restore_hooks: Vec::new(),
+          bound_vao: None,
+        }),
+      })
+      .ok();
+    if let Some(canvas) = canvas {
+      Self::setup_context_events(&canvas);
+    }
+  }
+  fn setup_context_events(canvas: &web_sys::HtmlCanvasElement) {
+    let on_lost = Closure::wrap(Box::new(move |event: web_sys::Event| {
+      // preventDefault しないと restored が来ない
+      event.prevent_default();
+      log::error("webgl context lost");
+      Self::get().state.write().is_lost = true;
+    }) as Box<dyn FnMut(_)>);
+    let on_restored = Closure::wrap(Box::new(move |_: web_sys::Event| {
+      log::info("webgl context restored");
+      let instance = Self::get();
+      instance.extensions.write().reenable(&instance.ctx);
+      let mut state = instance.state.write();
+      state.is_lost = false;
+      state.generation += 1;
+      state.need_restore_hooks = true;
+      state.bound_vao = None;
+    }) as Box<dyn FnMut(_)>);
+    canvas
+      .add_event_listener_with_callback("webglcontextlost", on_lost.as_ref().unchecked_ref())
+      .ok();
+    canvas
+      .add_event_listener_with_callback(
+        "webglcontextrestored",
+        on_restored.as_ref().unchecked_ref(),
+      )
+      .ok();
+    on_lost.forget();
+    on_restored.forget();
+  }
+  // getSupportedExtensions に含まれるか. 有効にはしない
+  pub fn is_extension_supported(extension: Extension) -> bool {
+    Self::get().extensions.read().is_supported(extension)
+  }
+  // 未対応なら false. 有効にした拡張は context restore 後も有効にし直される
+  pub fn enable_extension(extension: Extension) -> bool {
+    Self::extension(extension).is_some()
+  }
+  // どれか一つでも有効にできれば true
+  pub fn enable_any_extension(extensions: &[Extension]) -> bool {
+    extensions.iter().any(|x| Self::enable_extension(*x))
+  }
+  // 拡張の定数や関数を使うときに. 必要なら有効にする
+  pub fn extension(extension: Extension) -> Option<js_sys::Object> {
+    let instance = Self::get();
+    instance.extensions.write().enable(&instance.ctx, extension)
   }
   pub fn flush() {
     Self::ctx().flush();
   }
+  pub fn is_context_lost() -> bool {
+    Self::get().state.read().is_lost
+  }
+  pub fn generation() -> u64 {
+    Self::get().state.read().generation
+  }
+  // VAO は必ずここから bind すること(bound_vertex_array がずれる)
+  pub fn bind_vertex_array(vao: Option<&web_sys::WebGlVertexArrayObject>) {
+    let instance = Self::get();
+    instance.ctx.bind_vertex_array(vao);
+    instance.state.write().bound_vao = vao.cloned();
+  }
+  pub fn bound_vertex_array() -> Option<web_sys::WebGlVertexArrayObject> {
+    Self::get().state.read().bound_vao.clone()
+  }
+  // 自動で作り直せないもの(自前で持っている WebGl オブジェクトなど)は restore 後にここで作り直す
+  pub fn add_restore_hook(hook: Box<dyn FnMut()>) {
+    Self::get().state.write().restore_hooks.push(hook);
+  }
+  // restore 後最初の描画の前に呼ぶ
+  pub fn process_restore() {
+    let mut hooks = {
+      let mut state = Self::get().state.write();
+      if !state.need_restore_hooks {
+        return;
+      }
+      state.need_restore_hooks = false;
+      std::mem::take(&mut state.restore_hooks)
+    };
+    for hook in &mut hooks {
+      hook();
+    }
+    let mut state = Self::get().state.write();
+    hooks.append(&mut state.restore_hooks);
+    state.restore_hooks = hooks;
+  }
 }
@@ -5,6 +5,10 @@ mod recipe;
 pub use self::recipe::*;
 mod instance;
 pub use self::instance::*;
+mod capabilities;
+pub use self::capabilities::*;
+mod extensions;
+pub use self::extensions::*;
 
 use crate::system::log;
 pub use prpr::math::*;
@@ -3,3 +3,5 @@ mod buffer;
 pub use self::buffer::*;
 mod texture;
 pub use self::texture::*;
+mod texture_loader;
+pub use self::texture_loader::*;
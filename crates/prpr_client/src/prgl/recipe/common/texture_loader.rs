@@ -0,0 +1,196 @@
+use super::*;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+// fetch -> blob -> createImageBitmap -> upload の順に進む
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum LoadStage {
+  Fetch,
+  Blob,
+  Decode,
+  Loaded,
+  Failed,
+}
+type Resolved = SRc<SRwLock<Option<Result<JsValue, JsValue>>>>;
+type PromiseCallback = Closure<dyn FnMut(JsValue)>;
+
+// URL から画像を読み込むテクスチャ. 読み込みが終わるまでは dummy のテクスチャを返す
+// 毎フレーム update を呼ぶこと(Updater::own してもよい)
+pub struct TextureLoader {
+  // エラー表示用. url か mime type
+  label: String,
+  format: PixelFormat,
+  mipmap: bool,
+  sampler: Option<Sampler>,
+  texture: SOwner<Texture>,
+  stage: LoadStage,
+  resolved: Resolved,
+  // どの段階の Promise も同じ closure で受ける
+  closures: Option<(PromiseCallback, PromiseCallback)>,
+}
+impl TextureLoader {
+  pub fn new(url: &str) -> Self {
+    Self::new_with_format(url, PixelFormat::R8G8B8A8, true)
+  }
+  pub fn new_with_format(url: &str, format: PixelFormat, mipmap: bool) -> Self {
+    let result = Self::new_impl(url, format, mipmap, LoadStage::Fetch);
+    let promise = web_sys::window().unwrap().fetch_with_str(url);
+    result.wait(&promise);
+    result
+  }
+  // png / jpeg などのエンコードされたままのバイト列から. fetch は飛ばしてデコードから始める
+  pub fn new_encoded_bytes(
+    data: &[u8],
+    mime_type: &str,
+    format: PixelFormat,
+    mipmap: bool,
+  ) -> Self {
+    let mut result = Self::new_impl(mime_type, format, mipmap, LoadStage::Blob);
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(data));
+    let mut options = web_sys::BlobPropertyBag::new();
+    options.type_(mime_type);
+    match web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options) {
+      Ok(blob) => result.proceed(blob.into()),
+      Err(e) => result.fail(format!("{:?}", e)),
+    }
+    result
+  }
+  fn new_impl(label: &str, format: PixelFormat, mipmap: bool, stage: LoadStage) -> Self {
+    let resolved: Resolved = SRc::new(SRwLock::new(None));
+    let on_resolve = {
+      let resolved = resolved.clone();
+      Closure::wrap(Box::new(move |value: JsValue| {
+        *resolved.write() = Some(Ok(value));
+      }) as Box<dyn FnMut(_)>)
+    };
+    let on_reject = {
+      let resolved = resolved.clone();
+      Closure::wrap(Box::new(move |value: JsValue| {
+        *resolved.write() = Some(Err(value));
+      }) as Box<dyn FnMut(_)>)
+    };
+    Self {
+      label: label.to_string(),
+      format,
+      mipmap,
+      sampler: None,
+      texture: TextureRecipe::new_dummy(),
+      stage,
+      resolved,
+      closures: Some((on_resolve, on_reject)),
+    }
+  }
+  // 読み込み後のテクスチャに適用する
+  pub fn set_sampler(&mut self, sampler: Sampler) {
+    if self.is_loaded() {
+      self.texture.write().apply_sampler(&sampler);
+    }
+    self.sampler = Some(sampler);
+  }
+  pub fn texture(&self) -> SReader<Texture> {
+    self.texture.clone_reader()
+  }
+  pub fn is_loaded(&self) -> bool {
+    self.stage == LoadStage::Loaded
+  }
+  pub fn is_failed(&self) -> bool {
+    self.stage == LoadStage::Failed
+  }
+  fn is_loading(&self) -> bool {
+    !self.is_loaded() && !self.is_failed()
+  }
+  fn wait(&self, promise: &js_sys::Promise) {
+    if let Some((on_resolve, on_reject)) = &self.closures {
+      let _ = promise.then2(on_resolve, on_reject);
+    }
+  }
+  fn fail(&mut self, message: String) {
+    log::error(format!(
+      "failed to load texture {}: {}",
+      self.label, message
+    ));
+    self.stage = LoadStage::Failed;
+  }
+  fn proceed(&mut self, value: JsValue) {
+    match self.stage {
+      LoadStage::Fetch => {
+        let response = match value.dyn_into::<web_sys::Response>() {
+          Ok(response) => response,
+          Err(e) => return self.fail(format!("{:?}", e)),
+        };
+        if !response.ok() {
+          return self.fail(format!("status {}", response.status()));
+        }
+        match response.blob() {
+          Ok(promise) => {
+            self.stage = LoadStage::Blob;
+            self.wait(&promise);
+          }
+          Err(e) => self.fail(format!("{:?}", e)),
+        }
+      }
+      LoadStage::Blob => {
+        let blob = match value.dyn_into::<web_sys::Blob>() {
+          Ok(blob) => blob,
+          Err(e) => return self.fail(format!("{:?}", e)),
+        };
+        match web_sys::window()
+          .unwrap()
+          .create_image_bitmap_with_blob(&blob)
+        {
+          Ok(promise) => {
+            self.stage = LoadStage::Decode;
+            self.wait(&promise);
+          }
+          Err(e) => self.fail(format!("{:?}", e)),
+        }
+      }
+      LoadStage::Decode => {
+        let bitmap = match value.dyn_into::<web_sys::ImageBitmap>() {
+          Ok(bitmap) => bitmap,
+          Err(e) => return self.fail(format!("{:?}", e)),
+        };
+        // 中身を差し替えるので, SReader を持っている側はそのまま新しいテクスチャを使える
+        let texture = Texture::new_image_bitmap(
+          &Texture2dDescriptor {
+            width: bitmap.width() as usize,
+            height: bitmap.height() as usize,
+            format: self.format,
+            mipmap: self.mipmap,
+          },
+          &bitmap,
+        );
+        *self.texture.write() = match texture {
+          Some(texture) => texture,
+          None => return self.fail(String::from("texture exceeds the GPU limits")),
+        };
+        if let Some(sampler) = &self.sampler {
+          self.texture.write().apply_sampler(sampler);
+        }
+        self.stage = LoadStage::Loaded;
+      }
+      LoadStage::Loaded | LoadStage::Failed => {}
+    }
+  }
+}
+impl NeedUpdate for TextureLoader {
+  fn update(&mut self) {
+    if !self.is_loading() {
+      return;
+    }
+    let resolved = self.resolved.write().take();
+    match resolved {
+      Some(Ok(value)) => self.proceed(value),
+      Some(Err(e)) => self.fail(format!("{:?}", e)),
+      None => {}
+    }
+  }
+}
+impl Drop for TextureLoader {
+  fn drop(&mut self) {
+    // 読み込み中に捨てると後から closure が呼ばれるので残しておく
+    if self.is_loading() {
+      std::mem::forget(self.closures.take());
+    }
+  }
+}
@@ -0,0 +1,481 @@
+use super::*;
+use prpr::animation::AnimationClip;
+use prpr::gltf::*;
+use prpr::mesh::Mesh;
+use std::collections::HashMap;
+
+crate::shader_attr! {
+  struct GltfVertex {
+    position: vec3,
+    normal: vec3,
+    tangent: vec4,
+    uv: vec2,
+    color: vec4,
+    // joint の index を float で持つ(整数の頂点属性は使っていないので)
+    joints: vec4,
+    weights: vec4,
+  }
+}
+struct GltfDrawable {
+  vao: Box<dyn PipelineBindable>,
+  topology: PrimitiveToporogy,
+  // materials の index. 指定がなければ最後のデフォルトのもの
+  material: usize,
+}
+pub struct GltfObject {
+  pub name: String,
+  // skin のあるものは joint の行列で動かすので単位行列
+  pub transform: Transform,
+  mesh: usize,
+  skin: Option<usize>,
+  // morphs の index. mesh に morph target がなければ None
+  morph: Option<usize>,
+}
+// glTF を GPU に載せたもの. テクスチャは非同期に読まれるので毎フレーム update を呼ぶこと
+pub struct GltfModel {
+  meshes: Vec<Vec<GltfDrawable>>,
+  materials: Vec<PbrMaterial>,
+  material_sources: Vec<GltfMaterial>,
+  objects: Vec<GltfObject>,
+  loaders: Vec<TextureLoader>,
+  // 読み込みが終わったら差し替える (materials の index, loaders の index, map)
+  // それまでは PbrMaterial の既定の map(白 / 黒 / 平らな法線)のまま
+  pending_maps: Vec<(usize, usize, PbrMapSlot)>,
+  skins: Vec<SkinAnimator>,
+  // skin のないものに bind する
+  identity_skin: Skin,
+  // [skin][animation]. target を skin の joint にしたもの
+  skin_animations: Vec<Vec<AnimationClip>>,
+  morphs: Vec<MorphAnimator>,
+  // morph target のないものに bind する
+  identity_morph: Morph,
+  // [morph][animation]. target を 0 にしたもの
+  morph_animations: Vec<Vec<AnimationClip>>,
+  animation_names: Vec<String>,
+}
+impl GltfModel {
+  // base_url は .gltf の置いてあるディレクトリ(末尾の / 込み). 画像の相対パスの解決に使う
+  pub fn new(gltf: &Gltf, base_url: &str) -> Self {
+    let mut loaders = Vec::new();
+    // (texture, srgb) -> loaders の index
+    let mut textures: HashMap<(usize, bool), usize> = HashMap::new();
+    let mut texture = |index: usize, srgb: bool| -> Option<usize> {
+      if let Some(loader) = textures.get(&(index, srgb)) {
+        return Some(*loader);
+      }
+      let desc = gltf.textures.get(index)?;
+      let image = gltf.images.get(desc.source?)?;
+      let format = if srgb {
+        PixelFormat::R8G8B8A8Srgb
+      } else {
+        PixelFormat::R8G8B8A8
+      };
+      let mut loader = match image {
+        GltfImage::Uri(uri) => {
+          TextureLoader::new_with_format(&resolve_uri(base_url, uri), format, true)
+        }
+        GltfImage::Embedded { mime_type, data } => {
+          TextureLoader::new_encoded_bytes(data, mime_type, format, true)
+        }
+      };
+      if let Some(sampler) = desc.sampler.and_then(|i| gltf.samplers.get(i)) {
+        loader.set_sampler(to_sampler(sampler));
+      }
+      loaders.push(loader);
+      textures.insert((index, srgb), loaders.len() - 1);
+      Some(loaders.len() - 1)
+    };
+    let mut pending_maps = Vec::new();
+    let mut material_sources = gltf.materials.clone();
+    material_sources.push(GltfMaterial::default());
+    let mut materials = Vec::new();
+    for source in &material_sources {
+      let mut material = PbrMaterial::new();
+      {
+        let mut attribute = material.write_attribute();
+        attribute.base_color_factor = source.base_color_factor;
+        attribute.emissive_factor = source.emissive_factor;
+        attribute.metallic_factor = source.metallic_factor;
+        attribute.roughness_factor = source.roughness_factor;
+        attribute.normal_scale = source.normal_scale;
+        attribute.occlusion_strength = source.occlusion_strength;
+        attribute.alpha_cutoff = if source.alpha_mode == GltfAlphaMode::Mask {
+          source.alpha_cutoff
+        } else {
+          0.0
+        };
+      }
+      // TEXCOORD_1 以降は読んでいないので全部 TEXCOORD_0 で引く
+      let maps = [
+        (source.base_color_texture, true, PbrMapSlot::BaseColor),
+        (source.emissive_texture, true, PbrMapSlot::Emissive),
+        (
+          source.metallic_roughness_texture,
+          false,
+          PbrMapSlot::MetallicRoughness,
+        ),
+        (source.normal_texture, false, PbrMapSlot::Normal),
+        (source.occlusion_texture, false, PbrMapSlot::Occlusion),
+      ];
+      for (texture_ref, srgb, slot) in maps {
+        if let Some(loader) = texture_ref.and_then(|x| texture(x.index, srgb)) {
+          pending_maps.push((materials.len(), loader, slot));
+        }
+      }
+      materials.push(material);
+    }
+    let default_material = materials.len() - 1;
+    let meshes = gltf
+      .meshes
+      .iter()
+      .map(|mesh| {
+        mesh
+          .primitives
+          .iter()
+          .filter(|primitive| !primitive.positions.is_empty())
+          .map(|primitive| {
+            let flat = with_flat_normals(primitive);
+            let primitive = flat.as_ref().unwrap_or(primitive);
+            GltfDrawable {
+              vao: to_vao(primitive),
+              topology: to_topology(primitive.mode),
+              material: primitive
+                .material
+                .filter(|i| *i < default_material)
+                .unwrap_or(default_material),
+            }
+          })
+          .collect()
+      })
+      .collect();
+    let mut skins = Vec::new();
+    let mut skin_animations = Vec::new();
+    for i in 0..gltf.skins.len() {
+      let skeleton = gltf.skeleton(i).unwrap_or_default();
+      skins.push(SkinAnimator::new(Skin::new(skeleton)));
+      skin_animations.push(
+        (0..gltf.animations.len())
+          .filter_map(|a| gltf.skin_animation(i, a))
+          .collect(),
+      );
+    }
+    let mut objects = Vec::new();
+    let mut morphs = Vec::new();
+    let mut morph_animations = Vec::new();
+    for (i, (node, world)) in gltf.nodes.iter().zip(gltf.world_matrices()).enumerate() {
+      let (mesh, world) = match (node.mesh, world) {
+        (Some(mesh), Some(world)) if mesh < gltf.meshes.len() => (mesh, world),
+        _ => continue,
+      };
+      let skin = node.skin.filter(|i| *i < skins.len());
+      let world = if skin.is_some() {
+        Mat4::IDENTITY
+      } else {
+        world
+      };
+      let mut transform = Transform::new();
+      {
+        let (scale, rotation, translate) = world.to_scale_rotation_translation();
+        let mut data = transform.write();
+        data.scale = scale;
+        data.rotation = rotation;
+        data.translate = translate;
+      }
+      let has_targets = gltf.meshes[mesh]
+        .primitives
+        .iter()
+        .any(|x| !x.targets.is_empty());
+      let morph = if has_targets {
+        morphs.push(MorphAnimator::new(Morph::new(gltf.morph_weights(i))));
+        morph_animations.push(
+          gltf
+            .animations
+            .iter()
+            .map(|clip| clip.retarget(|node| (node == i).then_some(0)))
+            .collect(),
+        );
+        Some(morphs.len() - 1)
+      } else {
+        None
+      };
+      objects.push(GltfObject {
+        name: node.name.clone(),
+        transform,
+        mesh,
+        skin,
+        morph,
+      });
+    }
+    Self {
+      meshes,
+      materials,
+      material_sources,
+      objects,
+      loaders,
+      pending_maps,
+      skins,
+      identity_skin: Skin::new_identity(),
+      skin_animations,
+      morphs,
+      identity_morph: Morph::new_identity(),
+      morph_animations,
+      animation_names: gltf.animations.iter().map(|x| x.name.clone()).collect(),
+    }
+  }
+  // node ごと primitive ごとに Pipeline を作る. shader は GltfVertex を入力にしたもの
+  // SkinAttribute も bind されるので Skin::vs_helper_code の skin_mat が使える
+  // MorphAttribute も同様に Morph::vs_helper_code の morph_position / morph_normal が使える
+  pub fn new_pipelines(&self, shader: &dyn PipelineBindable) -> Vec<SOwner<Pipeline>> {
+    let mut result = Vec::new();
+    for object in &self.objects {
+      for drawable in &self.meshes[object.mesh] {
+        let source = &self.material_sources[drawable.material];
+        let mut pipeline = Pipeline::new();
+        pipeline.add(&object.transform);
+        pipeline.add(drawable.vao.as_ref());
+        pipeline.add(&self.materials[drawable.material]);
+        match object.skin {
+          Some(skin) => pipeline.add(self.skins[skin].skin()),
+          None => pipeline.add(&self.identity_skin),
+        }
+        match object.morph {
+          Some(morph) => pipeline.add(self.morphs[morph].morph()),
+          None => pipeline.add(&self.identity_morph),
+        }
+        pipeline.add(shader);
+        pipeline.set_draw_mode(drawable.topology);
+        if source.double_sided {
+          pipeline.set_cull_mode(CullMode::None);
+        }
+        if source.alpha_mode == GltfAlphaMode::Blend {
+          let state = pipeline
+            .state()
+            .to_builder()
+            .blend(Some(BlendState::alpha()))
+            .depth_write(false)
+            .build();
+          pipeline.set_state(&state);
+        }
+        result.push(SOwner::new(pipeline));
+      }
+    }
+    result
+  }
+  pub fn objects(&self) -> &[GltfObject] {
+    &self.objects
+  }
+  pub fn objects_mut(&mut self) -> &mut [GltfObject] {
+    &mut self.objects
+  }
+  // 最後の一つは material の指定がない primitive 用
+  pub fn materials_mut(&mut self) -> &mut [PbrMaterial] {
+    &mut self.materials
+  }
+  pub fn animation_names(&self) -> &[String] {
+    &self.animation_names
+  }
+  // 全ての skin / morph で同じ animation を再生する
+  pub fn play_animation(&mut self, index: usize, looping: bool) {
+    for (skin, clips) in self.skins.iter_mut().zip(&self.skin_animations) {
+      if let Some(clip) = clips.get(index) {
+        skin.play(clip.clone(), looping);
+      }
+    }
+    for (morph, clips) in self.morphs.iter_mut().zip(&self.morph_animations) {
+      if let Some(clip) = clips.get(index) {
+        morph.play(clip.clone(), looping);
+      }
+    }
+  }
+  pub fn stop_animation(&mut self) {
+    for skin in &mut self.skins {
+      skin.stop();
+    }
+    for morph in &mut self.morphs {
+      morph.stop();
+    }
+  }
+  pub fn skins_mut(&mut self) -> &mut [SkinAnimator] {
+    &mut self.skins
+  }
+  pub fn morphs_mut(&mut self) -> &mut [MorphAnimator] {
+    &mut self.morphs
+  }
+  pub fn is_loaded(&self) -> bool {
+    self.loaders.iter().all(|x| x.is_loaded() || x.is_failed())
+  }
+}
+impl NeedUpdate for GltfModel {
+  fn update(&mut self) {
+    for loader in &mut self.loaders {
+      loader.update();
+    }
+    let (loaders, materials) = (&self.loaders, &mut self.materials);
+    // 失敗したものは既定の map のまま
+    self.pending_maps.retain(|&(material, loader, slot)| {
+      let loader = &loaders[loader];
+      if loader.is_loaded() {
+        *slot.map(&mut materials[material].write_mapping()) = loader.texture();
+      }
+      !loader.is_loaded() && !loader.is_failed()
+    });
+    for skin in &mut self.skins {
+      skin.update();
+    }
+    for morph in &mut self.morphs {
+      morph.update();
+    }
+  }
+}
+
+#[derive(Clone, Copy)]
+enum PbrMapSlot {
+  BaseColor,
+  MetallicRoughness,
+  Normal,
+  Occlusion,
+  Emissive,
+}
+impl PbrMapSlot {
+  fn map(self, mapping: &mut PbrMapping) -> &mut sampler2D {
+    match self {
+      Self::BaseColor => &mut mapping.base_color_map,
+      Self::MetallicRoughness => &mut mapping.metallic_roughness_map,
+      Self::Normal => &mut mapping.normal_map,
+      Self::Occlusion => &mut mapping.occlusion_map,
+      Self::Emissive => &mut mapping.emissive_map,
+    }
+  }
+}
+
+fn resolve_uri(base_url: &str, uri: &str) -> String {
+  if uri.contains("://") || uri.starts_with('/') {
+    uri.to_string()
+  } else {
+    format!("{}{}", base_url, uri)
+  }
+}
+// NORMAL のない三角形は glTF の仕様通り面法線にする. 三角形ごとに頂点を分けるので index はなくなる
+// 法線があるもの, 点や線のもの, index の壊れているものは None
+fn with_flat_normals(primitive: &GltfPrimitive) -> Option<GltfPrimitive> {
+  if !primitive.normals.is_empty() {
+    return None;
+  }
+  let mut mesh = Mesh::new_gltf_primitive(primitive)?;
+  let indices = mesh.indices.clone();
+  let vertex_count = primitive.positions.len();
+  // 長さの合わない属性は持たない扱い(Mesh::new_gltf_primitive と同じ)
+  fn expand<T: Copy>(values: &[T], indices: &[u32], vertex_count: usize) -> Vec<T> {
+    if values.len() != vertex_count {
+      return Vec::new();
+    }
+    indices.iter().map(|i| values[*i as usize]).collect()
+  }
+  mesh.compute_flat_normals();
+  Some(GltfPrimitive {
+    mode: GltfPrimitiveMode::Triangles,
+    positions: mesh.positions,
+    normals: mesh.normals,
+    tangents: mesh.tangents,
+    uvs: mesh.uvs,
+    colors: mesh.colors,
+    joints: expand(&primitive.joints, &indices, vertex_count),
+    weights: expand(&primitive.weights, &indices, vertex_count),
+    indices: None,
+    material: primitive.material,
+    targets: primitive
+      .targets
+      .iter()
+      .map(|target| GltfMorphTarget {
+        positions: expand(&target.positions, &indices, vertex_count),
+        normals: expand(&target.normals, &indices, vertex_count),
+      })
+      .collect(),
+  })
+}
+// 頂点数が収まるなら u16 にする(FittedIndices)
+fn to_vao(primitive: &GltfPrimitive) -> Box<dyn PipelineBindable> {
+  fn to_vao_impl<I: IndexElement>(
+    v_data: Vec<GltfVertex>,
+    i_data: Option<Vec<I>>,
+    morph_stream: Option<VertexBuffer<MorphVertex>>,
+  ) -> Box<dyn PipelineBindable> {
+    let v_buffer = VertexBuffer::new(v_data);
+    let mut vao = match i_data {
+      Some(i_data) => Vao::new(v_buffer, IndexBuffer::new(i_data)),
+      None => Vao::new_without_index_buffer(v_buffer),
+    };
+    if let Some(morph_stream) = morph_stream {
+      vao.add_stream(morph_stream);
+    }
+    Box::new(SOwner::new(vao))
+  }
+  // ない属性は既定値で埋める
+  let v_data: Vec<GltfVertex> = (0..primitive.positions.len())
+    .map(|i| GltfVertex {
+      position: primitive.positions[i],
+      normal: primitive.normals.get(i).copied().unwrap_or(Vec3::ZERO),
+      tangent: primitive
+        .tangents
+        .get(i)
+        .copied()
+        .unwrap_or(Vec4::new(1.0, 0.0, 0.0, 1.0)),
+      uv: primitive.uvs.get(i).copied().unwrap_or(Vec2::ZERO),
+      color: primitive.colors.get(i).copied().unwrap_or(Vec4::ONE),
+      joints: primitive
+        .joints
+        .get(i)
+        .map(|j| Vec4::new(j[0] as f32, j[1] as f32, j[2] as f32, j[3] as f32))
+        .unwrap_or(Vec4::ZERO),
+      weights: primitive.weights.get(i).copied().unwrap_or(Vec4::ZERO),
+    })
+    .collect();
+  let morph_stream = if primitive.targets.is_empty() {
+    None
+  } else {
+    let targets: Vec<(&[Vec3], &[Vec3])> = primitive
+      .targets
+      .iter()
+      .map(|x| (&x.positions[..], &x.normals[..]))
+      .collect();
+    Some(Morph::new_stream(v_data.len(), &targets))
+  };
+  match &primitive.indices {
+    Some(indices) => match FittedIndices::new(v_data.len(), indices) {
+      FittedIndices::U16(i_data) => to_vao_impl(v_data, Some(i_data), morph_stream),
+      FittedIndices::U32(i_data) => to_vao_impl(v_data, Some(i_data), morph_stream),
+    },
+    None => to_vao_impl::<u32>(v_data, None, morph_stream),
+  }
+}
+fn to_topology(mode: GltfPrimitiveMode) -> PrimitiveToporogy {
+  match mode {
+    GltfPrimitiveMode::Points => PrimitiveToporogy::Points,
+    GltfPrimitiveMode::Lines => PrimitiveToporogy::Lines,
+    GltfPrimitiveMode::LineLoop => PrimitiveToporogy::LineLoop,
+    GltfPrimitiveMode::LineStrip => PrimitiveToporogy::LineStrip,
+    GltfPrimitiveMode::Triangles => PrimitiveToporogy::Triangles,
+    GltfPrimitiveMode::TriangleStrip => PrimitiveToporogy::TriangleStrip,
+    GltfPrimitiveMode::TriangleFan => PrimitiveToporogy::TriangleFan,
+  }
+}
+// wrap は s / t を別々に持てないので s に揃える
+fn to_sampler(sampler: &GltfSampler) -> Sampler {
+  let mag_filter = match sampler.mag_filter {
+    Some(gl::NEAREST) => SamplerMagFilter::Nearest,
+    _ => SamplerMagFilter::Linear,
+  };
+  let min_filter = match sampler.min_filter {
+    Some(gl::NEAREST) => SamplerMinFilter::Nearest,
+    Some(gl::LINEAR) => SamplerMinFilter::Linear,
+    Some(gl::LINEAR_MIPMAP_NEAREST) => SamplerMinFilter::LinearMipmapNearest,
+    Some(gl::NEAREST_MIPMAP_LINEAR) => SamplerMinFilter::NearestMipmapLinear,
+    _ => SamplerMinFilter::LinearMipmapLinear,
+  };
+  let wrap_mode = match sampler.wrap_s {
+    gl::CLAMP_TO_EDGE => SamplerWrapMode::ClampToEdge,
+    gl::MIRRORED_REPEAT => SamplerWrapMode::MirroredRepeat,
+    _ => SamplerWrapMode::Repeat,
+  };
+  Sampler::new(mag_filter, min_filter, wrap_mode)
+}
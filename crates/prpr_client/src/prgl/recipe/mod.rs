@@ -7,3 +7,7 @@ mod per_renderpass;
 pub use self::per_renderpass::*;
 mod common;
 pub use self::common::*;
+mod gltf;
+pub use self::gltf::*;
+mod scene;
+pub use self::scene::*;
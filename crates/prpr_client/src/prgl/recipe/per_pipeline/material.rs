@@ -1,12 +1,24 @@
 use super::*;
 
+// glTF の metallic-roughness と同じ
 crate::shader_attr! {
   struct PbrAttribute {
-    albedo_color: vec3
-    roughness: float
+    base_color_factor: vec4
+    emissive_factor: vec3
+    metallic_factor: float
+    roughness_factor: float
+    normal_scale: float
+    occlusion_strength: float
+    // 0 なら alpha test しない
+    alpha_cutoff: float
   }
   mapping PbrMapping {
+    base_color_map: sampler2D,
+    // g: roughness, b: metallic
+    metallic_roughness_map: sampler2D,
     normal_map: sampler2D,
+    occlusion_map: sampler2D,
+    emissive_map: sampler2D,
   }
 }
 pub struct PbrMaterial {
@@ -15,19 +27,104 @@ pub struct PbrMaterial {
 }
 impl PbrMaterial {
   pub fn new() -> Self {
-    let default_normal_map = SOwner::new(Texture::new_rgba_map(4, 4, |_, _| {
-      Vec4::new(0.0, 0.0, 1.0, 0.0)
-    }));
+    // map がないときは factor がそのまま使われるようにしておく
+    let pixel = |color: Vec4| {
+      let texture = Texture::new_rgba_map(1, 1, |_, _| color);
+      SOwner::new(texture.expect("1x1 texture is always within limits"))
+    };
+    let white = pixel(Vec4::ONE);
+    let black = pixel(Vec4::new(0.0, 0.0, 0.0, 1.0));
+    let default_normal_map = pixel(Vec4::new(0.5, 0.5, 1.0, 1.0));
     Self {
       ubo: SOwner::new(UniformBuffer::new(PbrAttribute {
-        albedo_color: Vec3::ONE,
-        roughness: 0.0,
+        base_color_factor: Vec4::ONE,
+        emissive_factor: Vec3::ZERO,
+        metallic_factor: 1.0,
+        roughness_factor: 1.0,
+        normal_scale: 1.0,
+        occlusion_strength: 1.0,
+        alpha_cutoff: 0.0,
       })),
       mapping: SOwner::new(TextureMapping::new(PbrMapping {
+        base_color_map: white.clone_reader(),
+        metallic_roughness_map: white.clone_reader(),
         normal_map: default_normal_map.clone_reader(),
+        occlusion_map: white.clone_reader(),
+        emissive_map: black.clone_reader(),
       })),
     }
   }
+  // テクスチャは ObjMaterial の *_map を読み込んで write_mapping で差し替えること
+  pub fn new_obj(material: &prpr::obj::ObjMaterial) -> Self {
+    let mut result = Self::new();
+    {
+      let mut attribute = result.write_attribute();
+      attribute.base_color_factor = material.diffuse.extend(material.dissolve);
+      attribute.emissive_factor = material.emissive;
+      attribute.metallic_factor = 0.0;
+      attribute.roughness_factor = material.roughness();
+    }
+    result
+  }
+  // Shape を Camera / Lights のある RenderPass で描く Cook-Torrance の shader
+  // 出力先は sRGB でないので最後に gamma をかける
+  pub fn shader() -> ShaderTemplate {
+    let mut result = crate::shader_template! {
+      attrs: [CameraAttribute, TransformAttribute, LightAttribute, PbrAttribute, PbrMapping],
+      vs_attr: ShapeVertex,
+      vs_code: {
+        void main() {
+          vec4 world_position = model_mat * vec4(position, 1.0);
+          gl_Position = view_proj_mat * world_position;
+          mat3 normal_mat = transpose(inverse(mat3(model_mat)));
+          in_position = world_position.xyz;
+          in_normal = normal_mat * normal;
+          in_tangent = vec4(mat3(model_mat) * tangent.xyz, tangent.w);
+          in_uv = uv;
+        }
+      },
+      fs_attr: { in_position: vec3, in_normal: vec3, in_tangent: vec4, in_uv: vec2 },
+      fs_code: {
+        void main() {
+          vec4 base_color = base_color_factor * texture(base_color_map, in_uv);
+          if (alpha_cutoff > 0.0 && base_color.a < alpha_cutoff) {
+            discard;
+          }
+          vec4 metallic_roughness = texture(metallic_roughness_map, in_uv);
+          float metallic = clamp(metallic_factor * metallic_roughness.b, 0.0, 1.0);
+          float roughness = clamp(roughness_factor * metallic_roughness.g, 0.04, 1.0);
+          vec3 n = normalize(in_normal);
+          if (!gl_FrontFacing) {
+            n = -n;
+          }
+          vec3 t = normalize(in_tangent.xyz - n * dot(n, in_tangent.xyz));
+          vec3 b = cross(n, t) * in_tangent.w;
+          vec3 normal_tex = texture(normal_map, in_uv).xyz * 2.0 - 1.0;
+          normal_tex.xy *= normal_scale;
+          n = normalize(mat3(t, b, n) * normal_tex);
+          vec3 v = normalize(camera_pos - in_position);
+          float occlusion = mix(1.0, texture(occlusion_map, in_uv).r, occlusion_strength);
+          vec3 color = pbr_lighting(in_position, n, v, base_color.rgb, metallic, roughness, occlusion);
+          color += emissive_factor * texture(emissive_map, in_uv).rgb;
+          out_color = vec4(pow(color, vec3(1.0 / 2.2)), base_color.a);
+        }
+      }
+      out_attr: { out_color: vec4 }
+    };
+    result.add_fs_helper(LightData::fs_helper_code());
+    result
+  }
+  pub fn write_attribute(&mut self) -> SDerefMutable<'_, UniformBuffer<PbrAttribute>> {
+    self.ubo.write()
+  }
+  pub fn write_mapping(&mut self) -> SDerefMutable<'_, TextureMapping<PbrMapping>> {
+    self.mapping.write()
+  }
+}
+impl Default for PbrMaterial {
+  fn default() -> Self {
+    Self::new()
+  }
 }
 impl PipelineBindable for PbrMaterial {
   fn bind_pipeline(&self, pipeline: &mut Pipeline) {
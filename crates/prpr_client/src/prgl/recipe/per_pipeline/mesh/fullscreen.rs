@@ -6,7 +6,7 @@ crate::shader_attr! {
   }
 }
 pub struct FullScreen {
-  vao: SOwner<Vao<FullScreenVertex>>,
+  vao: SOwner<Vao<FullScreenVertex, u16>>,
 }
 impl FullScreen {
   pub fn new() -> Self {
@@ -33,6 +33,7 @@ impl FullScreen {
   pub fn new_pipeline() -> Pipeline {
     let mut pipeline = Pipeline::new();
     pipeline.set_depth_func(DepthFunc::Always);
+    pipeline.set_depth_write(false);
     pipeline.set_cull_mode(CullMode::None);
     pipeline.add(&Self::new());
     pipeline
@@ -1,20 +1,34 @@
-// use super::*;
-/*
-crate::shader_attr! {
-  struct GridVertex {
-    position: vec3,
-  }
-}
+use super::*;
+
+// XZ 平面の目盛り. X 軸は赤, Z 軸は青で描く
 pub struct Grid {
-  vao: SOwner<Vao<FullScreenVertex>>,
+  shape: Shape,
 }
 impl Grid {
-  pub fn new(x: f32, y: f32, z: f32, interval: f32) -> Self {
-    let mut interval = interval;
-    if interval < 0.0 {
-      log::error("grid interval < 0");
-      interval = 1.0;
+  fn shader() -> ShaderTemplate {
+    crate::shader_template! {
+      attrs: [CameraAttribute],
+      vs_attr: ShapeVertex,
+      vs_code: {
+        void main() {
+          gl_Position = view_proj_mat * vec4(position, 1.0);
+          in_position = position;
+        }
+      },
+      fs_attr: { in_position: vec3 },
+      fs_code: {
+        void main() {
+          vec3 color = vec3(0.5);
+          if (abs(in_position.z) < 0.0001) { color = vec3(1.0, 0.2, 0.2); }
+          if (abs(in_position.x) < 0.0001) { color = vec3(0.2, 0.2, 1.0); }
+          out_color = vec4(color, 0.5);
+        }
+      }
+      out_attr: { out_color: vec4 }
     }
+  }
+  // [-x, x] x [-z, z] の範囲に interval 間隔で引く
+  pub fn new(x: f32, z: f32, interval: f32) -> Self {
     fn refine(a: f32) -> f32 {
       let eps = 0.001;
       let mut a = a.abs();
@@ -23,40 +37,28 @@ impl Grid {
       }
       a
     }
-    let x = refine(x);
-    let y = refine(y);
-    let z = refine(z);
-    // バインディングなしでも行けるがそんなに変わらないので
-    let i_buffer = IndexBuffer::new(vec![0, 1, 2, 2, 1, 3]);
-    let v_buffer = VertexBuffer::new(vec![
-      FullScreenVertex {
-        position: Vec2::new(-1.0, -1.0),
-      },
-      FullScreenVertex {
-        position: Vec2::new(1.0, -1.0),
-      },
-      FullScreenVertex {
-        position: Vec2::new(-1.0, 1.0),
-      },
-      FullScreenVertex {
-        position: Vec2::new(1.0, 1.0),
-      },
-    ]);
     Self {
-      vao: SOwner::new(Vao::new(v_buffer, i_buffer)),
+      shape: Shape::new_grid_lines(refine(x), refine(z), interval),
     }
   }
-  pub fn new_pipeline() -> Pipeline {
+  // 物体に隠れるように depth test はするが書き込まない
+  pub fn new_pipeline(x: f32, z: f32, interval: f32) -> Pipeline {
     let mut pipeline = Pipeline::new();
-    pipeline.set_depth_func(DepthFunc::Always);
+    let state = pipeline
+      .state()
+      .to_builder()
+      .blend(Some(BlendState::alpha()))
+      .depth_write(false)
+      .build();
+    pipeline.set_state(&state);
     pipeline.set_cull_mode(CullMode::None);
-    // pipeline.add(&Self::new());
+    pipeline.add(&Self::new(x, z, interval));
+    pipeline.add(&MayShader::new(Self::shader()));
     pipeline
   }
 }
 impl PipelineBindable for Grid {
   fn bind_pipeline(&self, pipeline: &mut Pipeline) {
-    pipeline.set_draw_vao(&self.vao);
+    pipeline.add(&self.shape);
   }
 }
-*/
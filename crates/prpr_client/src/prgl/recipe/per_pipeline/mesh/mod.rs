@@ -3,5 +3,7 @@ mod fullscreen;
 pub use fullscreen::*;
 mod grid;
 pub use grid::*;
+mod obj;
+pub use obj::*;
 mod shape;
 pub use shape::*;
@@ -0,0 +1,29 @@
+use super::*;
+use prpr::obj::*;
+
+// OBJ の usemtl ごとの Shape
+pub struct ObjShape {
+  material: Option<String>,
+  shape: Shape,
+}
+impl ObjShape {
+  pub fn new_submeshes(obj: &Obj) -> Vec<Self> {
+    obj
+      .submeshes
+      .iter()
+      .map(|submesh| Self {
+        material: submesh.material.clone(),
+        shape: Shape::new_obj_submesh(submesh),
+      })
+      .collect()
+  }
+  // usemtl の名前. ObjMaterial::name と対応する
+  pub fn material(&self) -> Option<&str> {
+    self.material.as_deref()
+  }
+}
+impl PipelineBindable for ObjShape {
+  fn bind_pipeline(&self, pipeline: &mut Pipeline) {
+    self.shape.bind_pipeline(pipeline);
+  }
+}
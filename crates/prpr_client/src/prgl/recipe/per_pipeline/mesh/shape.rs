@@ -1,88 +1,458 @@
 use super::*;
+use prpr::mesh::Mesh;
+use std::collections::HashMap;
+use std::f32::consts::PI;
 
 crate::shader_attr! {
   struct ShapeVertex {
     position: vec3,
     normal: vec3,
-    // tangent: vec3,
-    // uv: vec2
+    // w は bitangent = cross(normal, tangent) * w の符号
+    tangent: vec4,
+    uv: vec2,
   }
 }
 pub struct Shape {
-  vao: SOwner<Vao<ShapeVertex>>,
+  vao: Box<dyn PipelineBindable>,
+  topology: PrimitiveToporogy,
 }
+// 生成するものは特に書いていなければ原点中心で 1x1x1 に収まる大きさ
+// 三角形は外側から見て反時計回りなので CullMode::Back でよい
 impl Shape {
+  // 頂点数が収まるなら u16 にする(FittedIndices)
+  pub fn new(v_data: Vec<ShapeVertex>, i_data: Vec<u32>) -> Self {
+    Self::new_with_topology(v_data, i_data, PrimitiveToporogy::Triangles)
+  }
+  pub fn new_with_topology(
+    v_data: Vec<ShapeVertex>,
+    i_data: Vec<u32>,
+    topology: PrimitiveToporogy,
+  ) -> Self {
+    fn to_vao<I: IndexElement>(
+      v_data: Vec<ShapeVertex>,
+      i_data: Vec<I>,
+    ) -> Box<dyn PipelineBindable> {
+      let vao = Vao::new(VertexBuffer::new(v_data), IndexBuffer::new(i_data));
+      Box::new(SOwner::new(vao))
+    }
+    let vao = match FittedIndices::new(v_data.len(), &i_data) {
+      FittedIndices::U16(i_data) => to_vao(v_data, i_data),
+      FittedIndices::U32(i_data) => to_vao(v_data, i_data),
+    };
+    Self { vao, topology }
+  }
+  // 三角形リストとして載せる. ない属性は既定値で埋める(tangent は計算しない)
+  pub fn new_mesh(mesh: &Mesh) -> Self {
+    Self::new(to_vertices(mesh), mesh.indices.clone())
+  }
+  // 向きはファイルのままにする. uv がなければ 0
+  pub fn new_obj_submesh(submesh: &prpr::obj::ObjSubmesh) -> Self {
+    let mut mesh = Mesh::new_obj_submesh(submesh);
+    mesh.compute_tangents();
+    Self::new_mesh(&mesh)
+  }
   pub fn new_cube() -> Self {
-    let mut v_data: Vec<ShapeVertex> = Vec::new();
-    for x in [-0.5, 0.5] {
-      for y in [-0.5, 0.5] {
-        for z in [-0.5, 0.5] {
-          let position = Vec3::new(x, y, z);
-          for normal in [
-            x.signum() * Vec3::X,
-            y.signum() * Vec3::Y,
-            z.signum() * Vec3::Z,
-          ] {
-            v_data.push(ShapeVertex { position, normal })
-          }
-        }
-      }
+    let mut builder = ShapeBuilder::default();
+    for normal in box_normals() {
+      builder.box_face(normal, &[-0.5, 0.5], |p| (p, normal));
     }
-    let i_data: Vec<IndexBufferType> = vec![
-      0, 3, 6, 9, 6, 3, //
-      15, 12, 18, 18, 21, 15, //
-      1, 16, 4, 16, 1, 13, //
-      7, 10, 22, 22, 19, 7, //
-      2, 8, 14, 14, 8, 20, //
-      5, 17, 11, 17, 23, 11, //
-    ];
-    let i_buffer = IndexBuffer::new(i_data);
-    let v_buffer = VertexBuffer::new(v_data);
-    Self {
-      vao: SOwner::new(Vao::new(v_buffer, i_buffer)),
+    builder.build()
+  }
+  // 角を半径 radius で丸めた箱. segments は角の丸めの分割数
+  pub fn new_rounded_box(radius: f32, segments: usize) -> Self {
+    let radius = radius.clamp(0.0, 0.5);
+    let segments = segments.max(1);
+    // 面の端の radius の幅だけ細かく割る
+    let mut coords = Vec::new();
+    for i in 0..=segments {
+      coords.push(-0.5 + radius * i as f32 / segments as f32);
+    }
+    for i in 0..=segments {
+      coords.push(0.5 - radius + radius * i as f32 / segments as f32);
+    }
+    let inner = Vec3::ONE * (0.5 - radius);
+    let mut builder = ShapeBuilder::default();
+    for normal in box_normals() {
+      // 内側の箱に一番近い点から radius だけ離す
+      builder.box_face(normal, &coords, |p| {
+        let core = p.clamp(-inner, inner);
+        let dir = (p - core).normalize_or_zero();
+        if dir == Vec3::ZERO {
+          (p, normal)
+        } else {
+          (core + dir * radius, dir)
+        }
+      });
     }
+    builder.build()
   }
   pub fn new_sphere(xn: usize, yn: usize) -> Self {
-    use std::f32::consts::PI;
-    let mut v_data: Vec<ShapeVertex> = Vec::new();
-    for x in 0..=xn {
-      let x = x as f32;
-      let xn = xn as f32;
-      let xrad = PI * 2.0 * x / xn;
-      for y in 0..=yn {
-        let y = y as f32;
-        let yn = yn as f32;
-        let yrad = PI * y / yn;
-        let normal = Vec3::new(xrad.sin() * yrad.sin(), yrad.cos(), xrad.cos() * yrad.sin());
-        let position = normal * 0.5;
-        v_data.push(ShapeVertex { position, normal })
+    let strip = (0..=yn)
+      .map(|y| {
+        let yrad = PI * y as f32 / yn as f32;
+        let normal = Vec2::new(yrad.sin(), yrad.cos());
+        ProfilePoint::new(normal * 0.5, normal)
+      })
+      .collect();
+    let mut builder = ShapeBuilder::default();
+    builder.lathe(xn, &[strip]);
+    builder.build()
+  }
+  // 正二十面体を subdivisions 回分割した球. 極に頂点が集まらない
+  pub fn new_icosphere(subdivisions: usize) -> Self {
+    let t = (1.0 + 5.0_f32.sqrt()) * 0.5;
+    let mut positions: Vec<Vec3> = [
+      (-1.0, t, 0.0),
+      (1.0, t, 0.0),
+      (-1.0, -t, 0.0),
+      (1.0, -t, 0.0),
+      (0.0, -1.0, t),
+      (0.0, 1.0, t),
+      (0.0, -1.0, -t),
+      (0.0, 1.0, -t),
+      (t, 0.0, -1.0),
+      (t, 0.0, 1.0),
+      (-t, 0.0, -1.0),
+      (-t, 0.0, 1.0),
+    ]
+    .iter()
+    .map(|(x, y, z)| Vec3::new(*x, *y, *z).normalize())
+    .collect();
+    let mut faces: Vec<[u32; 3]> = vec![
+      [0, 11, 5],
+      [0, 5, 1],
+      [0, 1, 7],
+      [0, 7, 10],
+      [0, 10, 11],
+      [1, 5, 9],
+      [5, 11, 4],
+      [11, 10, 2],
+      [10, 7, 6],
+      [7, 1, 8],
+      [3, 9, 4],
+      [3, 4, 2],
+      [3, 2, 6],
+      [3, 6, 8],
+      [3, 8, 9],
+      [4, 9, 5],
+      [2, 4, 11],
+      [6, 2, 10],
+      [8, 6, 7],
+      [9, 8, 1],
+    ];
+    for _ in 0..subdivisions {
+      let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+      let mut midpoint = |a: u32, b: u32| -> u32 {
+        let key = (a.min(b), a.max(b));
+        *midpoints.entry(key).or_insert_with(|| {
+          let p = (positions[a as usize] + positions[b as usize]).normalize();
+          positions.push(p);
+          (positions.len() - 1) as u32
+        })
+      };
+      let mut next = Vec::with_capacity(faces.len() * 4);
+      for [a, b, c] in faces {
+        let ab = midpoint(a, b);
+        let bc = midpoint(b, c);
+        let ca = midpoint(c, a);
+        next.extend_from_slice(&[[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]);
       }
+      faces = next;
     }
-    let mut i_data: Vec<IndexBufferType> = Vec::new();
-    for x in 1..=xn {
-      for y in 1..=yn {
-        let i = y + (yn + 1) * x;
-        for idx in [
-          i,
-          i - 1,
-          i - (yn + 1),
-          i - (yn + 1),
-          i - 1,
-          i - (yn + 1) - 1,
-        ] {
-          i_data.push(idx as IndexBufferType);
-        }
-      }
+    // uv は経緯度. 継ぎ目の三角形は少し伸びる
+    let mut builder = ShapeBuilder::default();
+    for normal in positions {
+      let uv = Vec2::new(
+        normal.x.atan2(normal.z) / (PI * 2.0) + 0.5,
+        normal.y.clamp(-1.0, 1.0).acos() / PI,
+      );
+      builder.vertex(normal * 0.5, normal, uv);
     }
-    let i_buffer = IndexBuffer::new(i_data);
-    let v_buffer = VertexBuffer::new(v_data);
-    Self {
-      vao: SOwner::new(Vao::new(v_buffer, i_buffer)),
+    for [a, b, c] in faces {
+      builder.triangle(a, b, c);
+    }
+    builder.build()
+  }
+  // XZ 平面で +Y を向く
+  pub fn new_plane(xn: usize, zn: usize) -> Self {
+    let mut builder = ShapeBuilder::default();
+    builder.surface(xn, zn, |u, v| (Vec3::new(u - 0.5, 0.0, v - 0.5), Vec3::Y));
+    builder.build()
+  }
+  // XZ 平面上の線. [-x, x] x [-z, z] に原点を通るように interval 間隔で引く
+  pub fn new_grid_lines(x: f32, z: f32, interval: f32) -> Self {
+    let x = x.abs();
+    let z = z.abs();
+    let mut interval = interval;
+    if interval <= 0.0 {
+      log::error("grid interval <= 0");
+      interval = 1.0;
+    }
+    let mut builder = ShapeBuilder::default();
+    let uv = |p: Vec3| Vec2::new(p.x / x.max(f32::EPSILON), p.z / z.max(f32::EPSILON)) * 0.5 + 0.5;
+    let mut line = |a: Vec3, b: Vec3| {
+      let a = builder.vertex(a, Vec3::Y, uv(a));
+      let b = builder.vertex(b, Vec3::Y, uv(b));
+      builder.mesh.indices.extend_from_slice(&[a, b]);
+    };
+    let xn = (x / interval) as i32;
+    for i in -xn..=xn {
+      let px = i as f32 * interval;
+      line(Vec3::new(px, 0.0, -z), Vec3::new(px, 0.0, z));
+    }
+    let zn = (z / interval) as i32;
+    for i in -zn..=zn {
+      let pz = i as f32 * interval;
+      line(Vec3::new(-x, 0.0, pz), Vec3::new(x, 0.0, pz));
+    }
+    builder.build_with_topology(PrimitiveToporogy::Lines)
+  }
+  pub fn new_cylinder(xn: usize) -> Self {
+    let mut builder = ShapeBuilder::default();
+    builder.lathe(
+      xn,
+      &[
+        ProfilePoint::new_strip(&[(0.0, 0.5), (0.5, 0.5)], Vec2::Y),
+        ProfilePoint::new_strip(&[(0.5, 0.5), (0.5, -0.5)], Vec2::X),
+        ProfilePoint::new_strip(&[(0.5, -0.5), (0.0, -0.5)], -Vec2::Y),
+      ],
+    );
+    builder.build()
+  }
+  // 頂点が +Y
+  pub fn new_cone(xn: usize) -> Self {
+    let mut builder = ShapeBuilder::default();
+    builder.lathe(
+      xn,
+      &[
+        ProfilePoint::new_strip(&[(0.0, 0.5), (0.5, -0.5)], Vec2::new(1.0, 0.5)),
+        ProfilePoint::new_strip(&[(0.5, -0.5), (0.0, -0.5)], -Vec2::Y),
+      ],
+    );
+    builder.build()
+  }
+  // 半径 0.5 の半球を height だけ離したもの. yn は半球の分割数
+  pub fn new_capsule(height: f32, xn: usize, yn: usize) -> Self {
+    let half = height.max(0.0) * 0.5;
+    let mut strip = Vec::new();
+    for (offset, range) in [(half, 0..=yn), (-half, yn..=yn * 2)] {
+      for y in range {
+        let yrad = PI * 0.5 * y as f32 / yn as f32;
+        let normal = Vec2::new(yrad.sin(), yrad.cos());
+        strip.push(ProfilePoint::new(normal * 0.5 + Vec2::Y * offset, normal));
+      }
     }
+    let mut builder = ShapeBuilder::default();
+    builder.lathe(xn, &[strip]);
+    builder.build()
+  }
+  // Y 軸まわり. radius は管の中心までの距離
+  pub fn new_torus(radius: f32, tube_radius: f32, xn: usize, yn: usize) -> Self {
+    let strip = (0..=yn)
+      .map(|y| {
+        let yrad = PI * 2.0 * y as f32 / yn as f32;
+        let normal = Vec2::new(yrad.sin(), yrad.cos());
+        ProfilePoint::new(normal * tube_radius + Vec2::X * radius, normal)
+      })
+      .collect();
+    let mut builder = ShapeBuilder::default();
+    builder.lathe(xn, &[strip]);
+    builder.build()
+  }
+  // 原点から +Y に長さ 1 の矢印
+  pub fn new_arrow(xn: usize) -> Self {
+    ShapeBuilder::new_arrow(xn).build()
+  }
+  // X / Y / Z 軸の矢印
+  pub fn new_axis_gizmo(xn: usize) -> [Self; 3] {
+    [
+      ShapeBuilder::new_arrow(xn)
+        .transform(Mat4::from_rotation_z(-PI * 0.5))
+        .build(),
+      ShapeBuilder::new_arrow(xn).build(),
+      ShapeBuilder::new_arrow(xn)
+        .transform(Mat4::from_rotation_x(PI * 0.5))
+        .build(),
+    ]
   }
 }
 impl PipelineBindable for Shape {
   fn bind_pipeline(&self, pipeline: &mut Pipeline) {
-    pipeline.set_draw_vao(&self.vao);
+    pipeline.add(self.vao.as_ref());
+    pipeline.set_draw_mode(self.topology);
+  }
+}
+
+fn box_normals() -> [Vec3; 6] {
+  [Vec3::X, -Vec3::X, Vec3::Y, -Vec3::Y, Vec3::Z, -Vec3::Z]
+}
+
+// 回転体の断面. position.x は Y 軸からの距離
+#[derive(Clone, Copy)]
+struct ProfilePoint {
+  position: Vec2,
+  normal: Vec2,
+}
+impl ProfilePoint {
+  fn new(position: Vec2, normal: Vec2) -> Self {
+    Self {
+      position,
+      normal: normal.normalize(),
+    }
+  }
+  fn new_strip(points: &[(f32, f32)], normal: Vec2) -> Vec<Self> {
+    points
+      .iter()
+      .map(|(x, y)| Self::new(Vec2::new(*x, *y), normal))
+      .collect()
+  }
+}
+
+fn to_vertices(mesh: &Mesh) -> Vec<ShapeVertex> {
+  (0..mesh.vertex_count())
+    .map(|i| ShapeVertex {
+      position: mesh.positions[i],
+      normal: mesh.normals.get(i).copied().unwrap_or(Vec3::ZERO),
+      tangent: mesh
+        .tangents
+        .get(i)
+        .copied()
+        .unwrap_or(Vec4::new(1.0, 0.0, 0.0, 1.0)),
+      uv: mesh.uvs.get(i).copied().unwrap_or(Vec2::ZERO),
+    })
+    .collect()
+}
+
+#[derive(Default)]
+struct ShapeBuilder {
+  mesh: Mesh,
+}
+impl ShapeBuilder {
+  fn vertex(&mut self, position: Vec3, normal: Vec3, uv: Vec2) -> u32 {
+    self.mesh.positions.push(position);
+    self.mesh.normals.push(normal);
+    self.mesh.uvs.push(uv);
+    (self.mesh.positions.len() - 1) as u32
+  }
+  // 頂点法線の側から見て反時計回りになるように並べる. 面積のないものは捨てる
+  fn triangle(&mut self, a: u32, b: u32, c: u32) {
+    let p = |i: u32| self.mesh.positions[i as usize];
+    let n = |i: u32| self.mesh.normals[i as usize];
+    let face_normal = (p(b) - p(a)).cross(p(c) - p(a));
+    if face_normal == Vec3::ZERO {
+      return;
+    }
+    let normal = n(a) + n(b) + n(c);
+    if face_normal.dot(normal) < 0.0 {
+      self.mesh.indices.extend_from_slice(&[a, c, b]);
+    } else {
+      self.mesh.indices.extend_from_slice(&[a, b, c]);
+    }
+  }
+  // 周に沿った順の四角形
+  fn quad(&mut self, a: u32, b: u32, c: u32, d: u32) {
+    self.triangle(a, b, c);
+    self.triangle(a, c, d);
+  }
+  // (u, v) の格子を f で曲面に写す. uv はそのまま (u, v)
+  fn surface(&mut self, xn: usize, yn: usize, f: impl Fn(f32, f32) -> (Vec3, Vec3)) {
+    let coords = |n: usize| -> Vec<f32> { (0..=n).map(|i| i as f32 / n as f32).collect() };
+    let (us, vs) = (coords(xn), coords(yn));
+    self.surface_with_coords(&us, &vs, |x, y| f(us[x], vs[y]));
+  }
+  // f には us / vs の index が渡される
+  fn surface_with_coords(
+    &mut self,
+    us: &[f32],
+    vs: &[f32],
+    f: impl Fn(usize, usize) -> (Vec3, Vec3),
+  ) {
+    let base = self.mesh.positions.len() as u32;
+    for (x, u) in us.iter().enumerate() {
+      for (y, v) in vs.iter().enumerate() {
+        let (position, normal) = f(x, y);
+        self.vertex(position, normal, Vec2::new(*u, *v));
+      }
+    }
+    let stride = vs.len() as u32;
+    for x in 1..us.len() as u32 {
+      for y in 1..stride {
+        let i = base + x * stride + y;
+        self.quad(i - stride - 1, i - 1, i, i - stride);
+      }
+    }
+  }
+  // 外から見て u が右, v が下に増えるように箱の一面を張る. coords は [-0.5, 0.5] の分割
+  fn box_face(&mut self, normal: Vec3, coords: &[f32], f: impl Fn(Vec3) -> (Vec3, Vec3)) {
+    let u_axis = if normal.y == 0.0 {
+      Vec3::Y.cross(normal)
+    } else {
+      Vec3::X
+    };
+    let v_axis = u_axis.cross(normal);
+    let uvs: Vec<f32> = coords.iter().map(|x| x + 0.5).collect();
+    self.surface_with_coords(&uvs, &uvs, |x, y| {
+      f(normal * 0.5 + u_axis * coords[x] + v_axis * coords[y])
+    });
+  }
+  // 断面を Y 軸まわりに xn 分割で回す
+  // strip の中は滑らかにつなぎ, strip の間は頂点を分けて折り目にする
+  fn lathe(&mut self, xn: usize, strips: &[Vec<ProfilePoint>]) {
+    // v は断面に沿った長さで 0..1 にする
+    let mut lengths = Vec::new();
+    let mut total = 0.0;
+    let mut prev: Option<Vec2> = None;
+    for point in strips.iter().flatten() {
+      if let Some(prev) = prev {
+        total += prev.distance(point.position);
+      }
+      lengths.push(total);
+      prev = Some(point.position);
+    }
+    let vs: Vec<f32> = lengths
+      .iter()
+      .map(|x| if total > 0.0 { x / total } else { 0.0 })
+      .collect();
+    let us: Vec<f32> = (0..=xn).map(|x| x as f32 / xn as f32).collect();
+    let mut offset = 0;
+    for strip in strips {
+      let strip_vs = &vs[offset..offset + strip.len()];
+      self.surface_with_coords(&us, strip_vs, |x, y| {
+        let xrad = PI * 2.0 * us[x];
+        let dir = Vec3::new(xrad.sin(), 0.0, xrad.cos());
+        let point = strip[y];
+        (
+          dir * point.position.x + Vec3::Y * point.position.y,
+          dir * point.normal.x + Vec3::Y * point.normal.y,
+        )
+      });
+      offset += strip.len();
+    }
+  }
+  fn new_arrow(xn: usize) -> Self {
+    let (shaft, head, head_y) = (0.025, 0.075, 0.8);
+    let mut builder = Self::default();
+    builder.lathe(
+      xn,
+      &[
+        ProfilePoint::new_strip(&[(0.0, 1.0), (head, head_y)], Vec2::new(1.0 - head_y, head)),
+        ProfilePoint::new_strip(&[(head, head_y), (shaft, head_y)], -Vec2::Y),
+        ProfilePoint::new_strip(&[(shaft, head_y), (shaft, 0.0)], Vec2::X),
+        ProfilePoint::new_strip(&[(shaft, 0.0), (0.0, 0.0)], -Vec2::Y),
+      ],
+    );
+    builder
+  }
+  fn transform(mut self, mat: Mat4) -> Self {
+    self.mesh.transform(mat);
+    self
+  }
+  fn build(mut self) -> Shape {
+    self.mesh.compute_tangents();
+    Shape::new_mesh(&self.mesh)
+  }
+  // 線などは tangent を計算しない
+  fn build_with_topology(self, topology: PrimitiveToporogy) -> Shape {
+    Shape::new_with_topology(to_vertices(&self.mesh), self.mesh.indices, topology)
   }
 }
@@ -3,5 +3,9 @@ mod material;
 pub use self::material::*;
 mod mesh;
 pub use self::mesh::*;
+mod morph;
+pub use self::morph::*;
+mod skin;
+pub use self::skin::*;
 mod transform;
 pub use self::transform::*;
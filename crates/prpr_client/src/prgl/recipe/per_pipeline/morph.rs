@@ -0,0 +1,169 @@
+use super::*;
+use prpr::animation::*;
+
+// 頂点属性の数の保証は 16 なので, 同時に効かせられる target は 4 つまで
+pub const MAX_MORPH_TARGETS: usize = 4;
+crate::shader_attr! {
+  struct MorphVertex {
+    morph_position0: vec3,
+    morph_position1: vec3,
+    morph_position2: vec3,
+    morph_position3: vec3,
+    morph_normal0: vec3,
+    morph_normal1: vec3,
+    morph_normal2: vec3,
+    morph_normal3: vec3,
+  }
+}
+crate::shader_attr! {
+  struct MorphAttribute {
+    morph_weights: vec4
+  }
+}
+// morph target (blend shape) の重みを UBO に持つ. 差分は Vao::add_stream で頂点側に足すこと
+pub struct Morph {
+  weights: Vec<f32>,
+  ubo: SOwner<UniformBuffer<MorphAttribute>>,
+}
+impl Morph {
+  pub fn new(weights: Vec<f32>) -> Self {
+    let mut result = Self {
+      weights,
+      ubo: SOwner::new(UniformBuffer::new(MorphAttribute::new())),
+    };
+    result.apply();
+    result
+  }
+  // 重みが全て 0. morph しないものに同じ shader を使うとき用
+  pub fn new_identity() -> Self {
+    Self::new(Vec::new())
+  }
+  // targets は target ごとの (位置の差分, 法線の差分). 空のものは 0 で埋める
+  pub fn new_stream(
+    vertex_count: usize,
+    targets: &[(&[Vec3], &[Vec3])],
+  ) -> VertexBuffer<MorphVertex> {
+    if targets.len() > MAX_MORPH_TARGETS {
+      log::error(format!(
+        "too many morph targets: {} (max: {})",
+        targets.len(),
+        MAX_MORPH_TARGETS
+      ));
+    }
+    let delta = |values: &[Vec3], i: usize| values.get(i).copied().unwrap_or(Vec3::ZERO);
+    let target = |k: usize| targets.get(k).copied().unwrap_or((&[], &[]));
+    let data = (0..vertex_count)
+      .map(|i| MorphVertex {
+        morph_position0: delta(target(0).0, i),
+        morph_position1: delta(target(1).0, i),
+        morph_position2: delta(target(2).0, i),
+        morph_position3: delta(target(3).0, i),
+        morph_normal0: delta(target(0).1, i),
+        morph_normal1: delta(target(1).1, i),
+        morph_normal2: delta(target(2).1, i),
+        morph_normal3: delta(target(3).1, i),
+      })
+      .collect();
+    VertexBuffer::new(data)
+  }
+  pub fn weights(&self) -> &[f32] {
+    &self.weights
+  }
+  // 書き換えたら apply すること
+  pub fn weights_mut(&mut self) -> &mut Vec<f32> {
+    &mut self.weights
+  }
+  pub fn apply(&mut self) {
+    let mut weights = [0.0; MAX_MORPH_TARGETS];
+    for (dst, src) in weights.iter_mut().zip(&self.weights) {
+      *dst = *src;
+    }
+    let mut ubo = self.ubo.write();
+    ubo.morph_weights = Vec4::from(weights);
+  }
+  // main より前に置く. MorphAttribute を attrs に入れること
+  // 差分の頂点入力の宣言も含む. stream のない Vao では差分は 0 になる
+  pub fn vs_helper_code() -> &'static str {
+    "in vec3 morph_position0;
+in vec3 morph_position1;
+in vec3 morph_position2;
+in vec3 morph_position3;
+in vec3 morph_normal0;
+in vec3 morph_normal1;
+in vec3 morph_normal2;
+in vec3 morph_normal3;
+vec3 morph_position(vec3 position) {
+  return position
+    + morph_position0 * morph_weights.x
+    + morph_position1 * morph_weights.y
+    + morph_position2 * morph_weights.z
+    + morph_position3 * morph_weights.w;
+}
+vec3 morph_normal(vec3 normal) {
+  return normalize(normal
+    + morph_normal0 * morph_weights.x
+    + morph_normal1 * morph_weights.y
+    + morph_normal2 * morph_weights.z
+    + morph_normal3 * morph_weights.w);
+}
+"
+  }
+}
+impl PipelineBindable for Morph {
+  fn bind_pipeline(&self, pipeline: &mut Pipeline) {
+    pipeline.add(&self.ubo);
+  }
+}
+
+// 毎フレーム update を呼ぶこと(Updater::own してもよい)
+pub struct MorphAnimator {
+  morph: Morph,
+  // 重みを変えない間の値
+  rest_weights: Vec<f32>,
+  player: Option<AnimationPlayer>,
+  pre_milli_sec: Option<f64>,
+}
+impl MorphAnimator {
+  pub fn new(morph: Morph) -> Self {
+    Self {
+      rest_weights: morph.weights().to_vec(),
+      morph,
+      player: None,
+      pre_milli_sec: None,
+    }
+  }
+  // clip の Weights channel のうち target が 0 のものを使う
+  pub fn play(&mut self, clip: AnimationClip, looping: bool) {
+    self.player = Some(AnimationPlayer::new(clip, looping));
+  }
+  // 最後の重みのまま止まる
+  pub fn stop(&mut self) {
+    self.player = None;
+  }
+  pub fn player_mut(&mut self) -> Option<&mut AnimationPlayer> {
+    self.player.as_mut()
+  }
+  pub fn morph(&self) -> &Morph {
+    &self.morph
+  }
+  pub fn morph_mut(&mut self) -> &mut Morph {
+    &mut self.morph
+  }
+}
+impl NeedUpdate for MorphAnimator {
+  fn update(&mut self) {
+    let now = Time::now_milli_sec();
+    let delta_sec = self
+      .pre_milli_sec
+      .map(|pre| (now - pre) / 1000.0)
+      .unwrap_or(0.0);
+    self.pre_milli_sec = Some(now);
+    if let Some(player) = &mut self.player {
+      player.advance(delta_sec as f32);
+      let weights = self.morph.weights_mut();
+      weights.clone_from(&self.rest_weights);
+      player.sample_weights(0, weights);
+      self.morph.apply();
+    }
+  }
+}
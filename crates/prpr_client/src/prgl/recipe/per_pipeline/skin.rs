@@ -0,0 +1,129 @@
+use super::*;
+use prpr::animation::*;
+use prpr::skeleton::*;
+
+// uniform block の大きさの保証は 16KB なので mat4 で 256 個まで. 半分にしておく
+pub const MAX_SKIN_JOINTS: usize = 128;
+crate::shader_attr! {
+  struct SkinAttribute {
+    joint_mats: mat4[128]
+  }
+}
+// joint ごとの行列を UBO に持つ. 頂点側は joints / weights (vec4) を持つこと
+pub struct Skin {
+  skeleton: Skeleton,
+  poses: Vec<Pose>,
+  ubo: SOwner<UniformBuffer<SkinAttribute>>,
+}
+impl Skin {
+  pub fn new(skeleton: Skeleton) -> Self {
+    if skeleton.joints.len() > MAX_SKIN_JOINTS {
+      log::error(format!(
+        "too many joints: {} (max: {})",
+        skeleton.joints.len(),
+        MAX_SKIN_JOINTS
+      ));
+    }
+    let mut result = Self {
+      poses: skeleton.rest_poses(),
+      skeleton,
+      ubo: SOwner::new(UniformBuffer::new(SkinAttribute::new())),
+    };
+    result.apply();
+    result
+  }
+  // 全て単位行列. スキニングしないものに同じ shader を使うとき用
+  pub fn new_identity() -> Self {
+    Self::new(Skeleton::default())
+  }
+  pub fn skeleton(&self) -> &Skeleton {
+    &self.skeleton
+  }
+  pub fn poses(&self) -> &[Pose] {
+    &self.poses
+  }
+  // 書き換えたら apply すること
+  pub fn poses_mut(&mut self) -> &mut [Pose] {
+    &mut self.poses
+  }
+  pub fn reset_poses(&mut self) {
+    self.poses = self.skeleton.rest_poses();
+  }
+  pub fn apply(&mut self) {
+    let matrices = self.skeleton.skinning_matrices(&self.poses);
+    let mut ubo = self.ubo.write();
+    for (dst, src) in ubo.joint_mats.iter_mut().zip(matrices) {
+      *dst = src;
+    }
+  }
+  // main より前に置く. SkinAttribute を attrs に入れること
+  // weights が全て 0 なら単位行列になる
+  pub fn vs_helper_code() -> &'static str {
+    "mat4 skin_mat(vec4 joints, vec4 weights) {
+  float total = dot(weights, vec4(1.0));
+  if (total <= 0.0) {
+    return mat4(1.0);
+  }
+  mat4 result = joint_mats[int(joints.x)] * weights.x
+    + joint_mats[int(joints.y)] * weights.y
+    + joint_mats[int(joints.z)] * weights.z
+    + joint_mats[int(joints.w)] * weights.w;
+  return result / total;
+}
+"
+  }
+}
+impl PipelineBindable for Skin {
+  fn bind_pipeline(&self, pipeline: &mut Pipeline) {
+    pipeline.add(&self.ubo);
+  }
+}
+
+// 毎フレーム update を呼ぶこと(Updater::own してもよい)
+pub struct SkinAnimator {
+  skin: Skin,
+  player: Option<AnimationPlayer>,
+  pre_milli_sec: Option<f64>,
+}
+impl SkinAnimator {
+  pub fn new(skin: Skin) -> Self {
+    Self {
+      skin,
+      player: None,
+      pre_milli_sec: None,
+    }
+  }
+  // clip の target は skeleton.joints の index
+  pub fn play(&mut self, clip: AnimationClip, looping: bool) {
+    self.player = Some(AnimationPlayer::new(clip, looping));
+  }
+  // 最後の姿勢のまま止まる
+  pub fn stop(&mut self) {
+    self.player = None;
+  }
+  pub fn player_mut(&mut self) -> Option<&mut AnimationPlayer> {
+    self.player.as_mut()
+  }
+  pub fn skin(&self) -> &Skin {
+    &self.skin
+  }
+  pub fn skin_mut(&mut self) -> &mut Skin {
+    &mut self.skin
+  }
+}
+impl NeedUpdate for SkinAnimator {
+  fn update(&mut self) {
+    let now = Time::now_milli_sec();
+    let delta_sec = self
+      .pre_milli_sec
+      .map(|pre| (now - pre) / 1000.0)
+      .unwrap_or(0.0);
+    self.pre_milli_sec = Some(now);
+    if let Some(player) = &mut self.player {
+      player.advance(delta_sec as f32);
+      self.skin.reset_poses();
+      player.sample(self.skin.poses_mut());
+      self.skin.apply();
+    }
+  }
+}
@@ -73,6 +73,12 @@ impl CameraData {
 }
 
 impl CameraData {
+  pub fn view_mat(&self) -> Mat4 {
+    Self::to_view_mat(self.camera_pos, self.camera_target_pos)
+  }
+  pub fn proj_mat(&self) -> Mat4 {
+    Self::to_proj_mat(self.fovy, self.aspect_ratio, self.near, self.far)
+  }
   fn to_view_mat(camera_pos: Vec3, target_pos: Vec3) -> Mat4 {
     Mat4::look_at_rh(camera_pos, target_pos, Vec3::Y)
   }
@@ -0,0 +1,216 @@
+use super::*;
+
+pub const MAX_LIGHTS: usize = 8;
+crate::shader_attr! {
+  struct LightAttribute {
+    // xyz: 位置, w: 種類(1: directional, 2: point, 3: spot)
+    light_positions: vec4[8]
+    // xyz: 光の進む向き, w: 届く距離(0 なら減衰だけで打ち切らない)
+    light_directions: vec4[8]
+    // rgb: 色 x 強さ
+    light_colors: vec4[8]
+    // x: spot の内側の cos, y: 外側の cos
+    light_cones: vec4[8]
+    // rgb: 環境光
+    ambient_color: vec4
+    light_count: float
+  }
+}
+
+// 強さの単位は glTF の KHR_lights_punctual に倣う
+#[derive(Clone, Copy, Debug)]
+pub struct DirectionalLight {
+  pub direction: Vec3,
+  pub color: Vec3,
+  pub intensity: f32,
+}
+#[derive(Clone, Copy, Debug)]
+pub struct PointLight {
+  pub position: Vec3,
+  pub color: Vec3,
+  pub intensity: f32,
+  // 0 なら無限
+  pub range: f32,
+}
+#[derive(Clone, Copy, Debug)]
+pub struct SpotLight {
+  pub position: Vec3,
+  pub direction: Vec3,
+  pub color: Vec3,
+  pub intensity: f32,
+  pub range: f32,
+  // ラジアン. 内側は最大の明るさで外側に向けて 0 になる
+  pub inner_cone_angle: f32,
+  pub outer_cone_angle: f32,
+}
+impl Default for DirectionalLight {
+  fn default() -> Self {
+    Self {
+      direction: Vec3::new(-1.0, -2.0, -1.0).normalize(),
+      color: Vec3::ONE,
+      // 正面から当たると拡散反射で base_color がそのまま出る強さ
+      intensity: std::f32::consts::PI,
+    }
+  }
+}
+impl Default for PointLight {
+  fn default() -> Self {
+    Self {
+      position: Vec3::ZERO,
+      color: Vec3::ONE,
+      intensity: 1.0,
+      range: 0.0,
+    }
+  }
+}
+impl Default for SpotLight {
+  fn default() -> Self {
+    Self {
+      position: Vec3::ZERO,
+      direction: -Vec3::Y,
+      color: Vec3::ONE,
+      intensity: 1.0,
+      range: 0.0,
+      inner_cone_angle: 0.0,
+      outer_cone_angle: std::f32::consts::FRAC_PI_4,
+    }
+  }
+}
+#[derive(Clone, Copy, Debug)]
+pub enum Light {
+  Directional(DirectionalLight),
+  Point(PointLight),
+  Spot(SpotLight),
+}
+
+pub struct LightData {
+  // MAX_LIGHTS を超えた分は無視される
+  pub lights: Vec<Light>,
+  pub ambient_color: Vec3,
+}
+impl Default for LightData {
+  fn default() -> Self {
+    Self {
+      lights: vec![Light::Directional(Default::default())],
+      ambient_color: Vec3::ONE * 0.03,
+    }
+  }
+}
+impl RefInto<LightAttribute> for LightData {
+  fn ref_into(&self) -> LightAttribute {
+    if self.lights.len() > MAX_LIGHTS {
+      log::error(format!(
+        "too many lights: {} (max: {})",
+        self.lights.len(),
+        MAX_LIGHTS
+      ));
+    }
+    let mut result = LightAttribute::new();
+    let mut count = 0;
+    for (i, light) in self.lights.iter().take(MAX_LIGHTS).enumerate() {
+      let (position, direction, color, cone) = match light {
+        Light::Directional(x) => (
+          Vec3::ZERO.extend(1.0),
+          x.direction.normalize_or_zero().extend(0.0),
+          x.color * x.intensity,
+          Vec4::ZERO,
+        ),
+        Light::Point(x) => (
+          x.position.extend(2.0),
+          Vec3::ZERO.extend(x.range),
+          x.color * x.intensity,
+          Vec4::ZERO,
+        ),
+        Light::Spot(x) => (
+          x.position.extend(3.0),
+          x.direction.normalize_or_zero().extend(x.range),
+          x.color * x.intensity,
+          Vec4::new(x.inner_cone_angle.cos(), x.outer_cone_angle.cos(), 0.0, 0.0),
+        ),
+      };
+      result.light_positions[i] = position;
+      result.light_directions[i] = direction;
+      result.light_colors[i] = color.extend(0.0);
+      result.light_cones[i] = cone;
+      count += 1;
+    }
+    result.ambient_color = self.ambient_color.extend(0.0);
+    result.light_count = count as f32;
+    result
+  }
+}
+// RenderPass に add する
+pub type Lights = IntoUniformBufferTemplate<LightAttribute, LightData>;
+impl LightData {
+  // main より前に置く. LightAttribute を attrs に入れること
+  // 位置や向きは全てワールド座標. pbr_lighting が光源と環境光を足した色を返す
+  pub fn fs_helper_code() -> &'static str {
+    "const float PBR_PI = 3.14159265;
+float distribution_ggx(float n_dot_h, float alpha) {
+  float a2 = alpha * alpha;
+  float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+  return a2 / (PBR_PI * d * d);
+}
+float visibility_smith_ggx(float n_dot_l, float n_dot_v, float alpha) {
+  float a2 = alpha * alpha;
+  float lambda_l = n_dot_v * sqrt(n_dot_l * n_dot_l * (1.0 - a2) + a2);
+  float lambda_v = n_dot_l * sqrt(n_dot_v * n_dot_v * (1.0 - a2) + a2);
+  float g = lambda_l + lambda_v;
+  return g > 0.0 ? 0.5 / g : 0.0;
+}
+vec3 fresnel_schlick(vec3 f0, float v_dot_h) {
+  return f0 + (1.0 - f0) * pow(1.0 - v_dot_h, 5.0);
+}
+// l: 表面から光源への向き. radiance: 届く光
+vec3 cook_torrance(vec3 n, vec3 v, vec3 l, vec3 radiance, vec3 base_color, float metallic, float roughness) {
+  vec3 h = normalize(l + v);
+  float n_dot_l = clamp(dot(n, l), 0.0, 1.0);
+  float n_dot_v = clamp(abs(dot(n, v)), 0.001, 1.0);
+  float n_dot_h = clamp(dot(n, h), 0.0, 1.0);
+  float v_dot_h = clamp(dot(v, h), 0.0, 1.0);
+  // roughness が 0 に近いと GGX が発散して点光源のハイライトが消えたり白飛びしたりする
+  float r = clamp(roughness, 0.04, 1.0);
+  float alpha = r * r;
+  vec3 f0 = mix(vec3(0.04), base_color, metallic);
+  vec3 f = fresnel_schlick(f0, v_dot_h);
+  vec3 diffuse = (1.0 - f) * (1.0 - metallic) * base_color / PBR_PI;
+  vec3 specular = f * distribution_ggx(n_dot_h, alpha) * visibility_smith_ggx(n_dot_l, n_dot_v, alpha);
+  return (diffuse + specular) * radiance * n_dot_l;
+}
+// i 番目の光源. 届く光を返し, l に光源への向きを入れる
+vec3 light_radiance(int i, vec3 position, out vec3 l) {
+  vec4 p = light_positions[i];
+  vec4 d = light_directions[i];
+  vec3 color = light_colors[i].rgb;
+  if (p.w < 1.5) {
+    l = -d.xyz;
+    return color;
+  }
+  vec3 to_light = p.xyz - position;
+  float dist2 = max(dot(to_light, to_light), 0.0001);
+  l = to_light * inversesqrt(dist2);
+  float attenuation = 1.0 / dist2;
+  if (d.w > 0.0) {
+    float r = dist2 / (d.w * d.w);
+    float fade = clamp(1.0 - r * r, 0.0, 1.0);
+    attenuation *= fade * fade;
+  }
+  if (p.w > 2.5) {
+    vec2 cone = light_cones[i].xy;
+    float cd = dot(d.xyz, -l);
+    attenuation *= smoothstep(cone.y, max(cone.x, cone.y + 0.0001), cd);
+  }
+  return color * attenuation;
+}
+vec3 pbr_lighting(vec3 position, vec3 n, vec3 v, vec3 base_color, float metallic, float roughness, float occlusion) {
+  vec3 result = ambient_color.rgb * base_color * occlusion;
+  for (int i = 0; i < int(light_count); i++) {
+    vec3 l;
+    vec3 radiance = light_radiance(i, position, l);
+    result += cook_torrance(n, v, l, radiance, base_color, metallic, roughness);
+  }
+  return result;
+}
+"
+  }
+}
@@ -1,5 +1,11 @@
 use super::*;
 mod camera;
 pub use self::camera::*;
+mod light;
+pub use self::light::*;
+mod shadow;
+pub use self::shadow::*;
 mod surface;
 pub use self::surface::*;
+mod picking;
+pub use self::picking::*;
@@ -0,0 +1,210 @@
+use super::*;
+use std::collections::HashMap;
+
+// UBO には uint の型がないので float で渡す. 正確なのは 2^24 まで
+pub const MAX_PICKING_ID: u32 = 1 << 24;
+crate::shader_attr! {
+  struct PickingAttribute {
+    picking_id: float,
+    picking_dummy1: float,
+    picking_dummy2: vec2,
+  }
+}
+
+// 0 は何もないことを表す
+static INSTANCE: OnceCell<MRwLock<PickingImpl>> = OnceCell::new();
+unsafe impl Send for PickingImpl {}
+unsafe impl Sync for PickingImpl {}
+pub struct PickingImpl {
+  next_id: u32,
+  pipelines: HashMap<u32, SWeakReader<Pipeline>>,
+  hovered: u32,
+  pre_hovered: u32,
+  clicked: u32,
+}
+impl PickingImpl {
+  pub fn initialize_global() {
+    INSTANCE.set(MRwLock::new(Self::new())).ok();
+  }
+  pub fn read_global() -> MDerefable<'static, Self> {
+    INSTANCE
+      .get()
+      .expect("Picking global not initialized")
+      .read()
+  }
+  pub fn write_global() -> MDerefMutable<'static, Self> {
+    INSTANCE
+      .get()
+      .expect("Picking global not initialized")
+      .write()
+  }
+  fn new() -> Self {
+    Self {
+      next_id: 1,
+      pipelines: HashMap::new(),
+      hovered: 0,
+      pre_hovered: 0,
+      clicked: 0,
+    }
+  }
+  // 使い切ったら空いている id を 1 から探して使い直す. 空きがなければ 0
+  fn register(&mut self, pipeline: &dyn SReaderTrait<Pipeline>) -> u32 {
+    if self.pipelines.len() >= MAX_PICKING_ID as usize {
+      log::error(format!("too many picking ids (max: {})", MAX_PICKING_ID));
+      return 0;
+    }
+    while self.pipelines.contains_key(&self.next_id) {
+      self.next_id = self.next_id % MAX_PICKING_ID + 1;
+    }
+    let id = self.next_id;
+    self.next_id = self.next_id % MAX_PICKING_ID + 1;
+    self.pipelines.insert(id, pipeline.clone_weak_reader());
+    id
+  }
+  fn unregister(&mut self, id: u32) {
+    self.pipelines.remove(&id);
+  }
+  fn pipeline(&self, id: u32) -> Option<SReader<Pipeline>> {
+    self.pipelines.get(&id).and_then(|p| p.try_read())
+  }
+  fn as_option(id: u32) -> Option<u32> {
+    if id == 0 {
+      None
+    } else {
+      Some(id)
+    }
+  }
+}
+
+// ユーザーはこちらから参照する
+pub struct Picking {}
+impl Picking {
+  pub fn hovered_id() -> Option<u32> {
+    PickingImpl::as_option(PickingImpl::read_global().hovered)
+  }
+  pub fn clicked_id() -> Option<u32> {
+    PickingImpl::as_option(PickingImpl::read_global().clicked)
+  }
+  pub fn hovered_pipeline() -> Option<SReader<Pipeline>> {
+    let picking = PickingImpl::read_global();
+    picking.pipeline(picking.hovered)
+  }
+  pub fn clicked_pipeline() -> Option<SReader<Pipeline>> {
+    let picking = PickingImpl::read_global();
+    picking.pipeline(picking.clicked)
+  }
+}
+
+// Pipeline ごとの id. shader では uint(picking_id) を out_id に書く
+pub struct PickingId {
+  id: u32,
+  ubo: SOwner<UniformBuffer<PickingAttribute>>,
+}
+impl PickingId {
+  pub fn new(pipeline: &dyn SReaderTrait<Pipeline>) -> Self {
+    let id = PickingImpl::write_global().register(pipeline);
+    Self {
+      id,
+      ubo: SOwner::new(UniformBuffer::new(PickingAttribute {
+        picking_id: id as f32,
+        ..Default::default()
+      })),
+    }
+  }
+  pub fn id(&self) -> u32 {
+    self.id
+  }
+  pub fn is_hovered(&self) -> bool {
+    self.id != 0 && PickingImpl::read_global().hovered == self.id
+  }
+  pub fn is_hover_entered(&self) -> bool {
+    let picking = PickingImpl::read_global();
+    self.id != 0 && picking.hovered == self.id && picking.pre_hovered != self.id
+  }
+  pub fn is_hover_left(&self) -> bool {
+    let picking = PickingImpl::read_global();
+    self.id != 0 && picking.hovered != self.id && picking.pre_hovered == self.id
+  }
+  pub fn is_clicked(&self) -> bool {
+    self.id != 0 && PickingImpl::read_global().clicked == self.id
+  }
+}
+impl PipelineBindable for PickingId {
+  fn bind_pipeline(&self, pipeline: &mut Pipeline) {
+    pipeline.add(&self.ubo);
+  }
+}
+impl Drop for PickingId {
+  fn drop(&mut self) {
+    if self.id != 0 {
+      PickingImpl::write_global().unregister(self.id);
+    }
+  }
+}
+
+// RenderPass に R32UI の id バッファを追加して、マウス下の id を非同期に読む
+pub struct PickingTarget {
+  texture: SOwner<Texture>,
+  pending: Option<PendingReadback<u32>>,
+}
+impl PickingTarget {
+  // テクスチャが GPU の上限を超えるか, slot に付けられなければ None
+  pub fn new(renderpass: &mut RenderPass, slot: i32) -> Option<Self> {
+    let max_viewport = system::WholeScreen::max_viewport();
+    // 整数テクスチャは mipmap を作れない
+    let texture = SOwner::new(Texture::new_uninitialized(&Texture2dDescriptor {
+      width: max_viewport.width as usize,
+      height: max_viewport.height as usize,
+      format: PixelFormat::R32UI,
+      mipmap: false,
+    })?);
+    renderpass
+      .set_color_target_by_slot(Some(&texture), slot)
+      .ok()?;
+    renderpass.set_clear_uint_by_slot(Some(UVec4::ZERO), slot);
+    Some(Self {
+      texture,
+      pending: None,
+    })
+  }
+  pub fn texture(&self) -> &SOwner<Texture> {
+    &self.texture
+  }
+  fn mouse_position() -> Rect<i32> {
+    let viewport = system::WholeScreen::viewport();
+    let x = input::Mouse::x() + viewport.width / 2;
+    // 上からの pixel 行を readback 用に下からの行へ (height - 1 - y)
+    let top = viewport.height / 2 - input::Mouse::y();
+    let y = viewport.height - 1 - top;
+    Rect::new(
+      viewport.x + x.clamp(0, viewport.width - 1),
+      viewport.y + y.clamp(0, viewport.height - 1),
+      1,
+      1,
+    )
+  }
+}
+impl NeedUpdate for PickingTarget {
+  fn update(&mut self) {
+    let mut picking = PickingImpl::write_global();
+    picking.pre_hovered = picking.hovered;
+    if let Some(pending) = &self.pending {
+      if pending.is_lost() {
+        self.pending = None;
+      } else if let Some(pixels) = pending.poll() {
+        picking.hovered = pixels.get(0, 0)[0];
+        self.pending = None;
+      }
+    }
+    picking.clicked = if input::Mouse::state(input::MouseState::IsLeftClicked) {
+      picking.hovered
+    } else {
+      0
+    };
+    // context lost 中は読めないので restore を待つ
+    if self.pending.is_none() && !Instance::is_context_lost() {
+      let rect = Self::mouse_position();
+      self.pending = self.texture.read().read_pixels_async(&rect);
+    }
+  }
+}
@@ -0,0 +1,290 @@
+use super::*;
+
+pub const MAX_SHADOW_CASCADES: usize = 4;
+// spot light の影の near(range に対する割合)
+const SPOT_NEAR_RATIO: f32 = 0.05;
+crate::shader_attr! {
+  struct ShadowAttribute {
+    // cascade ごとの ワールド座標 -> ライトのクリップ座標
+    shadow_mats: mat4[4]
+    // cascade ごとの遠端(カメラからの奥行き)
+    shadow_splits: vec4
+    // x: cascade の数, y: 未使用, z: PCF の半径(texel), w: 1 texel の大きさ
+    shadow_params: vec4
+    // LightData.lights の何番目の光源の影か
+    shadow_light_index: float
+    shadow_dummy1: float
+    shadow_dummy2: vec2
+  }
+  mapping ShadowMapping {
+    shadow_map: sampler2DArrayShadow,
+  }
+}
+// 影を落とすもの(caster)をライトから描くときの view_proj
+crate::shader_attr! {
+  struct ShadowCasterAttribute {
+    shadow_caster_mat: mat4
+  }
+}
+
+// directional / spot light の影. cascade ごとに depth の 2d array の layer に描く
+// directional はカメラの視錐台を奥行きで分割し, それぞれを囲む正射影で描く(spot は 1 枚)
+// 毎フレーム update を呼ぶこと. 影を受ける RenderPass には add し, renderpasses は RenderPassExecuter で先に実行すること
+pub struct ShadowMap {
+  light_index: usize,
+  size: usize,
+  cascade_count: usize,
+  // カメラからこの距離までに影を落とす(directional 用)
+  pub shadow_distance: f32,
+  // 分割の仕方. 0 なら等間隔, 1 なら対数
+  pub split_lambda: f32,
+  // caster を描くときの polygon offset. 面の傾きに応じてずらして shadow acne を防ぐ
+  // 固定の値を window depth で引くと spot の透視投影では遠くほど効きすぎるので使わない
+  pub caster_offset: PolygonOffset,
+  // 0 なら比較 1 回(LINEAR なので 2x2 は補間される)
+  pub pcf_radius: usize,
+  depth: SOwner<Texture>,
+  renderpasses: Vec<SOwner<RenderPass>>,
+  caster_ubos: Vec<SOwner<UniformBuffer<ShadowCasterAttribute>>>,
+  ubo: SOwner<UniformBuffer<ShadowAttribute>>,
+  mapping: SOwner<TextureMapping<ShadowMapping>>,
+}
+impl ShadowMap {
+  // light_index は LightData.lights の index. spot なら cascade_count は無視して 1 枚
+  // size が GPU の上限を超えていれば None
+  pub fn new(light_index: usize, size: usize, cascade_count: usize) -> Option<Self> {
+    let cascade_count = cascade_count.clamp(1, MAX_SHADOW_CASCADES);
+    let mut depth = Texture::new_layered_uninitialized(&TextureDescriptor::new_array(
+      size,
+      size,
+      cascade_count,
+      PixelFormat::Depth24,
+      false,
+    ))?;
+    depth.apply_sampler(&Sampler::new_shadow());
+    let depth = SOwner::new(depth);
+    let mut renderpasses = Vec::new();
+    let mut caster_ubos = Vec::new();
+    for i in 0..cascade_count {
+      let caster_ubo = SOwner::new(UniformBuffer::new(ShadowCasterAttribute::new()));
+      let mut renderpass = RenderPass::new();
+      renderpass.set_depth_attachment(Some(RenderTarget::new(&depth).with_layer(i)));
+      renderpass.set_clear_depth(Some(1.0));
+      renderpass.add_uniform_buffer(&caster_ubo);
+      renderpasses.push(SOwner::new(renderpass));
+      caster_ubos.push(caster_ubo);
+    }
+    let mapping = SOwner::new(TextureMapping::new(ShadowMapping {
+      shadow_map: depth.clone_reader(),
+    }));
+    Some(Self {
+      light_index,
+      size,
+      cascade_count,
+      shadow_distance: 50.0,
+      split_lambda: 0.7,
+      caster_offset: PolygonOffset::new(2.0, 4.0),
+      pcf_radius: 1,
+      depth,
+      renderpasses,
+      caster_ubos,
+      ubo: SOwner::new(UniformBuffer::new(ShadowAttribute::new())),
+      mapping,
+    })
+  }
+  // caster の shader. TransformAttribute と ShapeVertex を使う. 色は書かない
+  pub fn caster_shader() -> ShaderTemplate {
+    crate::shader_template! {
+      attrs: [ShadowCasterAttribute, TransformAttribute],
+      vs_attr: ShapeVertex,
+      vs_code: {
+        void main() {
+          gl_Position = shadow_caster_mat * model_mat * vec4(position, 1.0);
+        }
+      },
+      fs_attr: {},
+      fs_code: {
+        void main() {
+        }
+      }
+    }
+  }
+  // caster_shader 用の Pipeline. caster_offset を polygon offset にしたもの
+  pub fn new_caster_pipeline(&self) -> Pipeline {
+    let mut pipeline = Pipeline::new();
+    pipeline.set_polygon_offset(Some(self.caster_offset));
+    pipeline
+  }
+  // caster_shader を使った Pipeline を全ての cascade で描く
+  // new_caster_pipeline で作るか, caster_offset を set_polygon_offset しておくこと
+  pub fn add_caster(&mut self, pipeline: &dyn SReaderTrait<Pipeline>) {
+    for renderpass in &mut self.renderpasses {
+      renderpass.write().add_pipeline(pipeline);
+    }
+  }
+  pub fn renderpasses(&self) -> &[SOwner<RenderPass>] {
+    &self.renderpasses
+  }
+  pub fn depth_texture(&self) -> SReader<Texture> {
+    self.depth.clone_reader()
+  }
+  pub fn update(&mut self, camera: &CameraData, lights: &LightData) {
+    let cascades = match lights.lights.get(self.light_index) {
+      Some(Light::Directional(light)) => self.directional_cascades(camera, light),
+      Some(Light::Spot(light)) => vec![(Self::spot_mat(light, self.shadow_distance), f32::MAX)],
+      _ => {
+        log::error(format!(
+          "light {} is not a directional or spot light",
+          self.light_index
+        ));
+        Vec::new()
+      }
+    };
+    for (caster_ubo, (mat, _)) in self.caster_ubos.iter_mut().zip(&cascades) {
+      caster_ubo.write().shadow_caster_mat = *mat;
+    }
+    // 使わない cascade は描かない
+    for (i, renderpass) in self.renderpasses.iter_mut().enumerate() {
+      renderpass.write().set_disabled(i >= cascades.len(), 0);
+    }
+    let mut ubo = self.ubo.write();
+    let mut splits = [0.0; MAX_SHADOW_CASCADES];
+    for (i, (mat, split)) in cascades.iter().enumerate() {
+      ubo.shadow_mats[i] = *mat;
+      splits[i] = *split;
+    }
+    ubo.shadow_splits = Vec4::from(splits);
+    ubo.shadow_params = Vec4::new(
+      cascades.len() as f32,
+      0.0,
+      self.pcf_radius as f32,
+      1.0 / self.size as f32,
+    );
+    ubo.shadow_light_index = self.light_index as f32;
+  }
+  // (view_proj, 遠端)
+  fn directional_cascades(
+    &self,
+    camera: &CameraData,
+    light: &DirectionalLight,
+  ) -> Vec<(Mat4, f32)> {
+    let near = camera.near;
+    let far = camera.far.min(self.shadow_distance).max(near);
+    let n = self.cascade_count;
+    let split = |i: usize| {
+      let t = i as f32 / n as f32;
+      let log = near * (far / near).powf(t);
+      let uniform = near + (far - near) * t;
+      log * self.split_lambda + uniform * (1.0 - self.split_lambda)
+    };
+    let inv_view = camera.view_mat().inverse();
+    let tan_y = (camera.fovy * 0.5).tan();
+    let tan_x = tan_y * camera.aspect_ratio;
+    let direction = light.direction.normalize_or_zero();
+    let up = if direction.y.abs() > 0.99 {
+      Vec3::X
+    } else {
+      Vec3::Y
+    };
+    (0..n)
+      .map(|i| {
+        let (z0, z1) = (split(i), split(i + 1));
+        // 分割した視錐台を囲む球. 向きによらず同じ大きさなのでカメラが回っても影がちらつかない
+        let k2 = tan_x * tan_x + tan_y * tan_y;
+        let center_z = ((z0 + z1) * (1.0 + k2) * 0.5).min(z1);
+        let radius = ((z1 - center_z).powi(2) + k2 * z1 * z1).sqrt();
+        let radius = (radius * 16.0).ceil() / 16.0;
+        let center = inv_view.transform_point3(-Vec3::Z * center_z);
+        // 視錐台の外の caster も描けるようにライト側へ伸ばす
+        let back = radius + self.shadow_distance;
+        let view = Mat4::look_at_rh(center - direction * back, center, up);
+        let mut proj =
+          Mat4::orthographic_rh_gl(-radius, radius, -radius, radius, 0.0, back + radius);
+        // texel 単位に揃えて移動によるちらつきを抑える
+        let half_size = self.size as f32 * 0.5;
+        let origin = (proj * view).transform_point3(Vec3::ZERO) * half_size;
+        let offset = (origin.round() - origin) / half_size;
+        proj.w_axis.x += offset.x;
+        proj.w_axis.y += offset.y;
+        (proj * view, z1)
+      })
+      .collect()
+  }
+  fn spot_mat(light: &SpotLight, shadow_distance: f32) -> Mat4 {
+    let direction = light.direction.normalize_or_zero();
+    let up = if direction.y.abs() > 0.99 {
+      Vec3::X
+    } else {
+      Vec3::Y
+    };
+    let far = if light.range > 0.0 {
+      light.range
+    } else {
+      shadow_distance
+    };
+    let view = Mat4::look_at_rh(light.position, light.position + direction, up);
+    let fovy = (light.outer_cone_angle * 2.0 + 0.1).min(std::f32::consts::PI - 0.1);
+    // near が小さすぎると depth の精度が手前に偏り, 遠くの影が潰れる
+    let proj = Mat4::perspective_rh_gl(fovy, 1.0, far * SPOT_NEAR_RATIO, far);
+    proj * view
+  }
+  // main より前に置く. CameraAttribute, ShadowAttribute, ShadowMapping を attrs に入れること
+  // shadow_factor は光が当たるなら 1, 影なら 0
+  // pbr_lighting_with_shadow は LightData::fs_helper_code の pbr_lighting の影つき版(そちらより後に置く)
+  pub fn fs_helper_code() -> &'static str {
+    "float shadow_pcf(int cascade, vec3 coord) {
+  int radius = int(shadow_params.z);
+  float texel = shadow_params.w;
+  float sum = 0.0;
+  float count = 0.0;
+  for (int x = -radius; x <= radius; x++) {
+    for (int y = -radius; y <= radius; y++) {
+      vec2 uv = coord.xy + vec2(float(x), float(y)) * texel;
+      sum += texture(shadow_map, vec4(uv, float(cascade), coord.z));
+      count += 1.0;
+    }
+  }
+  return sum / count;
+}
+float shadow_factor(vec3 world_position) {
+  int count = int(shadow_params.x);
+  float view_depth = -(view_mat * vec4(world_position, 1.0)).z;
+  int cascade = -1;
+  for (int i = 0; i < count; i++) {
+    if (view_depth < shadow_splits[i]) {
+      cascade = i;
+      break;
+    }
+  }
+  if (cascade < 0) {
+    return 1.0;
+  }
+  vec4 p = shadow_mats[cascade] * vec4(world_position, 1.0);
+  vec3 coord = p.xyz / p.w * 0.5 + 0.5;
+  if (any(lessThan(coord, vec3(0.0))) || any(greaterThan(coord, vec3(1.0)))) {
+    return 1.0;
+  }
+  return shadow_pcf(cascade, coord);
+}
+vec3 pbr_lighting_with_shadow(vec3 position, vec3 n, vec3 v, vec3 base_color, float metallic, float roughness, float occlusion) {
+  vec3 result = ambient_color.rgb * base_color * occlusion;
+  int shadow_light = int(shadow_light_index);
+  for (int i = 0; i < int(light_count); i++) {
+    vec3 l;
+    vec3 radiance = light_radiance(i, position, l);
+    if (i == shadow_light) {
+      radiance *= shadow_factor(position);
+    }
+    result += cook_torrance(n, v, l, radiance, base_color, metallic, roughness);
+  }
+  return result;
+}
+"
+  }
+}
+impl RenderPassBindable for ShadowMap {
+  fn bind_renderpass(&self, renderpass: &mut RenderPass) {
+    renderpass.add_uniform_buffer(&self.ubo);
+    renderpass.add_texture_mapping(&self.mapping);
+  }
+}
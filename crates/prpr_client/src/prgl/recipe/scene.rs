@@ -0,0 +1,195 @@
+use super::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct SceneNodeId(usize);
+
+struct SceneNode {
+  name: String,
+  local: TransformData,
+  parent: Option<SceneNodeId>,
+  children: Vec<SceneNodeId>,
+  world_mat: Mat4,
+  // local が変わった. 子孫も計算し直す
+  is_dirty: bool,
+  // pipelines に bind してある. world_mat を入れる
+  transform: UniformBufferTemplate<TransformAttribute>,
+  pipelines: Vec<SOwner<Pipeline>>,
+}
+
+// 親子関係のある Transform. ワールド行列は 親のワールド行列 x 自分の local
+// 毎フレーム update を呼ぶこと(Updater::own してもよい). 変更のあった部分木だけ計算し直す
+pub struct Scene {
+  // 削除したところは None
+  nodes: Vec<Option<SceneNode>>,
+  roots: Vec<SceneNodeId>,
+}
+impl Scene {
+  pub fn new() -> Self {
+    Self {
+      nodes: Vec::new(),
+      roots: Vec::new(),
+    }
+  }
+  fn node(&self, id: SceneNodeId) -> Option<&SceneNode> {
+    self.nodes.get(id.0).and_then(|x| x.as_ref())
+  }
+  fn node_mut(&mut self, id: SceneNodeId) -> Option<&mut SceneNode> {
+    self.nodes.get_mut(id.0).and_then(|x| x.as_mut())
+  }
+  fn siblings_mut(&mut self, parent: Option<SceneNodeId>) -> &mut Vec<SceneNodeId> {
+    match parent {
+      Some(parent) => &mut self.nodes[parent.0].as_mut().unwrap().children,
+      None => &mut self.roots,
+    }
+  }
+  pub fn add_node(&mut self, name: &str, parent: Option<SceneNodeId>) -> SceneNodeId {
+    let parent = parent.filter(|p| self.node(*p).is_some());
+    let id = SceneNodeId(self.nodes.len());
+    self.nodes.push(Some(SceneNode {
+      name: name.to_string(),
+      local: TransformData::default(),
+      parent,
+      children: Vec::new(),
+      world_mat: Mat4::IDENTITY,
+      is_dirty: true,
+      transform: UniformBufferTemplate::new(),
+      pipelines: Vec::new(),
+    }));
+    self.siblings_mut(parent).push(id);
+    id
+  }
+  // 子孫ごと消す. 持っていた Pipeline も破棄されるので RenderPass からも外れる
+  pub fn remove_node(&mut self, id: SceneNodeId) {
+    let parent = match self.node(id) {
+      Some(node) => node.parent,
+      None => return,
+    };
+    self.siblings_mut(parent).retain(|x| *x != id);
+    let mut stack = vec![id];
+    while let Some(id) = stack.pop() {
+      if let Some(node) = self.nodes[id.0].take() {
+        stack.extend(node.children);
+      }
+    }
+  }
+  // 自分の子孫を親にしようとしたら何もしない
+  pub fn set_parent(&mut self, id: SceneNodeId, parent: Option<SceneNodeId>) {
+    let old_parent = match self.node(id) {
+      Some(node) => node.parent,
+      None => return,
+    };
+    if let Some(parent) = parent {
+      let mut current = Some(parent);
+      while let Some(c) = current {
+        if c == id {
+          log::error("scene node can not be a child of its descendant");
+          return;
+        }
+        current = match self.node(c) {
+          Some(node) => node.parent,
+          None => {
+            log::error("scene node parent does not exist");
+            return;
+          }
+        };
+      }
+    }
+    self.siblings_mut(old_parent).retain(|x| *x != id);
+    self.siblings_mut(parent).push(id);
+    let node = self.node_mut(id).unwrap();
+    node.parent = parent;
+    node.is_dirty = true;
+  }
+  pub fn parent(&self, id: SceneNodeId) -> Option<SceneNodeId> {
+    self.node(id).and_then(|x| x.parent)
+  }
+  pub fn children(&self, id: SceneNodeId) -> &[SceneNodeId] {
+    self.node(id).map(|x| &x.children[..]).unwrap_or(&[])
+  }
+  pub fn roots(&self) -> &[SceneNodeId] {
+    &self.roots
+  }
+  pub fn name(&self, id: SceneNodeId) -> Option<&str> {
+    self.node(id).map(|x| x.name.as_str())
+  }
+  pub fn find(&self, name: &str) -> Option<SceneNodeId> {
+    self
+      .nodes
+      .iter()
+      .position(|x| matches!(x, Some(node) if node.name == name))
+      .map(SceneNodeId)
+  }
+  pub fn local(&self, id: SceneNodeId) -> Option<&TransformData> {
+    self.node(id).map(|x| &x.local)
+  }
+  // 書き換えると次の update で子孫ごと計算し直す
+  pub fn local_mut(&mut self, id: SceneNodeId) -> Option<&mut TransformData> {
+    self.node_mut(id).map(|x| {
+      x.is_dirty = true;
+      &mut x.local
+    })
+  }
+  pub fn set_local(&mut self, id: SceneNodeId, local: TransformData) {
+    if let Some(x) = self.local_mut(id) {
+      *x = local;
+    }
+  }
+  // 最後に update したときのもの
+  pub fn world_matrix(&self, id: SceneNodeId) -> Option<Mat4> {
+    self.node(id).map(|x| x.world_mat)
+  }
+  // ワールド行列の入った TransformAttribute を bind して持つ. RenderPass には返り値を add すること
+  pub fn add_pipeline(
+    &mut self,
+    id: SceneNodeId,
+    mut pipeline: Pipeline,
+  ) -> Option<SReader<Pipeline>> {
+    let node = self.node_mut(id)?;
+    pipeline.add(&node.transform);
+    let pipeline = SOwner::new(pipeline);
+    let reader = pipeline.clone_reader();
+    node.pipelines.push(pipeline);
+    Some(reader)
+  }
+  pub fn pipelines(&self, id: SceneNodeId) -> &[SOwner<Pipeline>] {
+    self.node(id).map(|x| &x.pipelines[..]).unwrap_or(&[])
+  }
+  pub fn clear_pipelines(&mut self, id: SceneNodeId) {
+    if let Some(node) = self.node_mut(id) {
+      node.pipelines.clear();
+    }
+  }
+}
+impl NeedUpdate for Scene {
+  fn update(&mut self) {
+    // (node, 親のワールド行列, 祖先が計算し直されたか)
+    let mut stack: Vec<(SceneNodeId, Mat4, bool)> = self
+      .roots
+      .iter()
+      .map(|id| (*id, Mat4::IDENTITY, false))
+      .collect();
+    while let Some((id, parent_mat, parent_changed)) = stack.pop() {
+      let node = match self.node_mut(id) {
+        Some(node) => node,
+        None => continue,
+      };
+      let changed = parent_changed || node.is_dirty;
+      if changed {
+        let local = &node.local;
+        node.world_mat = parent_mat
+          * Mat4::from_scale_rotation_translation(local.scale, local.rotation, local.translate);
+        node.transform.write().model_mat = node.world_mat;
+        node.is_dirty = false;
+      }
+      let world_mat = node.world_mat;
+      for child in &node.children {
+        stack.push((*child, world_mat, changed));
+      }
+    }
+  }
+}
+impl Default for Scene {
+  fn default() -> Self {
+    Self::new()
+  }
+}
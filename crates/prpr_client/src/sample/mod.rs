@@ -5,9 +5,10 @@ use prhtml;
 
 #[derive(Default)]
 pub struct CasualObject {
-  // Transformは親子関係をつけたい
+  // 親子関係をつけるなら Scene を使う
   pub transform: TransformWhy,
   pub pipeline: SOwner<Pipeline>,
+  pub picking_id: Option<PickingId>,
   // gl_Position は書くので、それをTransformFeedbackする
   // - Selection ができる
   // - オフスクリーンやUI上に書くときは？
@@ -20,7 +21,9 @@ struct CasualScene {
   objects: Vec<CasualObject>,
   renderpass: SOwner<RenderPass>,
   camera: Camera,
+  _lights: Lights,
   out_color: SOwner<Texture>,
+  picking: PickingTarget,
 }
 enum CasualRenderPassOrder {
   Scene,
@@ -28,29 +31,36 @@ enum CasualRenderPassOrder {
 }
 impl CasualScene {
   pub fn shader() -> ShaderTemplate {
-    crate::shader_template! {
+    let mut result = crate::shader_template! {
       attrs: [
-        CameraAttribute, TransformAttribute, PbrAttribute,
-        PbrMapping
+        CameraAttribute, TransformAttribute, LightAttribute,
+        PbrAttribute, PbrMapping, PickingAttribute
       ],
       vs_attr: ShapeVertex,
       vs_code: {
         void main() {
-          mat4 mvp_mat = view_proj_mat * model_mat;
-          gl_Position = mvp_mat * vec4(position, 1.0);
-          mat4 it_mvp_mat = transpose(inverse(mvp_mat));
-          in_normal = (it_mvp_mat * vec4(normal, 0.0)).xyz;
+          vec4 world_position = model_mat * vec4(position, 1.0);
+          gl_Position = view_proj_mat * world_position;
+          in_position = world_position.xyz;
+          in_normal = transpose(inverse(mat3(model_mat))) * normal;
         }
       },
-      fs_attr: { in_normal: vec3 },
+      fs_attr: { in_position: vec3, in_normal: vec3 },
       fs_code: {
         void main() {
-          // texture(normal_map, vec2(0.5, 0.5)).rgb;
-          out_color = vec4(in_normal + 0.5, 1.0);
+          vec3 n = normalize(in_normal);
+          vec3 v = normalize(camera_pos - in_position);
+          vec3 color = pbr_lighting(
+            in_position, n, v, base_color_factor.rgb, metallic_factor, roughness_factor, 1.0
+          );
+          out_color = vec4(pow(color, vec3(1.0 / 2.2)), 1.0);
+          out_id = uint(picking_id);
         }
       }
-      out_attr: { out_color: vec4 }
-    }
+      out_attr: { out_color: vec4, out_id: uint }
+    };
+    result.add_fs_helper(LightData::fs_helper_code());
+    result
   }
   pub fn new() -> Self {
     // renderpass
@@ -60,15 +70,27 @@ impl CasualScene {
     renderpass.set_clear_color(Some(Vec4::new(1.0, 1.0, 1.0, 0.0)));
     renderpass.set_clear_depth(Some(1.0));
     renderpass.add(&camera);
-    let out_color = TextureRecipe::new_fullscreen(PixelFormat::R8G8B8A8);
+    let lights = Lights::new();
+    renderpass.add(&lights);
+    let out_color = TextureRecipe::new_fullscreen(PixelFormat::R8G8B8A8)
+      .expect("failed to create the scene color target");
     renderpass.set_color_target(Some(&out_color));
-    let src_depth = TextureRecipe::new_fullscreen_depth();
+    let src_depth =
+      TextureRecipe::new_fullscreen_depth().expect("failed to create the scene depth target");
     renderpass.set_depth_target(Some(&src_depth));
+    let picking =
+      PickingTarget::new(&mut renderpass, 1).expect("failed to create the picking target");
     // objects
     // shader を1000個作ってもコンパイルに時間はかかるがそれ以降はサクサク
     let shader = MayShader::new(CasualScene::shader());
     // 切り替えても速度はほぼ変わらず！
-    let material = PbrMaterial::new();
+    let mut material = PbrMaterial::new();
+    {
+      let mut attribute = material.write_attribute();
+      attribute.base_color_factor = Vec4::new(0.8, 0.5, 0.3, 1.0);
+      attribute.metallic_factor = 0.0;
+      attribute.roughness_factor = 0.5;
+    }
     let shape1 = Shape::new_cube();
     let shape2 = Shape::new_sphere(20, 20);
     let mut objects = Vec::new();
@@ -85,6 +107,9 @@ impl CasualScene {
           }
           object.pipeline.write().add(&material);
           object.pipeline.write().add(&shader);
+          let picking_id = PickingId::new(&object.pipeline);
+          object.pipeline.write().add(&picking_id);
+          object.picking_id = Some(picking_id);
           object.transform.set_translate(
             Vec3::new(
               x as f32 - (COUNT as f32) * 0.5,
@@ -105,7 +130,9 @@ impl CasualScene {
       objects,
       renderpass,
       camera,
+      _lights: lights,
       out_color,
+      picking,
     }
   }
 }
@@ -125,8 +152,11 @@ impl NeedUpdate for CasualScene {
       ),
       [true, false, true],
     );
+    self.picking.update();
     let f = Time::frame() as f32;
     for (i, object) in &mut self.objects.iter_mut().enumerate() {
+      let is_hovered = object.picking_id.as_ref().map(|p| p.is_hovered());
+      let hover_scale = if is_hovered == Some(true) { 1.3 } else { 1.0 };
       object.transform.set_rotation(
         Quat::from_rotation_y(f * 0.01 + (i as f32).sin() * 0.02 * f),
         Why::ByAnimation,
@@ -135,9 +165,10 @@ impl NeedUpdate for CasualScene {
         Vec3::new(0.0, 0.15 * (f * 0.013 + i as f32).sin(), 0.0),
         Why::ByAnimation,
       );
-      object
-        .transform
-        .set_scale(Vec3::ONE * (1.0 + 0.01 * (f * 0.1).sin()), Why::ByAnimation);
+      object.transform.set_scale(
+        Vec3::ONE * hover_scale * (1.0 + 0.01 * (f * 0.1).sin()),
+        Why::ByAnimation,
+      );
     }
     // self.objects.retain(|_| input::Mouse::state(input::MouseState::IsDown));
     // adjust viewport
@@ -207,7 +238,8 @@ impl CasualPostEffect {
     pipeline.add(&SOwner::new(TextureMapping::new(CasualPostEffectMapping {
       src_color: src_color.clone_reader(),
     })));
-    let out_color = TextureRecipe::new_fullscreen(PixelFormat::R8G8B8A8);
+    let out_color = TextureRecipe::new_fullscreen(PixelFormat::R8G8B8A8)
+      .expect("failed to create the post effect target");
     renderpass.set_color_target(Some(&out_color));
     renderpass.set_clear_color(Some(Vec4::new(0.3, 0.3, 0.3, 1.0)));
     renderpass.own_pipeline(pipeline);
@@ -0,0 +1,214 @@
+use super::*;
+use crate::prgl;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+// メイキング/リプレイ用のスクリーンショットと録画
+#[derive(Clone, Copy, PartialEq)]
+pub enum RecordFormat {
+  // 毎フレーム PNG にする
+  ImageSequence,
+  // MediaRecorder で録画する. captureStream は実時間で取るので固定ステップにはならない
+  WebM,
+}
+
+struct WebMRecorder {
+  recorder: web_sys::MediaRecorder,
+  chunks: SRc<SRwLock<Vec<web_sys::Blob>>>,
+  is_stopped: SRc<SRwLock<bool>>,
+  _on_data_available: Closure<dyn FnMut(web_sys::BlobEvent)>,
+  _on_stop: Closure<dyn FnMut()>,
+}
+impl WebMRecorder {
+  fn new(canvas: &web_sys::HtmlCanvasElement, fps: f64) -> Option<Self> {
+    let stream = canvas.capture_stream_with_frame_request_rate(fps).ok()?;
+    let mut options = web_sys::MediaRecorderOptions::new();
+    options.mime_type("video/webm");
+    let recorder =
+      web_sys::MediaRecorder::new_with_media_stream_and_media_recorder_options(&stream, &options)
+        .ok()?;
+    let chunks = SRc::new(SRwLock::new(Vec::new()));
+    let is_stopped = SRc::new(SRwLock::new(false));
+    let on_data_available = {
+      let chunks = chunks.clone();
+      Closure::wrap(Box::new(move |event: web_sys::BlobEvent| {
+        if let Some(data) = event.data() {
+          chunks.write().push(data);
+        }
+      }) as Box<dyn FnMut(_)>)
+    };
+    let on_stop = {
+      let is_stopped = is_stopped.clone();
+      Closure::wrap(Box::new(move || {
+        *is_stopped.write() = true;
+      }) as Box<dyn FnMut()>)
+    };
+    recorder.set_ondataavailable(Some(on_data_available.as_ref().unchecked_ref()));
+    recorder.set_onstop(Some(on_stop.as_ref().unchecked_ref()));
+    recorder.start().ok()?;
+    Some(Self {
+      recorder,
+      chunks,
+      is_stopped,
+      _on_data_available: on_data_available,
+      _on_stop: on_stop,
+    })
+  }
+  // stop 後 onstop が来るまでは None
+  fn take(&self) -> Option<web_sys::Blob> {
+    if !*self.is_stopped.read() {
+      return None;
+    }
+    let parts = js_sys::Array::new();
+    for chunk in self.chunks.read().iter() {
+      parts.push(chunk);
+    }
+    let mut options = web_sys::BlobPropertyBag::new();
+    options.type_("video/webm");
+    web_sys::Blob::new_with_blob_sequence_and_options(&parts, &options).ok()
+  }
+}
+
+// 無圧縮 PNG なので 1920x1080 で 1 枚 8MB ほど. 超えたら録画を止める
+pub const DEFAULT_MAX_RECORD_FRAMES: usize = 120;
+
+static INSTANCE: OnceCell<MRwLock<CaptureImpl>> = OnceCell::new();
+unsafe impl Send for CaptureImpl {}
+unsafe impl Sync for CaptureImpl {}
+pub struct CaptureImpl {
+  canvas: web_sys::HtmlCanvasElement,
+  is_screenshot_requested: bool,
+  screenshot: Option<Vec<u8>>,
+  recording: Option<RecordFormat>,
+  frames: Vec<Vec<u8>>,
+  max_frames: usize,
+  webm: Option<WebMRecorder>,
+}
+impl CaptureImpl {
+  pub fn initialize_global(canvas: &web_sys::HtmlCanvasElement) {
+    INSTANCE
+      .set(MRwLock::new(Self {
+        canvas: canvas.clone(),
+        is_screenshot_requested: false,
+        screenshot: None,
+        recording: None,
+        frames: Vec::new(),
+        max_frames: DEFAULT_MAX_RECORD_FRAMES,
+        webm: None,
+      }))
+      .ok();
+  }
+  pub fn read_global() -> MDerefable<'static, Self> {
+    INSTANCE
+      .get()
+      .expect("capture global is not initialized")
+      .read()
+  }
+  pub fn write_global() -> MDerefMutable<'static, Self> {
+    INSTANCE
+      .get()
+      .expect("capture global is not initialized")
+      .write()
+  }
+  // 描画直後に呼ぶ(合成後は既定の framebuffer が破棄される)
+  pub fn post_render(&mut self) {
+    if self.is_screenshot_requested {
+      self.is_screenshot_requested = false;
+      self.screenshot = self.capture_canvas();
+    }
+    if self.recording == Some(RecordFormat::ImageSequence) {
+      if self.frames.len() >= self.max_frames {
+        log::error(format!(
+          "recording stopped: too many frames (max: {})",
+          self.max_frames
+        ));
+        self.recording = None;
+        time::TimeImpl::write_global().set_fixed_step(None);
+        return;
+      }
+      if let Some(frame) = self.capture_canvas() {
+        self.frames.push(frame);
+      }
+    }
+  }
+  fn capture_canvas(&self) -> Option<Vec<u8>> {
+    let width = self.canvas.width() as i32;
+    let height = self.canvas.height() as i32;
+    let rect = math::Rect::new(0, 0, width, height);
+    let pixels = prgl::DefaultFramebuffer::read_pixels::<u8>(&rect)?;
+    encode_png(&pixels)
+  }
+}
+
+// GL は下の行から並ぶので反転してから書き出す
+fn encode_png(pixels: &prgl::Pixels<u8>) -> Option<Vec<u8>> {
+  let stride = pixels.width * 4;
+  let mut flipped = Vec::with_capacity(pixels.data.len());
+  for row in pixels.data.chunks(stride).rev() {
+    flipped.extend_from_slice(row);
+  }
+  prpr::image::encode_png_rgba8(pixels.width, pixels.height, &flipped)
+}
+
+pub struct Capture {}
+impl Capture {
+  // 次のフレームの描画後に撮られる
+  pub fn request_screenshot() {
+    CaptureImpl::write_global().is_screenshot_requested = true;
+  }
+  pub fn take_screenshot() -> Option<Vec<u8>> {
+    CaptureImpl::write_global().screenshot.take()
+  }
+  pub fn texture_to_png(texture: &prgl::Texture) -> Option<Vec<u8>> {
+    let rect = math::Rect::new(0, 0, texture.width() as i32, texture.height() as i32);
+    encode_png(&texture.read_pixels::<u8>(&rect)?)
+  }
+  // 録画中は Time が固定ステップで進む
+  pub fn start_recording(format: RecordFormat, fps: f64) {
+    let mut capture = CaptureImpl::write_global();
+    if capture.recording.is_some() {
+      log::error("already recording");
+      return;
+    }
+    capture.frames.clear();
+    capture.webm = None;
+    if format == RecordFormat::WebM {
+      capture.webm = WebMRecorder::new(&capture.canvas, fps);
+      if capture.webm.is_none() {
+        log::error("failed to start MediaRecorder");
+        return;
+      }
+    }
+    capture.recording = Some(format);
+    time::TimeImpl::write_global().set_fixed_step(Some(1000.0 / fps));
+  }
+  pub fn stop_recording() {
+    let mut capture = CaptureImpl::write_global();
+    if capture.recording.take().is_none() {
+      return;
+    }
+    if let Some(webm) = &capture.webm {
+      webm.recorder.stop().ok();
+    }
+    time::TimeImpl::write_global().set_fixed_step(None);
+  }
+  pub fn is_recording() -> bool {
+    CaptureImpl::read_global().recording.is_some()
+  }
+  // ImageSequence で溜める枚数の上限. 録画中に take_image_sequence で取り出せば溜まらない
+  pub fn set_max_frames(max_frames: usize) {
+    CaptureImpl::write_global().max_frames = max_frames;
+  }
+  // PNG の列. 録画中に呼ぶとそこまでの分を取り出す
+  pub fn take_image_sequence() -> Vec<Vec<u8>> {
+    std::mem::take(&mut CaptureImpl::write_global().frames)
+  }
+  pub fn take_webm() -> Option<web_sys::Blob> {
+    let mut capture = CaptureImpl::write_global();
+    let blob = capture.webm.as_ref()?.take();
+    if blob.is_some() {
+      capture.webm = None;
+    }
+    blob
+  }
+}
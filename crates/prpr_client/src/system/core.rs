@@ -28,8 +28,12 @@ impl Core {
     prgl::Instance::set(layers.main_3d_context());
     prhtml::Instance::set(layers.html_layer());
     prgl::RenderPassExecuterImpl::initialize_global();
+    prgl::UniformBufferArenaImpl::initialize_global();
+    prgl::PipelineStateCacheImpl::initialize_global();
+    prgl::PickingImpl::initialize_global();
     UpdaterImpl::initialize_global();
     EventHolderImpl::initialize_global(layers.html_layer());
+    CaptureImpl::initialize_global(layers.main_3d_layer());
     if config.use_fontawesome {
       js::html::add_stylesheet_link(
         "https://cdnjs.cloudflare.com/ajax/libs/font-awesome/6.0.0-beta3/css/all.min.css",
@@ -48,6 +52,9 @@ impl Core {
   }
   pub fn post_update(&mut self) {
     prgl::RenderPassExecuterImpl::write_global().execute();
+    if !prgl::Instance::is_context_lost() {
+      CaptureImpl::write_global().post_render();
+    }
     prgl::Instance::flush();
     time::TimeImpl::write_global().post_update();
   }
@@ -67,6 +67,9 @@ impl Layers {
   pub fn main_3d_context(&self) -> web_sys::WebGl2RenderingContext {
     crate::js::html::canvas::get_webgl2_context(&self.main_3d_layer)
   }
+  pub fn main_3d_layer(&self) -> &web_sys::HtmlCanvasElement {
+    &self.main_3d_layer
+  }
   pub fn html_layer(&self) -> &SRc<web_sys::HtmlDivElement> {
     &self.html_layer
   }
@@ -15,7 +15,9 @@ mod screen;
 pub use screen::*;
 mod why;
 pub use why::*;
+mod capture;
 pub mod input;
+pub use capture::*;
 
 pub fn run(f: fn(), config: Option<ClientRunConfig>) {
   let mut core = Core::new(config.unwrap_or_default());
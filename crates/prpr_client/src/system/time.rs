@@ -1,9 +1,18 @@
 use super::*;
 static INSTANCE: OnceCell<MRwLock<TimeImpl>> = OnceCell::new();
 const PROCESSED_TIME_AVG_COUNT: usize = 10;
+// 録画用. 実時間ではなくフレーム数で時間を進める
+struct FixedStep {
+  started_milli_sec: f64,
+  started_frame: i64,
+  delta_milli_sec: f64,
+}
 pub struct TimeImpl {
+  fixed_step: Option<FixedStep>,
   frame: i64,
   started_milli_sec: f64,
+  // fixed step を切り替えても now_milli_sec が連続するように実時間へ足す量
+  offset_milli_sec: f64,
   pre_now_milli_sec: f64,
   now_milli_sec: f64,
   processed_time_index: usize,
@@ -27,12 +36,14 @@ impl TimeImpl {
     INSTANCE
       .set(MRwLock::new(Self {
         started_milli_sec: js_sys::Date::now(),
+        offset_milli_sec: 0.0,
         pre_now_milli_sec: 0.0,
         now_milli_sec: 0.0,
         processed_time_index: 0,
         processed_milli_secs: [0.0; PROCESSED_TIME_AVG_COUNT],
         processed_milli_sec_avg: 0.0,
         frame: 0,
+        fixed_step: None,
       }))
       .ok();
   }
@@ -40,6 +51,26 @@ impl TimeImpl {
     self.frame += 1;
     self.pre_now_milli_sec = js_sys::Date::now() - self.started_milli_sec;
   }
+  pub fn set_fixed_step(&mut self, delta_milli_sec: Option<f64>) {
+    let now = self.now_milli_sec();
+    self.fixed_step = delta_milli_sec.map(|delta_milli_sec| FixedStep {
+      started_milli_sec: now,
+      started_frame: self.frame,
+      delta_milli_sec,
+    });
+    // 実時間に戻すときは切り替えた時点の時刻から続ける
+    if self.fixed_step.is_none() {
+      self.offset_milli_sec = now - self.now_milli_sec;
+    }
+  }
+  fn now_milli_sec(&self) -> f64 {
+    if let Some(step) = &self.fixed_step {
+      let frames = (self.frame - step.started_frame) as f64;
+      step.started_milli_sec + frames * step.delta_milli_sec
+    } else {
+      self.now_milli_sec + self.offset_milli_sec
+    }
+  }
   pub fn post_update(&mut self) {
     self.now_milli_sec = js_sys::Date::now() - self.started_milli_sec;
     self.processed_time_index = (self.processed_time_index + 1) % PROCESSED_TIME_AVG_COUNT;
@@ -55,7 +86,7 @@ impl Time {
     TimeImpl::read_global().frame
   }
   pub fn now_milli_sec() -> f64 {
-    TimeImpl::read_global().now_milli_sec
+    TimeImpl::read_global().now_milli_sec()
   }
   pub fn processed_milli_sec_avg() -> f64 {
     TimeImpl::read_global().processed_milli_sec_avg